@@ -1,5 +1,8 @@
 fn main() {
     println!("cargo:rerun-if-changed=binding.c");
+    if std::env::var_os("CARGO_FEATURE_RUST_WITNESS_READER").is_some() {
+        return;
+    }
 
     let clang = match std::env::var_os("TARGET_CC") {
         Some(val) => val,
@@ -0,0 +1,74 @@
+/// Merkle proof is read from the first witness of the script group.
+pub const WITNESS_LOCATION_GROUP_INPUT: u8 = 0;
+/// Merkle proof is read from the witness at an absolute index, following
+/// in script args as u32 in little endian.
+pub const WITNESS_LOCATION_INDEX: u8 = 1;
+/// Merkle proof is read from the first witness beyond transaction inputs.
+pub const WITNESS_LOCATION_BEYOND_INPUTS: u8 = 2;
+
+/// Location of the witness containing the merkle proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WitnessLocation {
+    GroupInput,
+    Index(usize),
+    BeyondInputs,
+}
+
+/// Zero lock script args. All fields are optional, when args end before a
+/// field, the field's default value is used:
+///
+/// * Header quorum(u8, defaults to 1): number of distinct header deps named
+///   in the witness, whose block extensions must all carry the same merkle root
+/// * Witness location(u8, defaults to WITNESS_LOCATION_GROUP_INPUT): where
+///   the witness containing the merkle proof is placed. WITNESS_LOCATION_INDEX
+///   is followed by the absolute witness index(u32, little endian). Unless
+///   the proof ends up in zero lock's own witness, that witness must be empty
+#[derive(Debug)]
+pub struct ScriptArgs {
+    pub header_quorum: u8,
+    pub witness_location: WitnessLocation,
+}
+
+impl Default for ScriptArgs {
+    fn default() -> Self {
+        Self {
+            header_quorum: 1,
+            witness_location: WitnessLocation::GroupInput,
+        }
+    }
+}
+
+impl ScriptArgs {
+    /// Parses script args, returns None when args are malformed.
+    pub fn parse(args: &[u8]) -> Option<Self> {
+        let mut script_args = Self::default();
+        let Some((header_quorum, rest)) = args.split_first() else {
+            return Some(script_args);
+        };
+        if *header_quorum == 0 {
+            return None;
+        }
+        script_args.header_quorum = *header_quorum;
+        let Some((witness_location, rest)) = rest.split_first() else {
+            return Some(script_args);
+        };
+        let (witness_location, rest) = match *witness_location {
+            WITNESS_LOCATION_GROUP_INPUT => (WitnessLocation::GroupInput, rest),
+            WITNESS_LOCATION_INDEX if rest.len() >= 4 => {
+                let mut index = [0u8; 4];
+                index.copy_from_slice(&rest[..4]);
+                (
+                    WitnessLocation::Index(u32::from_le_bytes(index) as usize),
+                    &rest[4..],
+                )
+            }
+            WITNESS_LOCATION_BEYOND_INPUTS => (WitnessLocation::BeyondInputs, rest),
+            _ => return None,
+        };
+        if !rest.is_empty() {
+            return None;
+        }
+        script_args.witness_location = witness_location;
+        Some(script_args)
+    }
+}
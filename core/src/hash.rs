@@ -0,0 +1,74 @@
+use super::{leaf::SHA256_LEAF_VERSION, Data};
+use blake2b_ref::{Blake2b, Blake2bBuilder};
+use merkle_cbt::merkle_tree::Merge;
+use sha2::{Digest, Sha256};
+
+pub fn new_blake2b() -> Blake2b {
+    Blake2bBuilder::new(32)
+        .personal(b"ckb-default-hash")
+        .build()
+}
+
+/// Hash function for both the leaf and internal nodes of the merkle tree,
+/// selected by leaf version.
+pub enum TreeHasher {
+    Blake2b(Blake2b),
+    Sha256(Sha256),
+}
+
+impl TreeHasher {
+    pub fn for_leaf_version(leaf_version: u8) -> Self {
+        match leaf_version {
+            SHA256_LEAF_VERSION => TreeHasher::Sha256(Sha256::new()),
+            _ => TreeHasher::Blake2b(new_blake2b()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            TreeHasher::Blake2b(hasher) => hasher.update(data),
+            TreeHasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    pub fn finalize(self) -> Data {
+        let mut hash = [0u8; 32];
+        match self {
+            TreeHasher::Blake2b(hasher) => hasher.finalize(&mut hash[..]),
+            TreeHasher::Sha256(hasher) => hash.copy_from_slice(&hasher.finalize()),
+        }
+        Data::new(hash)
+    }
+}
+
+#[derive(Debug)]
+pub struct Blake2bHash;
+
+impl Merge for Blake2bHash {
+    type Item = Data;
+
+    fn merge(lhs: &Self::Item, rhs: &Self::Item) -> Self::Item {
+        let mut hasher = new_blake2b();
+        hasher.update(&lhs.as_bytes());
+        hasher.update(&rhs.as_bytes());
+        let mut hash = [0u8; 32];
+        hasher.finalize(&mut hash[..]);
+        Data::new(hash)
+    }
+}
+
+#[derive(Debug)]
+pub struct Sha256Hash;
+
+impl Merge for Sha256Hash {
+    type Item = Data;
+
+    fn merge(lhs: &Self::Item, rhs: &Self::Item) -> Self::Item {
+        let mut hasher = Sha256::new();
+        hasher.update(lhs.as_bytes());
+        hasher.update(rhs.as_bytes());
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&hasher.finalize());
+        Data::new(hash)
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{
+    debug,
+    hash::new_blake2b,
+    verifier::{DataSource, Error, SourceError},
+};
+
+/// Original leaf layout, used when the merkle proof has no leaf trailer.
+pub const LEAF_VERSION: u8 = 1;
+/// Extended leaf layout, the merkle proof is followed by a leaf trailer
+/// containing leaf options, which are committed in the leaf as well.
+pub const EXTENDED_LEAF_VERSION: u8 = 2;
+/// Extended leaf layout, using SHA-256 for the leaf and internal nodes of
+/// the merkle tree.
+pub const SHA256_LEAF_VERSION: u8 = 3;
+
+/// The output cell is a dep group, some of its entries are only known when
+/// the upgrade transaction is assembled.
+pub const LEAF_OPTION_DEP_GROUP: u8 = 1;
+/// Cells that must be present as cell deps in the upgrade transaction, for
+/// example, a library the upgraded contract depends on.
+pub const LEAF_OPTION_REQUIRED_CELL_DEPS: u8 = 2;
+/// The upgraded cell leaves zero lock, the output cell uses the lock script
+/// whose hash is the option value.
+pub const LEAF_OPTION_RELEASE: u8 = 3;
+/// Witness remainder fields designated by the option value must contain
+/// well-formed upgrade metadata, see schemas/upgrade_metadata.mol.
+pub const LEAF_OPTION_METADATA: u8 = 4;
+
+pub const METADATA_INPUT_TYPE: u8 = 1;
+pub const METADATA_OUTPUT_TYPE: u8 = 2;
+
+pub const CELL_DEP_DATA_HASH: u8 = 0;
+pub const CELL_DEP_TYPE_HASH: u8 = 1;
+
+pub const OUT_POINT_SIZE: usize = 36;
+/// OutPoint(36 bytes), dep type(u8)
+pub const CELL_DEP_SIZE: usize = OUT_POINT_SIZE + 1;
+pub const DEP_TYPE_CODE: u8 = 0;
+/// Entry index(u32), cell dep index(u32), hash kind(u8), hash(32 bytes)
+pub const DEP_GROUP_PLACEHOLDER_SIZE: usize = 41;
+/// Hash kind(u8), hash(32 bytes)
+pub const REQUIRED_CELL_DEP_SIZE: usize = 33;
+
+pub fn read_u32(data: &[u8]) -> u32 {
+    let mut t = [0u8; 4];
+    t.copy_from_slice(&data[0..4]);
+    u32::from_le_bytes(t)
+}
+
+/// Leaf options are encoded as a series of (tag, length, value) records,
+/// where tag is a single byte, length is a little-endian u32. Records must
+/// be sorted by tags, each tag can only appear once.
+#[derive(Default)]
+pub struct LeafOptions<'a> {
+    pub dep_group: Option<&'a [u8]>,
+    pub required_cell_deps: Option<&'a [u8]>,
+    pub release: Option<&'a [u8; 32]>,
+    pub metadata: Option<u8>,
+}
+
+impl<'a> LeafOptions<'a> {
+    pub fn parse(params: &'a [u8]) -> Option<Self> {
+        let mut options = Self::default();
+        let mut last_tag: Option<u8> = None;
+        let mut rest = params;
+        while !rest.is_empty() {
+            if rest.len() < 5 {
+                debug!("Leaf option is truncated!");
+                return None;
+            }
+            let tag = rest[0];
+            let length = read_u32(&rest[1..5]) as usize;
+            if rest.len() - 5 < length {
+                debug!("Leaf option {} is truncated!", tag);
+                return None;
+            }
+            if last_tag.is_some_and(|last_tag| last_tag >= tag) {
+                debug!("Leaf options must be sorted by tags!");
+                return None;
+            }
+            let value = &rest[5..5 + length];
+            match tag {
+                LEAF_OPTION_DEP_GROUP => options.dep_group = Some(value),
+                LEAF_OPTION_REQUIRED_CELL_DEPS => options.required_cell_deps = Some(value),
+                LEAF_OPTION_RELEASE => match value.try_into() {
+                    Ok(lock_hash) => options.release = Some(lock_hash),
+                    Err(_) => {
+                        debug!("Invalid release lock hash length: {}", value.len());
+                        return None;
+                    }
+                },
+                LEAF_OPTION_METADATA => match value {
+                    [fields]
+                        if *fields != 0
+                            && *fields & !(METADATA_INPUT_TYPE | METADATA_OUTPUT_TYPE) == 0 =>
+                    {
+                        options.metadata = Some(*fields)
+                    }
+                    _ => {
+                        debug!("Invalid metadata option: {:?}", value);
+                        return None;
+                    }
+                },
+                _ => {
+                    debug!("Unknown leaf option: {}", tag);
+                    return None;
+                }
+            }
+            last_tag = Some(tag);
+            rest = &rest[5 + length..];
+        }
+        Some(options)
+    }
+}
+
+/// Calculates the data hash of a dep group output cell, committed in the leaf.
+///
+/// A dep group option consists of placeholders, each placeholder names an
+/// entry in the OutPointVec data of the output cell, which must be the out
+/// point of the designated cell dep in current transaction. The cell dep
+/// must also have the expected data hash or type hash. Placeholder entries
+/// are zeroed before hashing the data, all other entries are committed as is.
+pub fn dep_group_data_hash<S: DataSource>(
+    source: &S,
+    option: &[u8],
+    output_index: usize,
+) -> Result<[u8; 32], Error> {
+    let mut data = source.output_data(output_index)?;
+    if data.len() < 4 || data.len() != 4 + read_u32(&data) as usize * OUT_POINT_SIZE {
+        debug!("Output cell data is not a valid dep group!");
+        return Err(Error::InvalidDepGroupData { output_index });
+    }
+    let entries = (data.len() - 4) / OUT_POINT_SIZE;
+    if option.len() % DEP_GROUP_PLACEHOLDER_SIZE != 0 {
+        debug!("Invalid dep group option length: {}", option.len());
+        return Err(Error::InvalidPlaceholderLength(option.len()));
+    }
+    let mut last_entry_index: Option<usize> = None;
+    for placeholder in option.chunks_exact(DEP_GROUP_PLACEHOLDER_SIZE) {
+        let entry_index = read_u32(&placeholder[0..4]) as usize;
+        let cell_dep_index = read_u32(&placeholder[4..8]) as usize;
+        if entry_index >= entries
+            || last_entry_index.is_some_and(|last_entry_index| last_entry_index >= entry_index)
+        {
+            debug!("Invalid dep group entry index: {}", entry_index);
+            return Err(Error::InvalidEntryIndex(entry_index));
+        }
+        let start = 4 + entry_index * OUT_POINT_SIZE;
+        let entry = &mut data[start..start + OUT_POINT_SIZE];
+        let entry_mismatch = Error::EntryMismatch {
+            entry_index,
+            cell_dep_index,
+        };
+        match load_code_cell_dep_out_point(source, cell_dep_index) {
+            Ok(Some(out_point)) if entry[..] == out_point[..] => (),
+            Ok(_) | Err(SourceError::IndexOutOfBound) => {
+                debug!(
+                    "Dep group entry {} does not match cell dep {}!",
+                    entry_index, cell_dep_index
+                );
+                return Err(entry_mismatch);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        let hash = &placeholder[9..DEP_GROUP_PLACEHOLDER_SIZE];
+        if !check_cell_dep_hash(source, cell_dep_index, placeholder[8], hash)? {
+            debug!("Cell dep {} has unexpected hash!", cell_dep_index);
+            return Err(Error::CellDepHashMismatch {
+                cell_dep_index,
+                expected: hash.try_into().expect("hash"),
+            });
+        }
+        entry.fill(0);
+        last_entry_index = Some(entry_index);
+    }
+
+    let mut hasher = new_blake2b();
+    hasher.update(&data);
+    let mut hash = [0u8; 32];
+    hasher.finalize(&mut hash[..]);
+    Ok(hash)
+}
+
+/// Checks that each cell dep required by the leaf is present in current
+/// transaction. Cell deps expanded from dep groups are also considered.
+pub fn check_required_cell_deps<S: DataSource>(source: &S, option: &[u8]) -> Result<(), Error> {
+    if option.len() % REQUIRED_CELL_DEP_SIZE != 0 {
+        debug!("Invalid required cell deps option length: {}", option.len());
+        return Err(Error::InvalidRequiredCellDeps);
+    }
+    for required in option.chunks_exact(REQUIRED_CELL_DEP_SIZE) {
+        let hash = &required[1..REQUIRED_CELL_DEP_SIZE];
+        let mut i = 0;
+        loop {
+            match check_cell_dep_hash(source, i, required[0], hash) {
+                Ok(true) => break,
+                Ok(false) => i += 1,
+                Err(Error::Source(SourceError::IndexOutOfBound)) => {
+                    debug!("Required cell dep is missing: {:?}", required);
+                    return Err(Error::RequiredCellDepMissing {
+                        kind: required[0],
+                        hash: hash.try_into().expect("hash"),
+                    });
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn check_cell_dep_hash<S: DataSource>(
+    source: &S,
+    index: usize,
+    kind: u8,
+    hash: &[u8],
+) -> Result<bool, Error> {
+    match kind {
+        CELL_DEP_DATA_HASH => Ok(source.cell_dep_data_hash(index)?[..] == hash[..]),
+        CELL_DEP_TYPE_HASH => Ok(source
+            .cell_dep_type_hash(index)?
+            .is_some_and(|type_hash| type_hash[..] == hash[..])),
+        _ => {
+            debug!("Unknown cell dep hash kind: {}", kind);
+            Err(Error::UnknownHashKind(kind))
+        }
+    }
+}
+
+/// Returns the out point of a cell dep in the raw transaction. Cell deps
+/// accessible via `DataSource::cell_dep_data_hash` are expanded from dep
+/// groups, so the cell dep, as well as all cell deps before it, must use
+/// DepType::Code, otherwise None is returned. This way the same index points
+/// to the same cell in expanded cell deps.
+fn load_code_cell_dep_out_point<S: DataSource>(
+    source: &S,
+    index: usize,
+) -> Result<Option<[u8; OUT_POINT_SIZE]>, SourceError> {
+    let mut cell_dep = [0u8; CELL_DEP_SIZE];
+    for i in 0..=index {
+        cell_dep = source.raw_cell_dep(i)?;
+        if cell_dep[OUT_POINT_SIZE] != DEP_TYPE_CODE {
+            debug!("Cell dep {} is not using DepType::Code!", i);
+            return Ok(None);
+        }
+    }
+    let mut out_point = [0u8; OUT_POINT_SIZE];
+    out_point.copy_from_slice(&cell_dep[..OUT_POINT_SIZE]);
+    Ok(Some(out_point))
+}
@@ -0,0 +1,59 @@
+//! Syscall free parts of zero lock, shared by the on-chain script and host
+//! side tooling: script args, merkle proof parsing, leaf options, hashing,
+//! and the verification flow itself, over a `verifier::DataSource`.
+#![no_std]
+
+extern crate alloc;
+
+pub mod args;
+pub mod hash;
+pub mod leaf;
+pub mod metadata;
+pub mod proof_pointer;
+pub mod proof_reader;
+pub mod verifier;
+pub mod witness_args;
+pub mod witness_reader;
+
+#[cfg(feature = "ckb-std")]
+pub(crate) use ckb_std::debug;
+
+#[cfg(not(feature = "ckb-std"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        let _ = core::format_args!($($arg)*);
+    };
+}
+#[cfg(not(feature = "ckb-std"))]
+pub(crate) use debug;
+
+pub const ERROR_CODE_WITNESS_READER: i32 = -70;
+pub const ERROR_CODE_PROOF_READER: i32 = -71;
+
+/// When used as header index in the witness, zero lock tries header deps in
+/// order, looking for the one carrying the merkle root.
+pub const DISCOVER_HEADER_INDEX: u32 = u32::MAX;
+/// Upper bound on the number of header deps tried in header discovery.
+pub const MAX_DISCOVERED_HEADER_DEPS: usize = 64;
+/// Offset of the merkle root in block extension.
+pub const MERKLE_ROOT_OFFSET: usize = 128;
+
+#[derive(Debug, Default, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct Data([u8; 32]);
+
+impl Data {
+    pub fn from_slice(data: &[u8]) -> Self {
+        assert_eq!(data.len(), 32);
+        let mut d = [0u8; 32];
+        d.copy_from_slice(data);
+        Self(d)
+    }
+
+    pub fn new(data: [u8; 32]) -> Self {
+        Self(data)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0[..]
+    }
+}
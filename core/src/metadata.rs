@@ -0,0 +1,41 @@
+// Verifies upgrade metadata as defined in schemas/upgrade_metadata.mol.
+// Only well-formedness is checked, zero lock does not interpret the fields.
+
+const UPGRADE_METADATA_FIELDS: usize = 4;
+const SEMANTIC_VERSION_SIZE: usize = 12;
+const BYTE32_SIZE: usize = 32;
+
+fn read_u32(data: &[u8], offset: usize) -> usize {
+    let mut t = [0u8; 4];
+    t.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(t) as usize
+}
+
+fn verify_bytes(data: &[u8]) -> bool {
+    data.len() >= 4 && read_u32(data, 0) == data.len() - 4
+}
+
+/// Checks that data is an UpgradeMetadata molecule table, additional fields
+/// are not allowed.
+pub fn verify_upgrade_metadata(data: &[u8]) -> bool {
+    let header_size = 4 * (UPGRADE_METADATA_FIELDS + 1);
+    if data.len() < header_size
+        || read_u32(data, 0) != data.len()
+        || read_u32(data, 4) != header_size
+    {
+        return false;
+    }
+    let mut offsets = [0usize; UPGRADE_METADATA_FIELDS + 1];
+    for (i, offset) in offsets.iter_mut().take(UPGRADE_METADATA_FIELDS).enumerate() {
+        *offset = read_u32(data, 4 * (i + 1));
+    }
+    offsets[UPGRADE_METADATA_FIELDS] = data.len();
+    if offsets.windows(2).any(|w| w[0] > w[1]) {
+        return false;
+    }
+    let field = |i: usize| &data[offsets[i]..offsets[i + 1]];
+    verify_bytes(field(0))
+        && verify_bytes(field(1))
+        && field(2).len() == SEMANTIC_VERSION_SIZE
+        && field(3).len() == BYTE32_SIZE
+}
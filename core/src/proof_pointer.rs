@@ -0,0 +1,74 @@
+use super::{debug, proof_reader::ProofVisitor, verifier::DataSource};
+use core::cmp;
+
+/// A witness lock of exactly this size is a proof pointer, instead of the
+/// merkle proof itself. A merkle proof takes at least 16 bytes, so the two
+/// can never be confused.
+pub const PROOF_POINTER_SIZE: usize = 12;
+
+/// Proof pointer names a range in the data of a cell dep, containing the
+/// merkle proof. It uses concatenation of the following fields, each as a
+/// little-endian u32:
+///
+/// * Index of the cell dep
+/// * Offset of the merkle proof in cell data
+/// * Length of the merkle proof
+///
+/// This way, large proofs, or proofs shared by many upgrade transactions,
+/// can be published once in a proof cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofPointer {
+    pub cell_dep_index: usize,
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl ProofPointer {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() != PROOF_POINTER_SIZE {
+            debug!("Invalid proof pointer length: {}", data.len());
+            return None;
+        }
+        let read = |i: usize| {
+            let mut t = [0u8; 4];
+            t.copy_from_slice(&data[i * 4..i * 4 + 4]);
+            u32::from_le_bytes(t) as usize
+        };
+        Some(Self {
+            cell_dep_index: read(0),
+            offset: read(1),
+            length: read(2),
+        })
+    }
+
+    /// Streams the designated range of cell dep data into the proof visitor.
+    /// On failure, the reason is returned.
+    pub fn load<S: DataSource>(
+        &self,
+        source: &S,
+        proof: &mut ProofVisitor,
+    ) -> Result<(), &'static str> {
+        let mut loaded = 0;
+        let mut buf = [0u8; 4096];
+        while loaded < self.length {
+            let len = cmp::min(buf.len(), self.length - loaded);
+            match source.load_cell_dep_data(
+                &mut buf[..len],
+                self.offset + loaded,
+                self.cell_dep_index,
+            ) {
+                Ok(actual_loaded_len) if actual_loaded_len == len => (),
+                e => {
+                    debug!("Error loading proof from cell dep: {:?}", e);
+                    let _ = e;
+                    return Err("proof pointer names a missing range");
+                }
+            }
+            if proof.process(&buf[..len]) != 0 {
+                return Err("merkle proof is malformed");
+            }
+            loaded += len;
+        }
+        Ok(())
+    }
+}
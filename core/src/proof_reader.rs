@@ -1,6 +1,9 @@
-use super::{Data, ERROR_CODE_PROOF_READER};
+use super::{
+    debug,
+    leaf::{EXTENDED_LEAF_VERSION, LEAF_VERSION, SHA256_LEAF_VERSION},
+    Data, ERROR_CODE_PROOF_READER,
+};
 use alloc::vec::Vec;
-use ckb_std::debug;
 use core::cmp;
 use merkle_cbt::{merkle_tree::Merge, MerkleProof};
 
@@ -53,6 +56,11 @@ enum ReadState {
     Indices,
     LemmasLength,
     Lemmas,
+    // Merkle proof is fully parsed at this point, an optional leaf trailer
+    // might follow.
+    LeafVersion,
+    LeafParamsLength,
+    LeafParams,
     Completed,
 }
 
@@ -63,32 +71,56 @@ pub struct ProofVisitor {
 
     buffer: FixedBuffer,
 
-    header_index: u32,
+    header_count: usize,
+    header_indices: Vec<u32>,
     indices: Vec<u32>,
     lemmas: Vec<Data>,
+    leaf_version: u8,
+    leaf_params: Vec<u8>,
 }
 
 impl Default for ProofVisitor {
     fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl ProofVisitor {
+    /// Creates a visitor expecting the proof to start with `header_count`
+    /// header indices.
+    pub fn new(header_count: usize) -> Self {
         Self {
             state: ReadState::HeaderIndex,
             total: 0,
             buffer: FixedBuffer::default(),
-            header_index: u32::MAX,
+            header_count,
+            header_indices: Vec::with_capacity(header_count),
             indices: Vec::new(),
             lemmas: Vec::new(),
+            leaf_version: LEAF_VERSION,
+            leaf_params: Vec::new(),
         }
     }
-}
 
-impl ProofVisitor {
-    pub fn build<M: Merge<Item = Data>>(self) -> Option<(u32, MerkleProof<Data, M>)> {
-        if self.state != ReadState::Completed {
+    pub fn completed(&self) -> bool {
+        self.state == ReadState::LeafVersion || self.state == ReadState::Completed
+    }
+
+    pub fn leaf_version(&self) -> u8 {
+        self.leaf_version
+    }
+
+    pub fn leaf_params(&self) -> &[u8] {
+        &self.leaf_params
+    }
+
+    pub fn build<M: Merge<Item = Data>>(self) -> Option<(Vec<u32>, MerkleProof<Data, M>)> {
+        if !self.completed() {
             debug!("Witness does not provide a complete merkle proof!");
             return None;
         }
         Some((
-            self.header_index,
+            self.header_indices,
             MerkleProof::new(self.indices, self.lemmas),
         ))
     }
@@ -103,8 +135,10 @@ impl ProofVisitor {
                         let mut t = [0u8; 4];
                         t.copy_from_slice(&data[0..4]);
                         self.buffer.consume(4);
-                        self.header_index = u32::from_le_bytes(t);
-                        self.state = ReadState::IndicesLength;
+                        self.header_indices.push(u32::from_le_bytes(t));
+                        if self.header_indices.len() >= self.header_count {
+                            self.state = ReadState::IndicesLength;
+                        }
                         changed = true;
                     }
                 }
@@ -144,7 +178,7 @@ impl ProofVisitor {
                 }
                 ReadState::Lemmas => {
                     if self.lemmas.len() >= self.total {
-                        self.state = ReadState::Completed;
+                        self.state = ReadState::LeafVersion;
                         changed = true;
                     } else if data.len() >= 32 {
                         self.lemmas.push(Data::from_slice(&data[0..32]));
@@ -152,6 +186,41 @@ impl ProofVisitor {
                         changed = true;
                     }
                 }
+                ReadState::LeafVersion => {
+                    if !data.is_empty() {
+                        // Leaf version 1 is denoted by the absence of a leaf trailer,
+                        // only extended leaves use one.
+                        if data[0] != EXTENDED_LEAF_VERSION && data[0] != SHA256_LEAF_VERSION {
+                            debug!("Unsupported leaf version in trailer: {}", data[0]);
+                            return ERROR_CODE_PROOF_READER;
+                        }
+                        self.leaf_version = data[0];
+                        self.buffer.consume(1);
+                        self.state = ReadState::LeafParamsLength;
+                        changed = true;
+                    }
+                }
+                ReadState::LeafParamsLength => {
+                    if data.len() >= 4 {
+                        let mut t = [0u8; 4];
+                        t.copy_from_slice(&data[0..4]);
+                        self.buffer.consume(4);
+                        self.total = u32::from_le_bytes(t) as usize;
+                        self.state = ReadState::LeafParams;
+                        changed = true;
+                    }
+                }
+                ReadState::LeafParams => {
+                    if self.leaf_params.len() >= self.total {
+                        self.state = ReadState::Completed;
+                        changed = true;
+                    } else if !data.is_empty() {
+                        let len = cmp::min(self.total - self.leaf_params.len(), data.len());
+                        self.leaf_params.extend_from_slice(&data[..len]);
+                        self.buffer.consume(len);
+                        changed = true;
+                    }
+                }
                 ReadState::Completed => break,
             }
             if !changed {
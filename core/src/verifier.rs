@@ -0,0 +1,590 @@
+//! Verification flow of zero lock. Transaction data is accessed via the
+//! `DataSource` trait: on chain, it is implemented via syscalls, host side
+//! tooling implements it over a resolved or mock transaction, so both run
+//! exactly the same checks.
+
+use super::{
+    args::{ScriptArgs, WitnessLocation},
+    debug,
+    hash::{Blake2bHash, Sha256Hash, TreeHasher},
+    leaf::{
+        self, LeafOptions, CELL_DEP_SIZE, EXTENDED_LEAF_VERSION, LEAF_VERSION, OUT_POINT_SIZE,
+        SHA256_LEAF_VERSION,
+    },
+    metadata,
+    proof_reader::ProofVisitor,
+    witness_args::WitnessArgsVisitor,
+    witness_reader::{self, LeafHasher},
+    Data, DISCOVER_HEADER_INDEX, MAX_DISCOVERED_HEADER_DEPS, MERKLE_ROOT_OFFSET,
+};
+use alloc::{boxed::Box, vec::Vec};
+use merkle_cbt::merkle_tree::Merge;
+
+/// Error accessing transaction data, mirroring the syscall errors zero lock
+/// handles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceError {
+    IndexOutOfBound,
+    ItemMissing,
+    /// Any other failure, zero lock aborts on it.
+    Other,
+}
+
+/// Witness named by its source, as in syscalls: `GroupInput` indexes the
+/// witnesses of the script group, `Input` indexes all witnesses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WitnessSource {
+    GroupInput(usize),
+    Input(usize),
+}
+
+/// Transaction data needed by zero lock, as seen by the running script.
+/// Outputs and cell deps are indexed as in the transaction, cell deps are
+/// expanded from dep groups unless noted otherwise.
+///
+/// Methods taking a `buf` and an `offset` use partial loading: data from
+/// `offset` is copied into `buf`, returning the number of bytes copied.
+pub trait DataSource {
+    /// Args of the running script.
+    fn script_args(&self) -> Result<Vec<u8>, SourceError>;
+    /// Hash of the running script.
+    fn script_hash(&self) -> Result<[u8; 32], SourceError>;
+    /// Out point of an input in the script group.
+    fn group_input_out_point(&self, index: usize) -> Result<[u8; OUT_POINT_SIZE], SourceError>;
+    fn input_count(&self) -> Result<usize, SourceError>;
+    fn input_lock_hash(&self, index: usize) -> Result<[u8; 32], SourceError>;
+    fn output_lock_hash(&self, index: usize) -> Result<[u8; 32], SourceError>;
+    fn output_data_hash(&self, index: usize) -> Result<[u8; 32], SourceError>;
+    fn output_data(&self, index: usize) -> Result<Vec<u8>, SourceError>;
+    /// Feeds the serialized CellOutput of an output to `f`, in one or more
+    /// pieces.
+    fn output_cell(&self, index: usize, f: &mut dyn FnMut(&[u8])) -> Result<(), SourceError>;
+    fn cell_dep_data_hash(&self, index: usize) -> Result<[u8; 32], SourceError>;
+    fn cell_dep_type_hash(&self, index: usize) -> Result<Option<[u8; 32]>, SourceError>;
+    fn load_cell_dep_data(
+        &self,
+        buf: &mut [u8],
+        offset: usize,
+        index: usize,
+    ) -> Result<usize, SourceError>;
+    /// Serialized CellDep at `index` of the raw transaction, cell deps are
+    /// not expanded here.
+    fn raw_cell_dep(&self, index: usize) -> Result<[u8; CELL_DEP_SIZE], SourceError>;
+    /// Block extension of a header dep, ItemMissing is returned when the
+    /// header has no extension.
+    fn load_block_extension(
+        &self,
+        buf: &mut [u8],
+        offset: usize,
+        index: usize,
+    ) -> Result<usize, SourceError>;
+    /// Length of a witness, IndexOutOfBound is returned when the witness is
+    /// missing.
+    fn witness_len(&self, witness: WitnessSource) -> Result<usize, SourceError>;
+    /// Streams a WitnessArgs to the visitor, returning the non-zero value
+    /// returned by the visitor, or a non-zero value when the witness is
+    /// missing or is not a valid WitnessArgs.
+    fn read_witness<V: WitnessArgsVisitor>(&self, witness: WitnessSource, visitor: &mut V) -> i32;
+}
+
+/// Reason zero lock rejects an upgrade. See `code` for the error code
+/// printed in debug output, zero lock always exits with -61.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Source(SourceError),
+    MultipleInputs,
+    MultipleOutputs {
+        lock_hash: [u8; 32],
+        first: usize,
+        second: usize,
+    },
+    MissingOutput,
+    ShortExtension(u32),
+    MissingExtension(u32),
+    RootMismatch {
+        header_index: u32,
+        header_root: Data,
+        proof_root: Data,
+    },
+    DiscoveryFailed {
+        matched: usize,
+        required: usize,
+        proof_root: Data,
+    },
+    MissingWitness(WitnessSource),
+    InvalidWitness {
+        witness: WitnessSource,
+        reason: &'static str,
+    },
+    IncompleteProof,
+    NoRoot,
+    UnsupportedLeafVersion(u8),
+    InvalidLeafOptions,
+    InvalidDepGroupData {
+        output_index: usize,
+    },
+    InvalidPlaceholderLength(usize),
+    InvalidEntryIndex(usize),
+    UnknownHashKind(u8),
+    EntryMismatch {
+        entry_index: usize,
+        cell_dep_index: usize,
+    },
+    CellDepHashMismatch {
+        cell_dep_index: usize,
+        expected: [u8; 32],
+    },
+    InvalidRequiredCellDeps,
+    RequiredCellDepMissing {
+        kind: u8,
+        hash: [u8; 32],
+    },
+    InvalidArgs,
+    DuplicateHeaderIndex(u32),
+    RootsDiffer {
+        header_index: u32,
+        root: Data,
+        expected: Data,
+    },
+    DiscoveryMixed,
+    ReleasedCellInZeroLock {
+        output_index: usize,
+    },
+    /// Index of the witness remainder field, 0 for input_type, 1 for
+    /// output_type.
+    InvalidMetadata(usize),
+    WitnessOfOtherInput(usize),
+    GroupWitnessNotEmpty,
+}
+
+impl Error {
+    /// Error code printed by zero lock in debug output, None for errors
+    /// accessing transaction data.
+    pub fn code(&self) -> Option<i8> {
+        let code = match self {
+            Error::Source(_) => return None,
+            Error::MultipleInputs => 1,
+            Error::MultipleOutputs { .. } => 2,
+            Error::MissingOutput => 4,
+            Error::ShortExtension(_) => 5,
+            Error::MissingExtension(_) => 6,
+            Error::RootMismatch { .. } | Error::DiscoveryFailed { .. } => 8,
+            Error::MissingWitness(_) | Error::InvalidWitness { .. } => 9,
+            Error::IncompleteProof => 10,
+            Error::NoRoot => 11,
+            Error::UnsupportedLeafVersion(_) | Error::InvalidLeafOptions => 12,
+            Error::InvalidDepGroupData { .. } => 13,
+            Error::InvalidPlaceholderLength(_)
+            | Error::InvalidEntryIndex(_)
+            | Error::UnknownHashKind(_) => 14,
+            Error::EntryMismatch { .. } => 15,
+            Error::CellDepHashMismatch { .. } => 16,
+            Error::InvalidRequiredCellDeps => 17,
+            Error::RequiredCellDepMissing { .. } => 18,
+            Error::InvalidArgs => 19,
+            Error::DuplicateHeaderIndex(_) => 20,
+            Error::RootsDiffer { .. } => 21,
+            Error::DiscoveryMixed => 22,
+            Error::ReleasedCellInZeroLock { .. } => 23,
+            Error::InvalidMetadata(_) => 24,
+            Error::WitnessOfOtherInput(_) => 25,
+            Error::GroupWitnessNotEmpty => 26,
+        };
+        Some(code)
+    }
+}
+
+impl From<SourceError> for Error {
+    fn from(e: SourceError) -> Self {
+        Error::Source(e)
+    }
+}
+
+/// Details of an upgrade that passes verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verified {
+    /// Witness containing the merkle proof.
+    pub witness: WitnessSource,
+    pub output_index: usize,
+    pub leaf_version: u8,
+    pub leaf: Data,
+    pub root: Data,
+    /// Header deps carrying the merkle root.
+    pub header_indices: Vec<u32>,
+}
+
+pub fn verify<S: DataSource>(source: &S) -> Result<Verified, Error> {
+    let args = source.script_args()?;
+    let script_args = ScriptArgs::parse(&args).ok_or_else(|| {
+        debug!("Invalid script args: {:?}", args);
+        Error::InvalidArgs
+    })?;
+    // Only one input cell can use zero lock
+    if source.group_input_out_point(1) != Err(SourceError::IndexOutOfBound) {
+        debug!("More than one input cell uses zero lock!");
+        return Err(Error::MultipleInputs);
+    }
+    // Only one output cell can use zero lock. The output cell is only
+    // required when the leaf turns out not to be a release leaf, which is
+    // decided when the merkle proof is parsed.
+    let current_script_hash = source.script_hash()?;
+    let zero_lock_output_index = find_output_by_lock_hash(source, &current_script_hash)?;
+    let witness = locate_witness(source, script_args.witness_location, &current_script_hash)?;
+
+    // Read the following data from the witness designated in script args:
+    //
+    // * Indices of headers to load merkle root, the number of indices is
+    //   decided by header quorum in script args, DISCOVER_HEADER_INDEX can be
+    //   used to let zero lock locate the headers
+    // * Merkle proof, or a proof pointer to the merkle proof in a cell dep
+    // * Optional leaf trailer, deciding the layout of the leaf
+    // * Remainder of witness data (input_type, output_type) so we can ensure non-malleability
+    let leaf_starter = Box::new(|proof_visitor: &ProofVisitor| {
+        start_leaf(source, proof_visitor, zero_lock_output_index)
+    });
+    let (proof_visitor, leaf_hasher, remainder_fields) = witness_reader::read_witness(
+        source,
+        witness,
+        ProofVisitor::new(script_args.header_quorum as usize),
+        leaf_starter,
+    )?;
+
+    for (i, field) in remainder_fields.iter().enumerate() {
+        if let Some(field) = field {
+            if !metadata::verify_upgrade_metadata(field) {
+                debug!("Witness does not contain valid upgrade metadata!");
+                return Err(Error::InvalidMetadata(i));
+            }
+        }
+    }
+
+    // Now we have all the data for the hasher, we can build the actual merkle leaf.
+    // The same hash function is used for internal nodes of the merkle tree.
+    let leaf_version = proof_visitor.leaf_version();
+    let LeafHasher {
+        hasher,
+        output_index,
+        ..
+    } = leaf_hasher;
+    let sha256 = matches!(hasher, TreeHasher::Sha256(_));
+    let leaf = hasher.finalize();
+    let (root, header_indices) = if sha256 {
+        verify_leaf::<Sha256Hash, _>(source, proof_visitor, leaf.clone())?
+    } else {
+        verify_leaf::<Blake2bHash, _>(source, proof_visitor, leaf.clone())?
+    };
+    Ok(Verified {
+        witness,
+        output_index,
+        leaf_version,
+        leaf,
+        root,
+        header_indices,
+    })
+}
+
+fn verify_leaf<M: Merge<Item = Data>, S: DataSource>(
+    source: &S,
+    proof_visitor: ProofVisitor,
+    leaf: Data,
+) -> Result<(Data, Vec<u32>), Error> {
+    let Some((header_indices, merkle_proof)) = proof_visitor.build::<M>() else {
+        debug!("parsing merkle proof failure!");
+        return Err(Error::IncompleteProof);
+    };
+
+    // When header discovery is requested, the root can only be computed from
+    // the merkle proof first, then header deps carrying the same root are
+    // located.
+    if header_indices.contains(&DISCOVER_HEADER_INDEX) {
+        if header_indices.iter().any(|i| *i != DISCOVER_HEADER_INDEX) {
+            debug!("Header discovery cannot be mixed with designated header deps!");
+            return Err(Error::DiscoveryMixed);
+        }
+        let Some(actual_root) = merkle_proof.root(&[leaf]) else {
+            debug!("no root");
+            return Err(Error::NoRoot);
+        };
+        let matched = discover_headers(source, &actual_root, header_indices.len())?;
+        return Ok((actual_root, matched));
+    }
+
+    // Find merkle root from extension field at offset 128 in the designated
+    // headers, when a header quorum is required, all designated headers must
+    // be distinct, and carry the same merkle root.
+    for (i, header_index) in header_indices.iter().enumerate() {
+        if header_indices[..i].contains(header_index) {
+            debug!("Header dep {} is designated more than once!", header_index);
+            return Err(Error::DuplicateHeaderIndex(*header_index));
+        }
+    }
+    let merkle_root = load_merkle_root(source, header_indices[0])?;
+    for header_index in &header_indices[1..] {
+        let other_root = load_merkle_root(source, *header_index)?;
+        if other_root != merkle_root {
+            debug!(
+                "Header dep {} has a different merkle root: {:?}, expected root: {:?}",
+                header_index, other_root, merkle_root
+            );
+            return Err(Error::RootsDiffer {
+                header_index: *header_index,
+                root: other_root,
+                expected: merkle_root,
+            });
+        }
+    }
+
+    // Actual merkle proof verification
+    let Some(actual_root) = merkle_proof.root(&[leaf]) else {
+        debug!("no root");
+        return Err(Error::NoRoot);
+    };
+    if actual_root != merkle_root {
+        debug!(
+            "Merkle proof failure! Actual root: {:?}, expected root: {:?}",
+            actual_root, merkle_root
+        );
+        return Err(Error::RootMismatch {
+            header_index: header_indices[0],
+            header_root: merkle_root,
+            proof_root: actual_root,
+        });
+    }
+
+    Ok((actual_root, header_indices))
+}
+
+// Only one output cell can use the designated lock, since output locks are not
+// considered in script groups for current transaction, we will need to
+// manually iterate over all of them.
+fn find_output_by_lock_hash<S: DataSource>(
+    source: &S,
+    lock_hash: &[u8; 32],
+) -> Result<Option<usize>, Error> {
+    let mut i = 0;
+    let mut output_index = None;
+    loop {
+        match source.output_lock_hash(i) {
+            Ok(hash) => {
+                if hash == *lock_hash {
+                    if let Some(first) = output_index {
+                        debug!("More than one output cell uses lock {:?}!", lock_hash);
+                        return Err(Error::MultipleOutputs {
+                            lock_hash: *lock_hash,
+                            first,
+                            second: i,
+                        });
+                    } else {
+                        output_index = Some(i);
+                    }
+                }
+            }
+            Err(SourceError::IndexOutOfBound) => break,
+            Err(e) => {
+                debug!("Lock hash loading error: {:?}", e);
+                return Err(e.into());
+            }
+        }
+        i += 1;
+    }
+    Ok(output_index)
+}
+
+// Resolves the witness containing the merkle proof. Witnesses at the
+// positions of other inputs belong to the locks of those inputs, which might
+// interpret or malleate them differently, hence only zero lock's own witness
+// or witnesses beyond transaction inputs can be used. The remainder of the
+// chosen witness is still hashed into the leaf.
+//
+// When the proof is read from elsewhere, nothing commits to zero lock's own
+// witness, so it must be empty, otherwise it could be malleated freely.
+fn locate_witness<S: DataSource>(
+    source: &S,
+    location: WitnessLocation,
+    lock_hash: &[u8; 32],
+) -> Result<WitnessSource, Error> {
+    let index = match location {
+        WitnessLocation::GroupInput => return Ok(WitnessSource::GroupInput(0)),
+        WitnessLocation::Index(index) => index,
+        WitnessLocation::BeyondInputs => source.input_count()?,
+    };
+    let own_witness = match source.input_lock_hash(index) {
+        Ok(input_lock_hash) if input_lock_hash != *lock_hash => {
+            debug!("Witness {} belongs to another input!", index);
+            return Err(Error::WitnessOfOtherInput(index));
+        }
+        Ok(_) => true,
+        Err(SourceError::IndexOutOfBound) => false,
+        Err(e) => return Err(e.into()),
+    };
+    if !own_witness {
+        match source.witness_len(WitnessSource::GroupInput(0)) {
+            Ok(0) | Err(SourceError::IndexOutOfBound) => (),
+            Ok(_) => {
+                debug!("Witness of zero lock input must be empty!");
+                return Err(Error::GroupWitnessNotEmpty);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(WitnessSource::Input(index))
+}
+
+// Try header deps in order, until enough header deps carrying the expected
+// merkle root are found. At most MAX_DISCOVERED_HEADER_DEPS header deps are
+// tried, so the cycles consumed here stay bounded.
+fn discover_headers<S: DataSource>(
+    source: &S,
+    merkle_root: &Data,
+    header_quorum: usize,
+) -> Result<Vec<u32>, Error> {
+    let mut matched = Vec::new();
+    for header_index in 0..MAX_DISCOVERED_HEADER_DEPS {
+        match try_load_merkle_root(source, header_index) {
+            Ok(Some(root)) if root == *merkle_root => {
+                matched.push(header_index as u32);
+                if matched.len() >= header_quorum {
+                    return Ok(matched);
+                }
+            }
+            Ok(_) | Err(SourceError::ItemMissing) => (),
+            Err(SourceError::IndexOutOfBound) => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    debug!(
+        "Only {} header deps carry merkle root {:?}, required: {}",
+        matched.len(),
+        merkle_root,
+        header_quorum
+    );
+    Err(Error::DiscoveryFailed {
+        matched: matched.len(),
+        required: header_quorum,
+        proof_root: merkle_root.clone(),
+    })
+}
+
+fn load_merkle_root<S: DataSource>(source: &S, header_index: u32) -> Result<Data, Error> {
+    match try_load_merkle_root(source, header_index as usize) {
+        Ok(Some(merkle_root)) => Ok(merkle_root),
+        Ok(None) => {
+            debug!("Extension does not have enough data for merkle root!");
+            Err(Error::ShortExtension(header_index))
+        }
+        Err(e) => {
+            debug!("Error loading merkle root from extension: {:?}", e);
+            let _ = e;
+            Err(Error::MissingExtension(header_index))
+        }
+    }
+}
+
+fn try_load_merkle_root<S: DataSource>(
+    source: &S,
+    header_index: usize,
+) -> Result<Option<Data>, SourceError> {
+    let mut merkle_root = [0u8; 32];
+    let loaded = source.load_block_extension(&mut merkle_root, MERKLE_ROOT_OFFSET, header_index)?;
+    Ok((loaded == merkle_root.len()).then(|| Data::new(merkle_root)))
+}
+
+fn continuing_output_index(output_index: Option<usize>) -> Result<usize, Error> {
+    output_index.ok_or_else(|| {
+        debug!("No output cell continues the upgraded cell!");
+        Error::MissingOutput
+    })
+}
+
+// Generate the beginning part of the leaf, the remainder of witness data will
+// be appended later. A leaf uses concatenation of the following bytes:
+//
+// * Byte 0x01, as version for future changes
+// * Zero lock input cell’s OutPoint
+// * Zero lock output cell’s data hash
+// * Zero lock output cell’s CellOutput structure
+// * Byte 0x01 if witness has input_type field, byte 0x00 otherwise
+// * (If input_type exists) Length of input_type as little-endian u32
+// * (If input_type exists) Content of input_type field
+// * Byte 0x01 if witness has output_type field, byte 0x00 otherwise
+// * (If output_type exists) Length of output_type as little-endian u32
+// * (If output_type exists) Content of output_type field
+//
+// When the merkle proof has a leaf trailer, an extended leaf is used instead,
+// which has the following changes:
+//
+// * Byte 0x02 is used as version
+// * Length of leaf options as little-endian u32, and the leaf options follow
+//   the version byte
+// * Depending on leaf options, output cell's data hash might be calculated
+//   differently
+// * Depending on leaf options, additional checks might be performed on the
+//   upgrade transaction
+// * With a release option, the output cell uses the target lock committed in
+//   the leaf instead of zero lock
+// * With a metadata option, designated witness remainder fields must contain
+//   well-formed upgrade metadata
+//
+// Byte 0x03 can also be used as version for an extended leaf, in which case
+// SHA-256 instead of blake2b is used to hash the leaf, as well as internal
+// nodes of the merkle tree. Cell data hashes are still calculated by CKB.
+fn start_leaf<S: DataSource>(
+    source: &S,
+    proof_visitor: &ProofVisitor,
+    zero_lock_output_index: Option<usize>,
+) -> Result<LeafHasher, Error> {
+    let mut hasher = TreeHasher::for_leaf_version(proof_visitor.leaf_version());
+    let mut retained_fields = 0;
+    let (data_hash, output_index) = match proof_visitor.leaf_version() {
+        LEAF_VERSION => {
+            let output_index = continuing_output_index(zero_lock_output_index)?;
+            hasher.update(&[LEAF_VERSION]);
+            hasher.update(&source.group_input_out_point(0)?);
+            (source.output_data_hash(output_index)?, output_index)
+        }
+        v @ (EXTENDED_LEAF_VERSION | SHA256_LEAF_VERSION) => {
+            let leaf_params = proof_visitor.leaf_params();
+            let Some(options) = LeafOptions::parse(leaf_params) else {
+                debug!("parsing leaf options failure!");
+                return Err(Error::InvalidLeafOptions);
+            };
+            hasher.update(&[v]);
+            hasher.update(&(leaf_params.len() as u32).to_le_bytes());
+            hasher.update(leaf_params);
+            hasher.update(&source.group_input_out_point(0)?);
+            if let Some(required_cell_deps) = options.required_cell_deps {
+                leaf::check_required_cell_deps(source, required_cell_deps)?;
+            }
+            // A released cell leaves zero lock for good, the continuing output
+            // is located by the target lock hash committed in the leaf.
+            let output_index = match options.release {
+                Some(release_lock_hash) => {
+                    if let Some(output_index) = zero_lock_output_index {
+                        debug!("Released cell cannot be kept in zero lock!");
+                        return Err(Error::ReleasedCellInZeroLock { output_index });
+                    }
+                    continuing_output_index(find_output_by_lock_hash(source, release_lock_hash)?)?
+                }
+                None => continuing_output_index(zero_lock_output_index)?,
+            };
+            // Upgrade metadata can only be checked once the witness is read
+            retained_fields = options.metadata.unwrap_or(0);
+            let data_hash = match options.dep_group {
+                Some(dep_group) => leaf::dep_group_data_hash(source, dep_group, output_index)?,
+                None => source.output_data_hash(output_index)?,
+            };
+            (data_hash, output_index)
+        }
+        v => {
+            debug!("Unsupported leaf version: {}", v);
+            return Err(Error::UnsupportedLeafVersion(v));
+        }
+    };
+    hasher.update(&data_hash);
+    source.output_cell(output_index, &mut |data: &[u8]| hasher.update(data))?;
+    Ok(LeafHasher {
+        hasher,
+        output_index,
+        retained_fields,
+    })
+}
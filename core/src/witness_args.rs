@@ -0,0 +1,26 @@
+/// Event of a WitnessArgs field. For each field, `Start` is delivered first,
+/// `length` is only meaningful when the field is present. A present field
+/// is then followed by zero or more `Data` pieces, which concatenate to the
+/// content of the field.
+#[derive(Debug)]
+pub enum FieldEvent<'a> {
+    Start { present: bool, length: u32 },
+    Data(&'a [u8]),
+}
+
+/// Visitor of WitnessArgs fields, fields are visited in the order of lock,
+/// input_type and output_type. A non-zero return value aborts reading, and
+/// is returned from the reader. Fields are ignored by default.
+pub trait WitnessArgsVisitor {
+    fn on_lock(&mut self, _event: FieldEvent) -> i32 {
+        0
+    }
+
+    fn on_input_type(&mut self, _event: FieldEvent) -> i32 {
+        0
+    }
+
+    fn on_output_type(&mut self, _event: FieldEvent) -> i32 {
+        0
+    }
+}
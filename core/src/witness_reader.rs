@@ -0,0 +1,213 @@
+use super::{
+    debug,
+    hash::TreeHasher,
+    proof_pointer::{ProofPointer, PROOF_POINTER_SIZE},
+    proof_reader::ProofVisitor,
+    verifier::{DataSource, Error, SourceError, WitnessSource},
+    witness_args::{FieldEvent, WitnessArgsVisitor},
+    ERROR_CODE_WITNESS_READER,
+};
+use alloc::{boxed::Box, vec::Vec};
+
+/// Builds the hasher for the leaf preceding witness remainder. Since leaf
+/// layout depends on the leaf trailer in the merkle proof, this can only be
+/// invoked once the witness lock field has been fully parsed.
+pub type LeafStarter<'a> = Box<dyn FnOnce(&ProofVisitor) -> Result<LeafHasher, Error> + 'a>;
+
+/// Number of witness remainder fields: input_type and output_type.
+pub const REMAINDER_FIELDS: usize = 2;
+
+/// Content of retained witness remainder fields, in the order of input_type
+/// and output_type. None is used for fields not retained, a retained but
+/// absent field is empty.
+pub type RemainderFields = [Option<Vec<u8>>; REMAINDER_FIELDS];
+
+pub struct LeafHasher {
+    pub hasher: TreeHasher,
+    /// Output cell continuing the upgraded cell, committed in the leaf.
+    pub output_index: usize,
+    /// Witness remainder fields to keep in memory, so they can be checked
+    /// once the witness has been read. Bit i denotes the i-th remainder field.
+    pub retained_fields: u8,
+}
+
+struct WitnessVisitor<'a, S> {
+    source: &'a S,
+    witness: WitnessSource,
+    proof: ProofVisitor,
+    // Set when witness lock contains a proof pointer instead of the proof
+    proof_pointer: Option<Vec<u8>>,
+    leaf_starter: Option<LeafStarter<'a>>,
+    remainder_hasher: Option<LeafHasher>,
+    remainder_index: usize,
+    remainder_fields: RemainderFields,
+    // The error aborting the reader, so it is not lost in a return code
+    error: Option<Error>,
+}
+
+impl<'a, S: DataSource> WitnessVisitor<'a, S> {
+    fn new(
+        source: &'a S,
+        witness: WitnessSource,
+        proof: ProofVisitor,
+        leaf_starter: LeafStarter<'a>,
+    ) -> Self {
+        Self {
+            source,
+            witness,
+            proof,
+            proof_pointer: None,
+            leaf_starter: Some(leaf_starter),
+            remainder_hasher: None,
+            remainder_index: 0,
+            remainder_fields: Default::default(),
+            error: None,
+        }
+    }
+
+    fn fail(&mut self, error: Error) -> i32 {
+        self.error = Some(error);
+        ERROR_CODE_WITNESS_READER
+    }
+
+    fn invalid(&mut self, reason: &'static str) -> i32 {
+        debug!("Invalid witness: {}", reason);
+        self.fail(Error::InvalidWitness {
+            witness: self.witness,
+            reason,
+        })
+    }
+
+    fn visit_lock_meta(&mut self, present: bool, length: u32) -> i32 {
+        if !present {
+            return self.invalid("witness lock is missing");
+        }
+        if length as usize == PROOF_POINTER_SIZE {
+            self.proof_pointer = Some(Vec::with_capacity(PROOF_POINTER_SIZE));
+        }
+        0
+    }
+
+    fn visit_lock_data(&mut self, data: &[u8]) -> i32 {
+        match self.proof_pointer.as_mut() {
+            Some(proof_pointer) => {
+                proof_pointer.extend_from_slice(data);
+                0
+            }
+            None => {
+                if self.proof.process(data) != 0 {
+                    return self.invalid("merkle proof is malformed");
+                }
+                0
+            }
+        }
+    }
+
+    fn visit_remainder_meta(&mut self, present: bool, length: u32) -> i32 {
+        if let Some(leaf_starter) = self.leaf_starter.take() {
+            if let Some(proof_pointer) = self.proof_pointer.take() {
+                let Some(proof_pointer) = ProofPointer::parse(&proof_pointer) else {
+                    return self.invalid("proof pointer is truncated");
+                };
+                if let Err(reason) = proof_pointer.load(self.source, &mut self.proof) {
+                    return self.invalid(reason);
+                }
+            }
+            if !self.proof.completed() {
+                return self.invalid("witness lock does not contain a complete merkle proof");
+            }
+            match leaf_starter(&self.proof) {
+                Ok(remainder_hasher) => self.remainder_hasher = Some(remainder_hasher),
+                Err(e) => {
+                    debug!("Error building merkle leaf: {:?}", e);
+                    return self.fail(e);
+                }
+            }
+        }
+        let Some(remainder_hasher) = self.remainder_hasher.as_mut() else {
+            return ERROR_CODE_WITNESS_READER;
+        };
+        let index = self.remainder_index;
+        if index >= REMAINDER_FIELDS {
+            return ERROR_CODE_WITNESS_READER;
+        }
+        self.remainder_index += 1;
+        if present {
+            remainder_hasher.hasher.update(&[1u8]);
+            remainder_hasher.hasher.update(&length.to_le_bytes());
+        } else {
+            remainder_hasher.hasher.update(&[0u8]);
+        }
+        if remainder_hasher.retained_fields & (1 << index) != 0 {
+            self.remainder_fields[index] = Some(Vec::with_capacity(length as usize));
+        }
+        0
+    }
+
+    fn visit_remainder(&mut self, event: FieldEvent) -> i32 {
+        match event {
+            FieldEvent::Start { present, length } => self.visit_remainder_meta(present, length),
+            FieldEvent::Data(data) => self.visit_remainder_data(data),
+        }
+    }
+
+    fn visit_remainder_data(&mut self, data: &[u8]) -> i32 {
+        let Some(remainder_hasher) = self.remainder_hasher.as_mut() else {
+            return ERROR_CODE_WITNESS_READER;
+        };
+        remainder_hasher.hasher.update(data);
+        if let Some(field) = self.remainder_fields[self.remainder_index - 1].as_mut() {
+            field.extend_from_slice(data);
+        }
+        0
+    }
+}
+
+impl<S: DataSource> WitnessArgsVisitor for WitnessVisitor<'_, S> {
+    fn on_lock(&mut self, event: FieldEvent) -> i32 {
+        match event {
+            FieldEvent::Start { present, length } => self.visit_lock_meta(present, length),
+            FieldEvent::Data(data) => self.visit_lock_data(data),
+        }
+    }
+
+    fn on_input_type(&mut self, event: FieldEvent) -> i32 {
+        self.visit_remainder(event)
+    }
+
+    fn on_output_type(&mut self, event: FieldEvent) -> i32 {
+        self.visit_remainder(event)
+    }
+}
+
+/// Reads the designated witness: the merkle proof from witness lock, then
+/// witness remainder into the leaf built by `leaf_starter`.
+pub fn read_witness<'a, S: DataSource>(
+    source: &'a S,
+    witness: WitnessSource,
+    proof: ProofVisitor,
+    leaf_starter: LeafStarter<'a>,
+) -> Result<(ProofVisitor, LeafHasher, RemainderFields), Error> {
+    match source.witness_len(witness) {
+        Ok(_) => (),
+        Err(SourceError::IndexOutOfBound) => {
+            debug!("Witness {:?} is missing!", witness);
+            return Err(Error::MissingWitness(witness));
+        }
+        Err(e) => return Err(e.into()),
+    }
+    let not_witness_args = Error::InvalidWitness {
+        witness,
+        reason: "not a valid WitnessArgs",
+    };
+    let mut visitor = WitnessVisitor::new(source, witness, proof, leaf_starter);
+    let result = source.read_witness(witness, &mut visitor);
+    if result != 0 {
+        debug!("Error reading witness! Return code: {}", result);
+        return Err(visitor.error.unwrap_or(not_witness_args));
+    }
+    match visitor.remainder_hasher {
+        Some(remainder_hasher) => Ok((visitor.proof, remainder_hasher, visitor.remainder_fields)),
+        None => Err(not_witness_args),
+    }
+}
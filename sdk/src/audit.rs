@@ -0,0 +1,228 @@
+//! Audit of past upgrades. Each upgrade found by the indexer has already
+//! been replayed through the offline verifier, the auditor then checks that
+//! every header dep the proof relied on is a header the operator's node
+//! accepted, carrying the same root, and optionally that the root is one of
+//! the published roots.
+
+use crate::{
+    extension::read_zero_lock_root,
+    indexer::{UpgradeIndexer, UpgradeRecord},
+    verifier::VerifyError,
+};
+use ckb_types::{bytes::Bytes, packed::Byte32, prelude::*};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+};
+
+/// A reason an upgrade is not proven authorized by an accepted root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// The upgrade cannot be verified, either the transaction does not pass
+    /// zero lock, or cells and extensions it refers to are missing.
+    VerificationFailed(VerifyError),
+    /// A header dep carrying the root is not among accepted headers.
+    UnacceptedHeader(Byte32),
+    /// The accepted extension of a header dep does not carry the proof root,
+    /// `accepted_root` is None when the extension is too short.
+    RootMismatch {
+        header: Byte32,
+        accepted_root: Option<Byte32>,
+        proof_root: Byte32,
+    },
+    UnpublishedRoot(Byte32),
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Discrepancy::VerificationFailed(e) => write!(f, "verification fails: {}", e),
+            Discrepancy::UnacceptedHeader(header) => {
+                write!(f, "header dep {:#x} is not an accepted header", header)
+            }
+            Discrepancy::RootMismatch {
+                header,
+                accepted_root: Some(accepted_root),
+                proof_root,
+            } => write!(
+                f,
+                "accepted header {:#x} carries root {:#x}, proof root is {:#x}",
+                header, accepted_root, proof_root
+            ),
+            Discrepancy::RootMismatch {
+                header,
+                accepted_root: None,
+                proof_root,
+            } => write!(
+                f,
+                "accepted header {:#x} carries no root, proof root is {:#x}",
+                header, proof_root
+            ),
+            Discrepancy::UnpublishedRoot(root) => {
+                write!(f, "root {:#x} is not a published root", root)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub record: UpgradeRecord,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+/// Result of an audit, in chain order. Its Display output is a plain text
+/// report meant to be attached to a hardfork post-mortem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditReport {
+    pub entries: Vec<AuditEntry>,
+}
+
+impl AuditReport {
+    pub fn is_clean(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|entry| entry.discrepancies.is_empty())
+    }
+
+    /// Entries with at least one discrepancy.
+    pub fn discrepancies(&self) -> impl Iterator<Item = &AuditEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| !entry.discrepancies.is_empty())
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Zero lock upgrade audit")?;
+        writeln!(f, "Upgrades: {}", self.entries.len())?;
+        writeln!(f, "With discrepancies: {}", self.discrepancies().count())?;
+
+        let mut roots: BTreeMap<String, usize> = BTreeMap::new();
+        for entry in &self.entries {
+            if let Ok(upgrade) = &entry.record.upgrade {
+                *roots
+                    .entry(format!("{:#x}", upgrade.verified.root))
+                    .or_default() += 1;
+            }
+        }
+        writeln!(f, "Roots used:")?;
+        for (root, count) in roots {
+            writeln!(f, "  {}: {} upgrades", root, count)?;
+        }
+
+        for entry in &self.entries {
+            let record = &entry.record;
+            writeln!(f)?;
+            writeln!(
+                f,
+                "Input {:#x}:{} in block {} {:#x}, transaction {:#x}",
+                record.input_out_point.tx_hash(),
+                Unpack::<u32>::unpack(&record.input_out_point.index()),
+                record.block_number,
+                record.block_hash,
+                record.tx_hash
+            )?;
+            if let Ok(upgrade) = &record.upgrade {
+                writeln!(
+                    f,
+                    "  Output {:#x}:{}",
+                    upgrade.output_out_point.tx_hash(),
+                    Unpack::<u32>::unpack(&upgrade.output_out_point.index())
+                )?;
+                writeln!(
+                    f,
+                    "  Leaf version {}, leaf {:#x}",
+                    upgrade.verified.leaf_version, upgrade.verified.leaf
+                )?;
+                writeln!(f, "  Root {:#x}", upgrade.verified.root)?;
+                for header in &upgrade.header_deps {
+                    writeln!(f, "  Header dep {:#x}", header)?;
+                }
+            }
+            if entry.discrepancies.is_empty() {
+                writeln!(f, "  OK")?;
+            }
+            for discrepancy in &entry.discrepancies {
+                writeln!(f, "  DISCREPANCY: {}", discrepancy)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks indexed upgrades against the headers accepted by a node.
+#[derive(Debug, Clone, Default)]
+pub struct Auditor {
+    accepted_headers: HashMap<Byte32, Bytes>,
+    published_roots: Option<HashSet<Byte32>>,
+}
+
+impl Auditor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a header accepted by the node, together with its extension.
+    pub fn accepted_header(mut self, block_hash: Byte32, extension: Bytes) -> Self {
+        self.accepted_headers.insert(block_hash, extension);
+        self
+    }
+
+    /// Adds a published root. Once any root is added, upgrades under other
+    /// roots are reported.
+    pub fn published_root(mut self, root: Byte32) -> Self {
+        self.published_roots
+            .get_or_insert_with(HashSet::new)
+            .insert(root);
+        self
+    }
+
+    /// Extensions of accepted headers, to be provided to the indexer when
+    /// the blocks carrying roots are not among the indexed blocks.
+    pub fn accepted_extensions(&self) -> impl Iterator<Item = (&Byte32, &Bytes)> {
+        self.accepted_headers.iter()
+    }
+
+    pub fn audit(&self, indexer: &UpgradeIndexer) -> AuditReport {
+        let entries = indexer
+            .records()
+            .iter()
+            .map(|record| AuditEntry {
+                record: record.clone(),
+                discrepancies: self.check(record),
+            })
+            .collect();
+        AuditReport { entries }
+    }
+
+    fn check(&self, record: &UpgradeRecord) -> Vec<Discrepancy> {
+        let upgrade = match &record.upgrade {
+            Ok(upgrade) => upgrade,
+            Err(e) => return vec![Discrepancy::VerificationFailed(e.clone())],
+        };
+        let proof_root = &upgrade.verified.root;
+        let mut discrepancies = Vec::new();
+        for header in &upgrade.header_deps {
+            let Some(extension) = self.accepted_headers.get(header) else {
+                discrepancies.push(Discrepancy::UnacceptedHeader(header.clone()));
+                continue;
+            };
+            let accepted_root = read_zero_lock_root(extension).ok();
+            if accepted_root.as_ref() != Some(proof_root) {
+                discrepancies.push(Discrepancy::RootMismatch {
+                    header: header.clone(),
+                    accepted_root,
+                    proof_root: proof_root.clone(),
+                });
+            }
+        }
+        if let Some(published_roots) = &self.published_roots {
+            if !published_roots.contains(proof_root) {
+                discrepancies.push(Discrepancy::UnpublishedRoot(proof_root.clone()));
+            }
+        }
+        discrepancies
+    }
+}
@@ -0,0 +1,766 @@
+use ckb_jsonrpc_types as json_types;
+use ckb_mock_tx_types::{MockInfo, MockTransaction, ReprMockTransaction};
+use ckb_types::{
+    bytes::Bytes,
+    core::{DepType, ScriptHashType},
+    packed::{self, Byte32, CellDep, OutPoint},
+    prelude::*,
+    H256,
+};
+use ckb_zero_lock_core::leaf::LeafOptions;
+use ckb_zero_lock_sdk::{
+    load_blocks, verify_mock_transaction, Auditor, Diagnostic, ExtensionBuilder, FeeCell, Manifest,
+    ProofSizeReport, Severity, TreeFile, TreeValidator, UpgradeIndexer, UpgradeLeaf,
+    UpgradeTxBuilder, DEFAULT_FEE_RATE, DEFAULT_MAX_WITNESS_SIZE, DEFAULT_OUTLIER_FACTOR,
+    EXTENDED_LEAF_VERSION, LEAF_VERSION, SHA256_LEAF_VERSION,
+};
+use clap::{Args, Parser, Subcommand};
+use serde::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+/// Tooling for cell upgrades authorized by zero lock.
+#[derive(Parser)]
+#[command(name = "zero-lock")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Computes the leaf hash upgrading an input cell to a new cell.
+    Leaf {
+        /// Out point of the zero lock cell, as <tx hash>:<index>.
+        #[arg(long, value_parser = parse_out_point)]
+        input_out_point: OutPoint,
+        /// JSON file containing the new CellOutput.
+        #[arg(long)]
+        output: PathBuf,
+        /// File containing the new cell data.
+        #[arg(long)]
+        data: PathBuf,
+        #[command(flatten)]
+        leaf: LeafArgs,
+    },
+    /// Builds the merkle tree from a manifest, printing the root and the
+    /// block extension.
+    Tree {
+        #[command(flatten)]
+        manifest: ManifestArgs,
+        #[command(flatten)]
+        validator: ValidatorArgs,
+        /// Writes the tree file, so proofs can be generated later without
+        /// the manifest.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Builds block extension bytes holding the zero lock root together
+    /// with other payloads, or splits existing extension bytes by layout.
+    Extension {
+        /// Zero lock merkle root.
+        #[arg(long)]
+        root: Option<String>,
+        /// Region left zeroed for another consumer, as <name>:<start>..<end>.
+        #[arg(long)]
+        reserved: Vec<String>,
+        /// Payload of another consumer, as <name>:<offset>:<hex>.
+        #[arg(long)]
+        payload: Vec<String>,
+        /// Size limit of the extension in the hardfork block, 64 KiB by
+        /// default.
+        #[arg(long)]
+        max_size: Option<usize>,
+        /// Extension bytes in hex to split by the layout, instead of building.
+        #[arg(long)]
+        parse: Option<String>,
+    },
+    /// Checks a manifest for conflicts and mistakes before its root is
+    /// published.
+    Check {
+        #[command(flatten)]
+        manifest: ManifestArgs,
+        #[command(flatten)]
+        validator: ValidatorArgs,
+    },
+    /// Reports the proof size of every leaf and the proof cell size of
+    /// every batch of a manifest, flagging leaves with unusually large
+    /// witnesses.
+    Sizes {
+        #[command(flatten)]
+        manifest: ManifestArgs,
+        /// Number of header deps designated in each proof.
+        #[arg(long, default_value_t = 1)]
+        header_quorum: usize,
+        /// Witnesses above this multiple of the median witness size are
+        /// flagged.
+        #[arg(long, default_value_t = DEFAULT_OUTLIER_FACTOR)]
+        outlier_factor: usize,
+    },
+    /// Prints the witness proving one leaf in the merkle tree.
+    Proof {
+        #[command(flatten)]
+        proof: ProofArgs,
+    },
+    /// Assembles an upgrade transaction ready to be signed, as mock
+    /// transaction JSON. Fee cells pay the fee and receive the change, mock
+    /// info is left to be filled in.
+    Assemble {
+        #[command(flatten)]
+        proof: ProofArgs,
+        #[command(flatten)]
+        assemble: AssembleArgs,
+    },
+    /// Audits past upgrades found in blocks against headers accepted by a
+    /// node, writing a report of every upgrade and its discrepancies.
+    Audit {
+        #[command(flatten)]
+        audit: AuditArgs,
+    },
+    /// Verifies an upgrade transaction offline, explaining which check fails.
+    Verify {
+        /// ReprMockTransaction JSON file, as produced by tx_generator.
+        #[arg(long)]
+        tx: PathBuf,
+        /// Index of the zero lock input in the transaction.
+        #[arg(long, default_value_t = 0)]
+        input: usize,
+    },
+}
+
+#[derive(Args)]
+struct LeafArgs {
+    #[arg(long, default_value_t = LEAF_VERSION)]
+    leaf_version: u8,
+    /// Leaf options in hex, only used by leaf versions with leaf trailer.
+    #[arg(long)]
+    leaf_options: Option<String>,
+    /// input_type field of the witness in hex.
+    #[arg(long)]
+    input_type: Option<String>,
+    /// output_type field of the witness in hex.
+    #[arg(long)]
+    output_type: Option<String>,
+}
+
+#[derive(Args)]
+struct ManifestArgs {
+    /// Upgrade manifest in TOML or JSON format.
+    #[arg(long)]
+    manifest: PathBuf,
+}
+
+#[derive(Args)]
+struct ValidatorArgs {
+    /// Code hash of zero lock, as used in lock scripts.
+    #[arg(long)]
+    zero_lock_code_hash: String,
+    /// Hash type of zero lock: data, type, data1 or data2.
+    #[arg(long, default_value = "data2", value_parser = parse_hash_type)]
+    zero_lock_hash_type: ScriptHashType,
+    /// Number of header deps designated in each proof.
+    #[arg(long, default_value_t = 1)]
+    header_quorum: usize,
+    #[arg(long, default_value_t = DEFAULT_MAX_WITNESS_SIZE)]
+    max_witness_size: usize,
+    /// JSON file listing the live cells being upgraded, in the format of
+    /// audit --cells, so Type ID continuity and lock changes are checked.
+    #[arg(long)]
+    inputs: Option<PathBuf>,
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+struct TreeSource {
+    /// Upgrade manifest in TOML or JSON format.
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+    /// Tree file written by the tree subcommand.
+    #[arg(long)]
+    tree: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct AssembleArgs {
+    /// Hash of the header dep carrying the merkle root, repeated in the
+    /// order of header indices for a header quorum.
+    #[arg(long = "header-dep", required = true)]
+    header_deps: Vec<String>,
+    /// Out point of the cell containing zero lock, as <tx hash>:<index>.
+    #[arg(long, value_parser = parse_out_point)]
+    zero_lock_dep: OutPoint,
+    /// Other code cell deps, such as type scripts of the new cell or locks
+    /// of fee cells, as <tx hash>:<index>.
+    #[arg(long = "cell-dep", value_parser = parse_out_point)]
+    cell_deps: Vec<OutPoint>,
+    /// Capacity of the zero lock cell being upgraded, in shannons.
+    #[arg(long)]
+    input_capacity: u64,
+    /// JSON file containing the zero lock Script of the cell being upgraded,
+    /// required for a release leaf, the lock of the new cell otherwise.
+    #[arg(long)]
+    input_lock: Option<PathBuf>,
+    /// JSON file listing fee cells, each with out_point, output and an
+    /// optional witness_lock_size.
+    #[arg(long)]
+    fee_cells: Option<PathBuf>,
+    /// JSON file containing the change lock Script, the lock of the first
+    /// fee cell is used when omitted.
+    #[arg(long)]
+    change_lock: Option<PathBuf>,
+    /// Fee rate in shannons per 1000 bytes.
+    #[arg(long, default_value_t = DEFAULT_FEE_RATE)]
+    fee_rate: u64,
+    /// File containing the new cell data, required unless the manifest
+    /// provides it.
+    #[arg(long)]
+    data: Option<PathBuf>,
+    /// Output file, the JSON is printed when omitted.
+    #[arg(long)]
+    tx: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFeeCell {
+    out_point: json_types::OutPoint,
+    output: json_types::CellOutput,
+    #[serde(default)]
+    witness_lock_size: Option<usize>,
+}
+
+#[derive(Args)]
+struct AuditArgs {
+    /// Code hash of zero lock, as used in lock scripts.
+    #[arg(long)]
+    zero_lock_code_hash: String,
+    /// Hash type of zero lock: data, type, data1 or data2.
+    #[arg(long, default_value = "data2", value_parser = parse_hash_type)]
+    zero_lock_hash_type: ScriptHashType,
+    /// Blocks in get_block RPC JSON format, repeated in chain order.
+    #[arg(long = "blocks", required = true)]
+    blocks: Vec<PathBuf>,
+    /// JSON file listing headers accepted by the node, each with hash and
+    /// extension.
+    #[arg(long)]
+    headers: PathBuf,
+    /// JSON file listing cells created before the blocks, each with
+    /// out_point, output and data.
+    #[arg(long)]
+    cells: Option<PathBuf>,
+    /// Published merkle root, repeated for each hardfork.
+    #[arg(long = "root")]
+    roots: Vec<String>,
+    /// Tree file whose root is published, repeated for each hardfork.
+    #[arg(long = "tree")]
+    trees: Vec<PathBuf>,
+    /// Output file, the report is printed when omitted.
+    #[arg(long)]
+    report: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAcceptedHeader {
+    hash: H256,
+    extension: json_types::JsonBytes,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCell {
+    out_point: json_types::OutPoint,
+    output: json_types::CellOutput,
+    data: json_types::JsonBytes,
+}
+
+#[derive(Args)]
+struct ProofArgs {
+    #[command(flatten)]
+    source: TreeSource,
+    /// Input out point of the upgrade to prove, as <tx hash>:<index>.
+    #[arg(long, value_parser = parse_out_point)]
+    input_out_point: OutPoint,
+    /// Header deps carrying the merkle root, repeated for a header quorum.
+    #[arg(long = "header-index", default_values_t = [0])]
+    header_indices: Vec<u32>,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Leaf {
+            input_out_point,
+            output,
+            data,
+            leaf,
+        } => compute_leaf(input_out_point, &output, &data, &leaf),
+        Command::Tree {
+            manifest,
+            validator,
+            out,
+        } => build_validator(&validator)
+            .and_then(|validator| print_tree(&manifest, &validator, out.as_deref())),
+        Command::Extension {
+            root,
+            reserved,
+            payload,
+            max_size,
+            parse,
+        } => extension(
+            root.as_deref(),
+            &reserved,
+            &payload,
+            max_size,
+            parse.as_deref(),
+        ),
+        Command::Check {
+            manifest,
+            validator,
+        } => build_validator(&validator).and_then(|validator| check(&manifest, &validator)),
+        Command::Sizes {
+            manifest,
+            header_quorum,
+            outlier_factor,
+        } => print_sizes(&manifest, header_quorum, outlier_factor),
+        Command::Proof { proof } => print_proof(&proof),
+        Command::Assemble { proof, assemble } => assemble_tx(&proof, &assemble),
+        Command::Audit { audit } => audit_upgrades(&audit),
+        Command::Verify { tx, input } => verify(&tx, input),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_hex(hex: &str) -> Result<Bytes, String> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    // Slicing by byte offsets below only lands on char boundaries for ASCII
+    if !hex.is_ascii() {
+        return Err(format!("Invalid hex string: {}", hex));
+    }
+    if hex.len() % 2 != 0 {
+        return Err(format!("Odd length hex string: {}", hex));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map(Bytes::from)
+        .map_err(|e| format!("Invalid hex string {}: {}", hex, e))
+}
+
+fn parse_byte32(hex: &str) -> Result<Byte32, String> {
+    Byte32::from_slice(&parse_hex(hex)?).map_err(|_| format!("Not a 32-byte hash: {}", hex))
+}
+
+fn parse_out_point(value: &str) -> Result<OutPoint, String> {
+    let (tx_hash, index) = value
+        .split_once(':')
+        .ok_or_else(|| format!("Out point must be <tx hash>:<index>: {}", value))?;
+    let index: u32 = index
+        .parse()
+        .map_err(|e| format!("Invalid out point index {}: {}", index, e))?;
+    Ok(OutPoint::new(parse_byte32(tx_hash)?, index))
+}
+
+fn parse_hash_type(value: &str) -> Result<ScriptHashType, String> {
+    match value {
+        "data" => Ok(ScriptHashType::Data),
+        "type" => Ok(ScriptHashType::Type),
+        "data1" => Ok(ScriptHashType::Data1),
+        "data2" => Ok(ScriptHashType::Data2),
+        _ => Err(format!("Unknown hash type: {}", value)),
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, String> {
+    serde_json::from_slice(&read_file(path)?)
+        .map_err(|e| format!("Error parsing {}: {}", path.display(), e))
+}
+
+fn compute_leaf(
+    input_out_point: OutPoint,
+    output: &Path,
+    data: &Path,
+    args: &LeafArgs,
+) -> Result<(), String> {
+    let output: json_types::CellOutput = read_json(output)?;
+    let data = read_file(data)?;
+    let mut leaf = UpgradeLeaf::new(input_out_point, output.into(), &data);
+    leaf.leaf_version = args.leaf_version;
+    if let Some(leaf_options) = &args.leaf_options {
+        leaf.leaf_options = parse_hex(leaf_options)?;
+    }
+    match leaf.leaf_version {
+        LEAF_VERSION if !leaf.leaf_options.is_empty() => {
+            return Err(format!(
+                "Leaf version {} cannot carry leaf options",
+                LEAF_VERSION
+            ));
+        }
+        LEAF_VERSION => (),
+        EXTENDED_LEAF_VERSION | SHA256_LEAF_VERSION => {
+            if LeafOptions::parse(&leaf.leaf_options).is_none() {
+                return Err("Invalid leaf options".to_string());
+            }
+        }
+        v => return Err(format!("Unsupported leaf version: {}", v)),
+    }
+    leaf.input_type = args.input_type.as_deref().map(parse_hex).transpose()?;
+    leaf.output_type = args.output_type.as_deref().map(parse_hex).transpose()?;
+    println!("{:#x}", leaf.hash());
+    Ok(())
+}
+
+fn load_manifest(args: &ManifestArgs) -> Result<Manifest, String> {
+    Manifest::load(&args.manifest).map_err(|e| e.to_string())
+}
+
+fn build_validator(args: &ValidatorArgs) -> Result<TreeValidator, String> {
+    let mut validator = TreeValidator::new(
+        parse_byte32(&args.zero_lock_code_hash)?,
+        args.zero_lock_hash_type,
+    )
+    .header_quorum(args.header_quorum)
+    .max_witness_size(args.max_witness_size);
+    if let Some(inputs) = &args.inputs {
+        for cell in read_json::<Vec<RawCell>>(inputs)? {
+            validator = validator.input_cell(cell.out_point.into(), cell.output.into());
+        }
+    }
+    Ok(validator)
+}
+
+// Prints diagnostics, returning the number of errors
+fn print_diagnostics(diagnostics: &[Diagnostic]) -> usize {
+    let mut errors = 0;
+    for diagnostic in diagnostics {
+        match diagnostic.severity() {
+            Severity::Error => {
+                errors += 1;
+                println!("error: {}", diagnostic);
+            }
+            Severity::Warning => println!("warning: {}", diagnostic),
+        }
+    }
+    errors
+}
+
+// The root printed here is meant to be published, so the manifest must pass
+// the same checks as in check first
+fn print_tree(
+    args: &ManifestArgs,
+    validator: &TreeValidator,
+    out: Option<&Path>,
+) -> Result<(), String> {
+    let tree_file = match TreeFile::validated(&load_manifest(args)?, validator) {
+        Ok((tree_file, warnings)) => {
+            print_diagnostics(&warnings);
+            tree_file
+        }
+        Err(diagnostics) => {
+            let errors = print_diagnostics(&diagnostics);
+            return Err(format!("Manifest has {} errors, no tree is built", errors));
+        }
+    };
+    let tree = tree_file.tree();
+    for (leaf, hash) in tree_file.leaves().iter().zip(tree.leaves()) {
+        println!("Leaf {}: {:#x}", leaf.input_out_point, hash);
+    }
+    println!("Manifest hash: {:#x}", tree_file.manifest_hash());
+    println!("Merkle root: {:#x}", tree.root());
+    println!("Extension: 0x{:x}", tree.extension());
+    if let Some(out) = out {
+        tree_file.write(out).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn extension(
+    root: Option<&str>,
+    reserved: &[String],
+    payload: &[String],
+    max_size: Option<usize>,
+    parse: Option<&str>,
+) -> Result<(), String> {
+    // The root content does not matter when parsing
+    let root = match (root, parse) {
+        (Some(root), _) => parse_byte32(root)?,
+        (None, Some(_)) => Byte32::zero(),
+        (None, None) => return Err("Merkle root is required, use --root".to_string()),
+    };
+    let mut builder = ExtensionBuilder::new().zero_lock_root(&root);
+    for region in reserved {
+        let (name, range) = region
+            .split_once(':')
+            .ok_or_else(|| format!("Reserved region must be <name>:<start>..<end>: {}", region))?;
+        let (start, end) = range
+            .split_once("..")
+            .and_then(|(start, end)| Some((start.parse().ok()?, end.parse().ok()?)))
+            .ok_or_else(|| format!("Invalid range: {}", range))?;
+        builder = builder.reserved(name, start..end);
+    }
+    for region in payload {
+        let mut parts = region.splitn(3, ':');
+        let (Some(name), Some(offset), Some(data)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!("Payload must be <name>:<offset>:<hex>: {}", region));
+        };
+        let offset = offset
+            .parse()
+            .map_err(|e| format!("Invalid offset {}: {}", offset, e))?;
+        builder = builder
+            .payload(name, offset, parse_hex(data)?)
+            .map_err(|e| e.to_string())?;
+    }
+    if let Some(max_size) = max_size {
+        builder = builder.max_size(max_size);
+    }
+    match parse {
+        Some(extension) => {
+            let regions = builder
+                .parse(&parse_hex(extension)?)
+                .map_err(|e| e.to_string())?;
+            for (region, data) in regions {
+                println!(
+                    "{} [{}..{}]: 0x{:x}",
+                    region.name, region.range.start, region.range.end, data
+                );
+            }
+        }
+        None => {
+            for region in builder.layout().map_err(|e| e.to_string())? {
+                println!(
+                    "{} [{}..{}]: {:?}",
+                    region.name, region.range.start, region.range.end, region.kind
+                );
+            }
+            let extension = builder.build().map_err(|e| e.to_string())?;
+            println!("Extension: 0x{:x}", extension);
+        }
+    }
+    Ok(())
+}
+
+fn check(args: &ManifestArgs, validator: &TreeValidator) -> Result<(), String> {
+    let manifest = load_manifest(args)?;
+    let errors = print_diagnostics(&validator.validate(&manifest));
+    if errors > 0 {
+        return Err(format!("Manifest has {} errors", errors));
+    }
+    println!(
+        "Manifest passes checks, merkle root: {:#x}",
+        manifest.tree().root()
+    );
+    Ok(())
+}
+
+fn print_sizes(
+    args: &ManifestArgs,
+    header_quorum: usize,
+    outlier_factor: usize,
+) -> Result<(), String> {
+    let report = ProofSizeReport::from_manifest(&load_manifest(args)?, header_quorum);
+    print!("{}", report);
+    for leaf in report.outliers(outlier_factor) {
+        println!(
+            "warning: witness of leaf {} is {} bytes, above {} times the median",
+            leaf.position, leaf.witness_size, outlier_factor
+        );
+    }
+    Ok(())
+}
+
+// Returns the selected leaf, its data when the manifest provides it, and
+// the witness proving it
+fn build_witness(
+    args: &ProofArgs,
+) -> Result<(UpgradeLeaf, Option<Bytes>, packed::WitnessArgs), String> {
+    let (tree_file, manifest) = match (&args.source.manifest, &args.source.tree) {
+        (Some(manifest), _) => {
+            let manifest = Manifest::load(manifest).map_err(|e| e.to_string())?;
+            (TreeFile::from_manifest(&manifest), Some(manifest))
+        }
+        (None, Some(tree)) => (TreeFile::load(tree).map_err(|e| e.to_string())?, None),
+        (None, None) => unreachable!("clap requires a tree source"),
+    };
+    let index = tree_file
+        .position(&args.input_out_point)
+        .ok_or_else(|| format!("No upgrade for {} in tree", args.input_out_point))?;
+    let data = manifest.and_then(|m| m.entries()[index as usize].data.clone());
+    let witness = tree_file
+        .witness(index, &args.header_indices)
+        .ok_or_else(|| format!("Leaf {} is missing from tree", index))?;
+    Ok((tree_file.leaves()[index as usize].clone(), data, witness))
+}
+
+fn print_proof(args: &ProofArgs) -> Result<(), String> {
+    let (_, _, witness) = build_witness(args)?;
+    println!("{:#x}", witness);
+    Ok(())
+}
+
+fn code_cell_dep(out_point: OutPoint) -> CellDep {
+    CellDep::new_builder()
+        .out_point(out_point)
+        .dep_type(DepType::Code.into())
+        .build()
+}
+
+fn assemble_tx(proof: &ProofArgs, args: &AssembleArgs) -> Result<(), String> {
+    let (leaf, manifest_data, witness) = build_witness(proof)?;
+    let data = match &args.data {
+        Some(data) => Bytes::from(read_file(data)?),
+        None => manifest_data.ok_or("New cell data is required, use --data")?,
+    };
+    let header_deps = args
+        .header_deps
+        .iter()
+        .map(|header_dep| parse_byte32(header_dep))
+        .collect::<Result<Vec<_>, _>>()?;
+    let input_lock = match &args.input_lock {
+        Some(input_lock) => read_json::<json_types::Script>(input_lock)?.into(),
+        None => {
+            let options = LeafOptions::parse(&leaf.leaf_options).ok_or("Invalid leaf options")?;
+            if options.release.is_some() {
+                return Err("Zero lock of a release leaf is required, use --input-lock".to_string());
+            }
+            leaf.output.lock()
+        }
+    };
+    let input = packed::CellOutput::new_builder()
+        .capacity(args.input_capacity.pack())
+        .lock(input_lock)
+        .build();
+    let mut builder = UpgradeTxBuilder::new(
+        leaf,
+        &input,
+        data,
+        witness.lock().to_opt().expect("proof").raw_data(),
+        header_deps[0].clone(),
+    )
+    .cell_dep(code_cell_dep(args.zero_lock_dep.clone()))
+    .fee_rate(args.fee_rate);
+    for header_dep in &header_deps[1..] {
+        builder = builder.header_dep(header_dep.clone());
+    }
+    for cell_dep in &args.cell_deps {
+        builder = builder.cell_dep(code_cell_dep(cell_dep.clone()));
+    }
+    if let Some(fee_cells) = &args.fee_cells {
+        for fee_cell in read_json::<Vec<RawFeeCell>>(fee_cells)? {
+            builder = builder.fee_cell(FeeCell {
+                out_point: fee_cell.out_point.into(),
+                output: fee_cell.output.into(),
+                witness_lock_size: fee_cell.witness_lock_size,
+            });
+        }
+    }
+    if let Some(change_lock) = &args.change_lock {
+        builder = builder.change_lock(read_json::<json_types::Script>(change_lock)?.into());
+    }
+    let upgrade_tx = builder.build().map_err(|e| e.to_string())?;
+    eprintln!(
+        "Fee: {} shannons for {} bytes, using {} fee cells",
+        upgrade_tx.fee, upgrade_tx.size, upgrade_tx.fee_cells
+    );
+
+    let mock_tx = MockTransaction {
+        mock_info: MockInfo {
+            inputs: vec![],
+            cell_deps: vec![],
+            header_deps: vec![],
+            extensions: vec![],
+        },
+        tx: upgrade_tx.tx.data(),
+    };
+    let repr_tx: ReprMockTransaction = mock_tx.into();
+    let json = serde_json::to_string_pretty(&repr_tx).expect("json");
+    match &args.tx {
+        Some(path) => std::fs::write(path, json)
+            .map_err(|e| format!("Error writing {}: {}", path.display(), e)),
+        None => {
+            println!("{}", json);
+            Ok(())
+        }
+    }
+}
+
+fn audit_upgrades(args: &AuditArgs) -> Result<(), String> {
+    let mut auditor = Auditor::new();
+    for header in read_json::<Vec<RawAcceptedHeader>>(&args.headers)? {
+        auditor = auditor.accepted_header(header.hash.pack(), header.extension.into_bytes());
+    }
+    for root in &args.roots {
+        auditor = auditor.published_root(parse_byte32(root)?);
+    }
+    for tree in &args.trees {
+        let tree_file = TreeFile::load(tree).map_err(|e| e.to_string())?;
+        auditor = auditor.published_root(tree_file.root());
+    }
+
+    let mut indexer = UpgradeIndexer::new(
+        parse_byte32(&args.zero_lock_code_hash)?,
+        args.zero_lock_hash_type,
+    );
+    for (block_hash, extension) in auditor.accepted_extensions() {
+        indexer.insert_extension(block_hash.clone(), extension.clone());
+    }
+    if let Some(cells) = &args.cells {
+        for cell in read_json::<Vec<RawCell>>(cells)? {
+            indexer.insert_cell(
+                cell.out_point.into(),
+                cell.output.into(),
+                cell.data.into_bytes(),
+            );
+        }
+    }
+    for blocks in &args.blocks {
+        indexer.index_blocks(load_blocks(blocks).map_err(|e| e.to_string())?);
+    }
+
+    let report = auditor.audit(&indexer);
+    match &args.report {
+        Some(path) => std::fs::write(path, report.to_string())
+            .map_err(|e| format!("Error writing {}: {}", path.display(), e))?,
+        None => print!("{}", report),
+    }
+    if !report.is_clean() {
+        return Err(format!(
+            "{} upgrades have discrepancies",
+            report.discrepancies().count()
+        ));
+    }
+    Ok(())
+}
+
+fn verify(path: &Path, input: usize) -> Result<(), String> {
+    let mock_tx: MockTransaction = read_json::<ReprMockTransaction>(path)?.into();
+    match verify_mock_transaction(&mock_tx, input) {
+        Ok(verified) => {
+            println!("Upgrade passes verification");
+            println!("Witness index: {}", verified.witness_index);
+            println!("Output index: {}", verified.output_index);
+            println!("Leaf version: {}", verified.leaf_version);
+            println!("Leaf: {:#x}", verified.leaf);
+            println!("Merkle root: {:#x}", verified.root);
+            println!("Header deps: {:?}", verified.header_indices);
+            Ok(())
+        }
+        Err(e) => Err(match e.code() {
+            Some(code) => format!("Verification fails at zero lock check {}: {}", code, e),
+            None => format!("Verification cannot run: {}", e),
+        }),
+    }
+}
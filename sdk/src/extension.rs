@@ -0,0 +1,222 @@
+//! Block extension layout. The extension field of a block is shared by all
+//! its consumers, zero lock only owns the 32 bytes at MERKLE_ROOT_OFFSET.
+//! `ExtensionBuilder` places the zero lock root next to other payloads and
+//! reserved regions, rejecting overlapping or oversized layouts.
+
+use ckb_types::{bytes::Bytes, packed::Byte32, prelude::*};
+pub use ckb_zero_lock_core::MERKLE_ROOT_OFFSET;
+use std::{fmt, ops::Range};
+
+/// Name of the region holding the zero lock root.
+pub const ZERO_LOCK_ROOT_REGION: &str = "zero-lock-root";
+
+/// Size limit used when none is set, well above any extension a block can
+/// carry, so a malformed layout is rejected before it is allocated.
+pub const DEFAULT_MAX_EXTENSION_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    /// Owned by another consumer, left zeroed for it to fill.
+    Reserved,
+    /// Bytes provided when building the extension.
+    Payload,
+    ZeroLockRoot,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub name: String,
+    pub kind: RegionKind,
+    pub range: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtensionError {
+    MissingRoot,
+    DuplicateRoot,
+    EmptyRegion(String),
+    Overlap {
+        first: String,
+        second: String,
+    },
+    TooLarge {
+        size: usize,
+        limit: usize,
+    },
+    /// `region` ends beyond the largest addressable offset.
+    RangeOverflow(String),
+    /// The extension ends before `region` does.
+    Truncated {
+        region: String,
+        size: usize,
+    },
+}
+
+impl fmt::Display for ExtensionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtensionError::MissingRoot => write!(f, "extension has no zero lock root"),
+            ExtensionError::DuplicateRoot => {
+                write!(f, "extension has more than one zero lock root")
+            }
+            ExtensionError::EmptyRegion(name) => write!(f, "region {} is empty", name),
+            ExtensionError::Overlap { first, second } => {
+                write!(f, "regions {} and {} overlap", first, second)
+            }
+            ExtensionError::TooLarge { size, limit } => write!(
+                f,
+                "extension takes {} bytes, more than the limit of {}",
+                size, limit
+            ),
+            ExtensionError::RangeOverflow(name) => {
+                write!(f, "region {} ends beyond the addressable range", name)
+            }
+            ExtensionError::Truncated { region, size } => write!(
+                f,
+                "extension has {} bytes, region {} is not fully included",
+                size, region
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExtensionError {}
+
+/// Builds block extension bytes from typed regions. Gaps between regions
+/// and reserved regions are zero filled.
+#[derive(Debug, Clone, Default)]
+pub struct ExtensionBuilder {
+    // Reserved regions carry no data, they are only zeroed when building
+    regions: Vec<(Region, Bytes)>,
+    max_size: Option<usize>,
+}
+
+impl ExtensionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reserved(mut self, name: &str, range: Range<usize>) -> Self {
+        self.regions.push((
+            Region {
+                name: name.to_string(),
+                kind: RegionKind::Reserved,
+                range,
+            },
+            Bytes::new(),
+        ));
+        self
+    }
+
+    pub fn payload(
+        mut self,
+        name: &str,
+        offset: usize,
+        data: Bytes,
+    ) -> Result<Self, ExtensionError> {
+        let end = offset
+            .checked_add(data.len())
+            .ok_or_else(|| ExtensionError::RangeOverflow(name.to_string()))?;
+        self.regions.push((
+            Region {
+                name: name.to_string(),
+                kind: RegionKind::Payload,
+                range: offset..end,
+            },
+            data,
+        ));
+        Ok(self)
+    }
+
+    /// Places the zero lock root at MERKLE_ROOT_OFFSET.
+    pub fn zero_lock_root(mut self, root: &Byte32) -> Self {
+        self.regions.push((
+            Region {
+                name: ZERO_LOCK_ROOT_REGION.to_string(),
+                kind: RegionKind::ZeroLockRoot,
+                range: MERKLE_ROOT_OFFSET..MERKLE_ROOT_OFFSET + 32,
+            },
+            root.as_bytes(),
+        ));
+        self
+    }
+
+    /// Size limit of the extension, as set by consensus rules in effect for
+    /// the hardfork block, DEFAULT_MAX_EXTENSION_SIZE by default.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Regions sorted by offset, after checking the layout.
+    pub fn layout(&self) -> Result<Vec<Region>, ExtensionError> {
+        let mut regions: Vec<Region> = self.regions.iter().map(|(r, _)| r.clone()).collect();
+        regions.sort_by_key(|region| region.range.start);
+        match regions
+            .iter()
+            .filter(|region| region.kind == RegionKind::ZeroLockRoot)
+            .count()
+        {
+            0 => return Err(ExtensionError::MissingRoot),
+            1 => (),
+            _ => return Err(ExtensionError::DuplicateRoot),
+        }
+        if let Some(region) = regions.iter().find(|region| region.range.is_empty()) {
+            return Err(ExtensionError::EmptyRegion(region.name.clone()));
+        }
+        for pair in regions.windows(2) {
+            if pair[0].range.end > pair[1].range.start {
+                return Err(ExtensionError::Overlap {
+                    first: pair[0].name.clone(),
+                    second: pair[1].name.clone(),
+                });
+            }
+        }
+        let size = regions.last().map(|r| r.range.end).unwrap_or(0);
+        let limit = self.max_size.unwrap_or(DEFAULT_MAX_EXTENSION_SIZE);
+        if size > limit {
+            return Err(ExtensionError::TooLarge { size, limit });
+        }
+        Ok(regions)
+    }
+
+    pub fn build(&self) -> Result<Bytes, ExtensionError> {
+        let layout = self.layout()?;
+        let mut extension = vec![0u8; layout.last().map(|r| r.range.end).unwrap_or(0)];
+        for (region, data) in &self.regions {
+            if region.kind != RegionKind::Reserved {
+                extension[region.range.clone()].copy_from_slice(data);
+            }
+        }
+        Ok(extension.into())
+    }
+
+    /// Splits an extension built with the same layout back into regions.
+    pub fn parse(&self, extension: &[u8]) -> Result<Vec<(Region, Bytes)>, ExtensionError> {
+        self.layout()?
+            .into_iter()
+            .map(|region| match extension.get(region.range.clone()) {
+                Some(data) => {
+                    let data = Bytes::copy_from_slice(data);
+                    Ok((region, data))
+                }
+                None => Err(ExtensionError::Truncated {
+                    region: region.name,
+                    size: extension.len(),
+                }),
+            })
+            .collect()
+    }
+}
+
+/// Reads the zero lock root from extension bytes, regardless of the other
+/// regions.
+pub fn read_zero_lock_root(extension: &[u8]) -> Result<Byte32, ExtensionError> {
+    extension
+        .get(MERKLE_ROOT_OFFSET..MERKLE_ROOT_OFFSET + 32)
+        .map(|root| Byte32::from_slice(root).expect("32 bytes"))
+        .ok_or_else(|| ExtensionError::Truncated {
+            region: ZERO_LOCK_ROOT_REGION.to_string(),
+            size: extension.len(),
+        })
+}
@@ -0,0 +1,56 @@
+use ckb_types::{packed::Byte32, prelude::*};
+pub use ckb_zero_lock_core::leaf::{EXTENDED_LEAF_VERSION, LEAF_VERSION, SHA256_LEAF_VERSION};
+use ckb_zero_lock_core::{
+    hash::{self, Blake2bHash, Sha256Hash},
+    Data,
+};
+use merkle_cbt::merkle_tree::Merge;
+
+fn byte32(data: &Data) -> Byte32 {
+    Byte32::from_slice(data.as_bytes()).expect("byte32")
+}
+
+fn data(hash: &Byte32) -> Data {
+    Data::from_slice(&hash.raw_data())
+}
+
+/// Hash function for both the leaf and internal nodes of the merkle tree,
+/// selected by leaf version. Wraps the hasher used by zero lock, producing
+/// Byte32.
+pub struct TreeHasher(hash::TreeHasher);
+
+impl TreeHasher {
+    pub fn for_leaf_version(leaf_version: u8) -> Self {
+        Self(hash::TreeHasher::for_leaf_version(leaf_version))
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data)
+    }
+
+    pub fn finalize(self) -> Byte32 {
+        byte32(&self.0.finalize())
+    }
+}
+
+/// Merges internal nodes of a blake2b tree, via `Blake2bHash` of zero lock.
+pub struct Blake2bMerge;
+
+impl Merge for Blake2bMerge {
+    type Item = Byte32;
+
+    fn merge(lhs: &Self::Item, rhs: &Self::Item) -> Self::Item {
+        byte32(&Blake2bHash::merge(&data(lhs), &data(rhs)))
+    }
+}
+
+/// Merges internal nodes of a SHA-256 tree, via `Sha256Hash` of zero lock.
+pub struct Sha256Merge;
+
+impl Merge for Sha256Merge {
+    type Item = Byte32;
+
+    fn merge(lhs: &Self::Item, rhs: &Self::Item) -> Self::Item {
+        byte32(&Sha256Hash::merge(&data(lhs), &data(rhs)))
+    }
+}
@@ -0,0 +1,383 @@
+//! Upgrade history of zero lock cells, built from a stream of blocks without
+//! a live node. Each zero lock input is replayed through the offline
+//! verifier, so the recorded leaf and root are exactly what zero lock
+//! checked on chain.
+//!
+//! The indexer keeps every live cell created in the indexed blocks, cells
+//! created earlier and needed for verification, such as the zero lock cells
+//! themselves or their cell deps, are provided via `insert_cell`.
+
+use crate::verifier::{verify_with_proof, VerifiedUpgrade, VerifyError};
+use ckb_jsonrpc_types as json_types;
+use ckb_mock_tx_types::{MockCellDep, MockInfo, MockInput, MockTransaction};
+use ckb_types::{
+    bytes::Bytes,
+    core::{BlockView, DepType, ScriptHashType, TransactionView},
+    packed::{Byte, Byte32, CellDep, CellOutput, OutPoint, OutPointVec, Script},
+    prelude::*,
+};
+use ckb_zero_lock_core::{args::ScriptArgs, hash::Blake2bHash, proof_reader::ProofVisitor};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexerError {
+    Read { path: PathBuf, reason: String },
+    Parse { path: PathBuf, reason: String },
+}
+
+impl fmt::Display for IndexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexerError::Read { path, reason } => {
+                write!(f, "cannot read blocks from {}: {}", path.display(), reason)
+            }
+            IndexerError::Parse { path, reason } => {
+                write!(f, "invalid blocks in {}: {}", path.display(), reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IndexerError {}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawBlocks {
+    Many(Vec<json_types::BlockView>),
+    One(Box<json_types::BlockView>),
+}
+
+/// Loads blocks in the JSON format returned by the `get_block` RPC, a file
+/// contains either a single block or an array of blocks.
+pub fn load_blocks(path: &Path) -> Result<Vec<BlockView>, IndexerError> {
+    let content = std::fs::read(path).map_err(|e| IndexerError::Read {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    let raw: RawBlocks = serde_json::from_slice(&content).map_err(|e| IndexerError::Parse {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    Ok(match raw {
+        RawBlocks::Many(blocks) => blocks.into_iter().map(Into::into).collect(),
+        RawBlocks::One(block) => vec![(*block).into()],
+    })
+}
+
+/// Merkle proof as found in the witness, after following a proof pointer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedProof {
+    /// Header indices as written, which might ask for header discovery.
+    pub header_indices: Vec<u32>,
+    pub leaf_version: u8,
+    pub leaf_options: Bytes,
+    pub indices: Vec<u32>,
+    pub lemmas: Vec<Byte32>,
+}
+
+impl DecodedProof {
+    pub(crate) fn decode(proof: &[u8], header_quorum: usize) -> Option<Self> {
+        let mut proof_visitor = ProofVisitor::new(header_quorum);
+        if proof_visitor.process(proof) != 0 {
+            return None;
+        }
+        let leaf_version = proof_visitor.leaf_version();
+        let leaf_options = Bytes::copy_from_slice(proof_visitor.leaf_params());
+        // Indices and lemmas do not depend on the hash function
+        let (header_indices, merkle_proof) = proof_visitor.build::<Blake2bHash>()?;
+        Some(Self {
+            header_indices,
+            leaf_version,
+            leaf_options,
+            indices: merkle_proof.indices().to_vec(),
+            lemmas: merkle_proof
+                .lemmas()
+                .iter()
+                .map(|lemma| Byte32::from_slice(lemma.as_bytes()).expect("byte32"))
+                .collect(),
+        })
+    }
+}
+
+/// An upgrade that passes verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedUpgrade {
+    pub output_out_point: OutPoint,
+    /// Hashes of the header deps carrying the root.
+    pub header_deps: Vec<Byte32>,
+    pub verified: VerifiedUpgrade,
+    pub proof: DecodedProof,
+}
+
+/// A zero lock input found in the indexed blocks. Verification only fails
+/// when the indexer lacks cells or extensions the transaction refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeRecord {
+    pub block_number: u64,
+    pub block_hash: Byte32,
+    pub tx_hash: Byte32,
+    pub input_out_point: OutPoint,
+    pub upgrade: Result<IndexedUpgrade, VerifyError>,
+}
+
+/// Records upgrades of zero lock cells, indexed by input and output.
+#[derive(Debug, Clone)]
+pub struct UpgradeIndexer {
+    zero_lock_code_hash: Byte32,
+    zero_lock_hash_type: ScriptHashType,
+    cells: HashMap<OutPoint, (CellOutput, Bytes)>,
+    extensions: HashMap<Byte32, Bytes>,
+    records: Vec<UpgradeRecord>,
+    by_input: HashMap<OutPoint, usize>,
+    by_output: HashMap<OutPoint, usize>,
+    // Type hash of cells which have been in zero lock, to their live cell
+    type_cells: HashMap<Byte32, OutPoint>,
+}
+
+impl UpgradeIndexer {
+    pub fn new(zero_lock_code_hash: Byte32, zero_lock_hash_type: ScriptHashType) -> Self {
+        Self {
+            zero_lock_code_hash,
+            zero_lock_hash_type,
+            cells: HashMap::new(),
+            extensions: HashMap::new(),
+            records: Vec::new(),
+            by_input: HashMap::new(),
+            by_output: HashMap::new(),
+            type_cells: HashMap::new(),
+        }
+    }
+
+    fn is_zero_lock(&self, lock: &Script) -> bool {
+        lock.code_hash() == self.zero_lock_code_hash
+            && lock.hash_type() == Byte::new(self.zero_lock_hash_type as u8)
+    }
+
+    /// Adds a live cell created before the indexed blocks.
+    pub fn insert_cell(&mut self, out_point: OutPoint, output: CellOutput, data: Bytes) {
+        if let Some(type_script) = output.type_().to_opt() {
+            if self.is_zero_lock(&output.lock()) {
+                self.type_cells
+                    .insert(type_script.calc_script_hash(), out_point.clone());
+            }
+        }
+        self.cells.insert(out_point, (output, data));
+    }
+
+    /// Adds the extension of a block outside the indexed blocks.
+    pub fn insert_extension(&mut self, block_hash: Byte32, extension: Bytes) {
+        self.extensions.insert(block_hash, extension);
+    }
+
+    pub fn index_blocks(&mut self, blocks: impl IntoIterator<Item = BlockView>) {
+        for block in blocks {
+            self.index_block(&block);
+        }
+    }
+
+    /// Indexes a block, blocks must be provided in chain order.
+    pub fn index_block(&mut self, block: &BlockView) {
+        if let Some(extension) = block.extension() {
+            self.extensions.insert(block.hash(), extension.raw_data());
+        }
+        for tx in block.transactions() {
+            let zero_lock_inputs: Vec<usize> = tx
+                .input_pts_iter()
+                .enumerate()
+                .filter(|(_, out_point)| {
+                    self.cells
+                        .get(out_point)
+                        .is_some_and(|(output, _)| self.is_zero_lock(&output.lock()))
+                })
+                .map(|(i, _)| i)
+                .collect();
+            if !zero_lock_inputs.is_empty() {
+                let mock_tx = self.mock_transaction(&tx);
+                for input_index in zero_lock_inputs {
+                    let record = UpgradeRecord {
+                        block_number: block.number(),
+                        block_hash: block.hash(),
+                        tx_hash: tx.hash(),
+                        input_out_point: tx.inputs().get(input_index).unwrap().previous_output(),
+                        upgrade: self.replay(&tx, &mock_tx, input_index),
+                    };
+                    self.push_record(record);
+                }
+            }
+            self.apply(&tx);
+        }
+    }
+
+    fn replay(
+        &self,
+        tx: &TransactionView,
+        mock_tx: &MockTransaction,
+        input_index: usize,
+    ) -> Result<IndexedUpgrade, VerifyError> {
+        let (verified, proof) = verify_with_proof(mock_tx, input_index)?;
+        let args = mock_tx.mock_info.inputs[input_index]
+            .output
+            .lock()
+            .args()
+            .raw_data();
+        let header_quorum = ScriptArgs::parse(&args)
+            .expect("verified args")
+            .header_quorum as usize;
+        let proof =
+            DecodedProof::decode(&proof, header_quorum).expect("verified proof can be decoded");
+        let header_deps = verified
+            .header_indices
+            .iter()
+            .map(|i| {
+                tx.header_deps()
+                    .get(*i as usize)
+                    .expect("verified header dep")
+            })
+            .collect();
+        Ok(IndexedUpgrade {
+            output_out_point: OutPoint::new(tx.hash(), verified.output_index as u32),
+            header_deps,
+            verified,
+            proof,
+        })
+    }
+
+    fn mock_transaction(&self, tx: &TransactionView) -> MockTransaction {
+        // Only the lock of an input is read by the verifier, a cell unknown
+        // to the indexer cannot be a zero lock cell it tracks
+        let inputs = tx
+            .inputs()
+            .into_iter()
+            .map(|input| {
+                let (output, data) = self
+                    .cells
+                    .get(&input.previous_output())
+                    .cloned()
+                    .unwrap_or_default();
+                MockInput {
+                    input,
+                    output,
+                    data,
+                    header: None,
+                }
+            })
+            .collect();
+        let mut cell_deps = Vec::new();
+        for cell_dep in tx.cell_deps_iter() {
+            let Some((output, data)) = self.cells.get(&cell_dep.out_point()).cloned() else {
+                continue;
+            };
+            if cell_dep.dep_type() == DepType::DepGroup.into() {
+                for out_point in OutPointVec::from_slice(&data).into_iter().flatten() {
+                    if let Some((output, data)) = self.cells.get(&out_point).cloned() {
+                        cell_deps.push(MockCellDep {
+                            cell_dep: CellDep::new_builder()
+                                .out_point(out_point)
+                                .dep_type(DepType::Code.into())
+                                .build(),
+                            output,
+                            data,
+                            header: None,
+                        });
+                    }
+                }
+            }
+            cell_deps.push(MockCellDep {
+                cell_dep,
+                output,
+                data,
+                header: None,
+            });
+        }
+        let extensions = tx
+            .header_deps_iter()
+            .filter_map(|hash| {
+                let extension = self.extensions.get(&hash)?.clone();
+                Some((hash, extension))
+            })
+            .collect();
+        MockTransaction {
+            mock_info: MockInfo {
+                inputs,
+                cell_deps,
+                header_deps: vec![],
+                extensions,
+            },
+            tx: tx.data(),
+        }
+    }
+
+    fn push_record(&mut self, record: UpgradeRecord) {
+        let index = self.records.len();
+        self.by_input.insert(record.input_out_point.clone(), index);
+        if let Ok(upgrade) = &record.upgrade {
+            self.by_output
+                .insert(upgrade.output_out_point.clone(), index);
+        }
+        self.records.push(record);
+    }
+
+    // Updates live cells, following cells which have been in zero lock by
+    // their type script, including after they are released
+    fn apply(&mut self, tx: &TransactionView) {
+        let mut followed = HashSet::new();
+        for out_point in tx.input_pts_iter() {
+            let Some((output, _)) = self.cells.remove(&out_point) else {
+                continue;
+            };
+            if let Some(type_script) = output.type_().to_opt() {
+                let type_hash = type_script.calc_script_hash();
+                if self.type_cells.get(&type_hash) == Some(&out_point) {
+                    self.type_cells.remove(&type_hash);
+                    followed.insert(type_hash);
+                }
+            }
+        }
+        for (i, (output, data)) in tx.outputs_with_data_iter().enumerate() {
+            let out_point = OutPoint::new(tx.hash(), i as u32);
+            if let Some(type_script) = output.type_().to_opt() {
+                let type_hash = type_script.calc_script_hash();
+                if self.is_zero_lock(&output.lock()) || followed.contains(&type_hash) {
+                    self.type_cells.insert(type_hash, out_point.clone());
+                }
+            }
+            self.cells.insert(out_point, (output, data));
+        }
+    }
+
+    /// All zero lock inputs found, in chain order.
+    pub fn records(&self) -> &[UpgradeRecord] {
+        &self.records
+    }
+
+    /// Current live cell with the type script hash, such as a Type ID,
+    /// among cells which have been in zero lock.
+    pub fn live_cell_by_type(&self, type_hash: &Byte32) -> Option<&OutPoint> {
+        self.type_cells.get(type_hash)
+    }
+
+    /// Upgrades of the cell lineage containing `out_point`, which can be any
+    /// cell in the lineage, from the earliest to the latest.
+    pub fn upgrade_chain(&self, out_point: &OutPoint) -> Vec<&UpgradeRecord> {
+        let mut first = out_point.clone();
+        while let Some(index) = self.by_output.get(&first) {
+            first = self.records[*index].input_out_point.clone();
+        }
+        let mut chain = Vec::new();
+        let mut current = first;
+        while let Some(index) = self.by_input.get(&current) {
+            let record = &self.records[*index];
+            chain.push(record);
+            match &record.upgrade {
+                Ok(upgrade) => current = upgrade.output_out_point.clone(),
+                Err(_) => break,
+            }
+        }
+        chain
+    }
+}
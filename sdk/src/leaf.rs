@@ -0,0 +1,89 @@
+use crate::hash::{TreeHasher, LEAF_VERSION};
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    packed::{Byte32, CellOutput, OutPoint, WitnessArgs},
+    prelude::*,
+};
+
+/// Leaf trailer carrying `leaf_version` and `leaf_options`: the version
+/// byte, length of leaf options as little-endian u32, then leaf options.
+pub fn leaf_trailer(leaf_version: u8, leaf_options: &[u8]) -> Bytes {
+    let mut trailer = vec![leaf_version];
+    trailer.extend((leaf_options.len() as u32).to_le_bytes());
+    trailer.extend_from_slice(leaf_options);
+    trailer.into()
+}
+
+/// Content committed in a merkle leaf, authorizing zero lock to upgrade the
+/// cell at `input_out_point` to `output`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeLeaf {
+    /// LEAF_VERSION, or a version using leaf trailer, in which case leaf
+    /// options are committed as well.
+    pub leaf_version: u8,
+    pub leaf_options: Bytes,
+    pub input_out_point: OutPoint,
+    pub output: CellOutput,
+    /// Data hash of the new cell. For a dep group option, this is the hash of
+    /// the template data with placeholder entries zeroed.
+    pub output_data_hash: Byte32,
+    pub input_type: Option<Bytes>,
+    pub output_type: Option<Bytes>,
+}
+
+impl UpgradeLeaf {
+    /// Creates a leaf using the original layout, without witness remainder.
+    pub fn new(input_out_point: OutPoint, output: CellOutput, output_data: &[u8]) -> Self {
+        Self {
+            leaf_version: LEAF_VERSION,
+            leaf_options: Bytes::new(),
+            input_out_point,
+            output,
+            output_data_hash: blake2b_256(output_data).pack(),
+            input_type: None,
+            output_type: None,
+        }
+    }
+
+    pub fn hash(&self) -> Byte32 {
+        let mut hasher = TreeHasher::for_leaf_version(self.leaf_version);
+        hasher.update(&[self.leaf_version]);
+        if self.leaf_version != LEAF_VERSION {
+            hasher.update(&(self.leaf_options.len() as u32).to_le_bytes());
+            hasher.update(&self.leaf_options);
+        }
+        hasher.update(self.input_out_point.as_slice());
+        hasher.update(self.output_data_hash.as_slice());
+        hasher.update(self.output.as_slice());
+        for field in [&self.input_type, &self.output_type] {
+            match field {
+                Some(field) => {
+                    hasher.update(&[1u8]);
+                    hasher.update(&(field.len() as u32).to_le_bytes());
+                    hasher.update(field);
+                }
+                None => hasher.update(&[0u8]),
+            }
+        }
+        hasher.finalize()
+    }
+
+    /// Leaf trailer appended to the merkle proof, empty for LEAF_VERSION.
+    pub fn trailer(&self) -> Bytes {
+        if self.leaf_version == LEAF_VERSION {
+            return Bytes::new();
+        }
+        leaf_trailer(self.leaf_version, &self.leaf_options)
+    }
+
+    /// Builds the witness of the upgrade transaction, using `proof` as
+    /// witness lock, which can be a merkle proof or a proof pointer.
+    pub fn witness(&self, proof: Bytes) -> WitnessArgs {
+        WitnessArgs::new_builder()
+            .lock(Some(proof).pack())
+            .input_type(self.input_type.clone().pack())
+            .output_type(self.output_type.clone().pack())
+            .build()
+    }
+}
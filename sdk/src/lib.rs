@@ -0,0 +1,43 @@
+//! Helpers for assembling and verifying transactions that upgrade cells
+//! locked by zero lock.
+
+mod audit;
+mod extension;
+mod hash;
+mod indexer;
+mod leaf;
+mod manifest;
+mod metadata;
+mod proof_cell;
+mod proof_size;
+mod tree;
+mod tree_file;
+mod upgrade_tx;
+mod validate;
+mod verifier;
+
+pub use audit::{AuditEntry, AuditReport, Auditor, Discrepancy};
+pub use extension::{
+    read_zero_lock_root, ExtensionBuilder, ExtensionError, Region, RegionKind,
+    DEFAULT_MAX_EXTENSION_SIZE, MERKLE_ROOT_OFFSET, ZERO_LOCK_ROOT_REGION,
+};
+pub use hash::{
+    Blake2bMerge, Sha256Merge, TreeHasher, EXTENDED_LEAF_VERSION, LEAF_VERSION, SHA256_LEAF_VERSION,
+};
+pub use indexer::{
+    load_blocks, DecodedProof, IndexedUpgrade, IndexerError, UpgradeIndexer, UpgradeRecord,
+};
+pub use leaf::{leaf_trailer, UpgradeLeaf};
+pub use manifest::{Manifest, ManifestEntry, ManifestError, MANIFEST_VERSION};
+pub use metadata::{SemanticVersion, UpgradeMetadata};
+pub use proof_cell::{ProofCellBuilder, ProofPointer, PROOF_POINTER_SIZE};
+pub use proof_size::{
+    proof_lemmas, BatchProofSize, LeafProofSize, ProofSizeReport, DEFAULT_OUTLIER_FACTOR,
+};
+pub use tree::UpgradeTree;
+pub use tree_file::{TreeFile, TreeFileError, TREE_FILE_VERSION};
+pub use upgrade_tx::{FeeCell, UpgradeTx, UpgradeTxBuilder, UpgradeTxError, DEFAULT_FEE_RATE};
+pub use validate::{
+    Diagnostic, Severity, TreeValidator, DEFAULT_MAX_WITNESS_SIZE, TYPE_ID_CODE_HASH,
+};
+pub use verifier::{verify_mock_transaction, VerifiedUpgrade, VerifyError};
@@ -0,0 +1,376 @@
+//! Declarative description of the cells upgraded in a hardfork. A manifest
+//! lists upgrade entries in TOML or JSON:
+//!
+//! ```toml
+//! version = 1
+//!
+//! [[upgrade]]
+//! input_out_point = { tx_hash = "0x...", index = "0x0" }
+//! capacity = "0x174876e800"
+//! lock = { code_hash = "0x...", hash_type = "type", args = "0x00" }
+//! type = { code_hash = "0x...", hash_type = "type", args = "0x..." }
+//! data = "new_contract.bin"
+//! output_type = "0x..."
+//! batch = 1
+//! ```
+//!
+//! Either `data`, a path relative to the manifest, or `data_hash` must be
+//! provided. `leaf_version`, `leaf_options`, `input_type`, `output_type` and
+//! `batch` are optional. Entries are sorted by input out point, so the tree
+//! and its root do not depend on the order entries are written in.
+//!
+//! `batch` groups entries expected to be upgraded together, either at the
+//! same time or because the contracts are related. Batched entries come
+//! first, in ascending batch order, so each batch occupies adjacent leaves
+//! and earlier batches take the shallower leaves of the tree, whose proofs
+//! carry one lemma less, see `ProofSizeReport`.
+
+use crate::{
+    hash::{EXTENDED_LEAF_VERSION, LEAF_VERSION, SHA256_LEAF_VERSION},
+    leaf::UpgradeLeaf,
+    tree::UpgradeTree,
+};
+use ckb_hash::{blake2b_256, new_blake2b};
+use ckb_jsonrpc_types as json_types;
+use ckb_types::{
+    bytes::Bytes,
+    packed::{Byte32, CellOutput, OutPoint, Script},
+    prelude::*,
+};
+use ckb_zero_lock_core::leaf::LeafOptions;
+use serde::Deserialize;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Version of the manifest format understood by this SDK.
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawManifest {
+    version: u32,
+    #[serde(default, rename = "upgrade")]
+    upgrades: Vec<RawEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawEntry {
+    input_out_point: json_types::OutPoint,
+    capacity: json_types::Capacity,
+    lock: json_types::Script,
+    #[serde(default, rename = "type")]
+    type_: Option<json_types::Script>,
+    #[serde(default)]
+    data: Option<PathBuf>,
+    #[serde(default)]
+    data_hash: Option<json_types::JsonBytes>,
+    #[serde(default)]
+    leaf_version: Option<u8>,
+    #[serde(default)]
+    leaf_options: Option<json_types::JsonBytes>,
+    #[serde(default)]
+    input_type: Option<json_types::JsonBytes>,
+    #[serde(default)]
+    output_type: Option<json_types::JsonBytes>,
+    #[serde(default)]
+    batch: Option<u64>,
+}
+
+/// Reason a manifest is rejected. `index` refers to the position of the
+/// entry as written in the manifest, before sorting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestError {
+    Read {
+        path: PathBuf,
+        reason: String,
+    },
+    UnknownFormat(PathBuf),
+    Parse(String),
+    UnsupportedVersion(u32),
+    Empty,
+    MissingData {
+        index: usize,
+    },
+    DataAndDataHash {
+        index: usize,
+    },
+    InvalidDataHash {
+        index: usize,
+    },
+    ReadData {
+        index: usize,
+        path: PathBuf,
+        reason: String,
+    },
+    UnsupportedLeafVersion {
+        index: usize,
+        leaf_version: u8,
+    },
+    UnexpectedLeafOptions {
+        index: usize,
+    },
+    InvalidLeafOptions {
+        index: usize,
+    },
+    MixedHashes,
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Read { path, reason } => {
+                write!(f, "cannot read manifest {}: {}", path.display(), reason)
+            }
+            ManifestError::UnknownFormat(path) => write!(
+                f,
+                "manifest {} must have .toml or .json extension",
+                path.display()
+            ),
+            ManifestError::Parse(reason) => write!(f, "invalid manifest: {}", reason),
+            ManifestError::UnsupportedVersion(version) => write!(
+                f,
+                "manifest version {} is not supported, expected {}",
+                version, MANIFEST_VERSION
+            ),
+            ManifestError::Empty => write!(f, "manifest contains no upgrade"),
+            ManifestError::MissingData { index } => {
+                write!(f, "upgrade {} has neither data nor data_hash", index)
+            }
+            ManifestError::DataAndDataHash { index } => {
+                write!(f, "upgrade {} has both data and data_hash", index)
+            }
+            ManifestError::InvalidDataHash { index } => {
+                write!(f, "data_hash of upgrade {} is not 32 bytes", index)
+            }
+            ManifestError::ReadData {
+                index,
+                path,
+                reason,
+            } => write!(
+                f,
+                "cannot read data {} of upgrade {}: {}",
+                path.display(),
+                index,
+                reason
+            ),
+            ManifestError::UnsupportedLeafVersion {
+                index,
+                leaf_version,
+            } => write!(
+                f,
+                "upgrade {} uses unsupported leaf version {}",
+                index, leaf_version
+            ),
+            ManifestError::UnexpectedLeafOptions { index } => write!(
+                f,
+                "upgrade {} has leaf options, which leaf version {} cannot carry",
+                index, LEAF_VERSION
+            ),
+            ManifestError::InvalidLeafOptions { index } => {
+                write!(f, "leaf options of upgrade {} are malformed", index)
+            }
+            ManifestError::MixedHashes => write!(
+                f,
+                "leaf version {} cannot be mixed with other leaf versions",
+                SHA256_LEAF_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+/// One upgrade of a manifest. `data` is None when the manifest only
+/// provides the data hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub leaf: UpgradeLeaf,
+    pub data: Option<Bytes>,
+    pub batch: Option<u64>,
+}
+
+/// A validated manifest, with entries sorted by batch, then by input out
+/// point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Loads a manifest, the format is decided by the file extension. Data
+    /// paths are resolved relative to the directory of the manifest.
+    pub fn load(path: &Path) -> Result<Self, ManifestError> {
+        let content = std::fs::read_to_string(path).map_err(|e| ManifestError::Read {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })?;
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml_str(&content, base_dir),
+            Some("json") => Self::from_json_str(&content, base_dir),
+            _ => Err(ManifestError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+
+    pub fn from_toml_str(content: &str, base_dir: &Path) -> Result<Self, ManifestError> {
+        let raw = toml::from_str(content).map_err(|e| ManifestError::Parse(e.to_string()))?;
+        Self::from_raw(raw, base_dir)
+    }
+
+    pub fn from_json_str(content: &str, base_dir: &Path) -> Result<Self, ManifestError> {
+        let raw = serde_json::from_str(content).map_err(|e| ManifestError::Parse(e.to_string()))?;
+        Self::from_raw(raw, base_dir)
+    }
+
+    fn from_raw(raw: RawManifest, base_dir: &Path) -> Result<Self, ManifestError> {
+        if raw.version != MANIFEST_VERSION {
+            return Err(ManifestError::UnsupportedVersion(raw.version));
+        }
+        if raw.upgrades.is_empty() {
+            return Err(ManifestError::Empty);
+        }
+        let mut entries = raw
+            .upgrades
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| parse_entry(index, entry, base_dir))
+            .collect::<Result<Vec<_>, _>>()?;
+        let sha256 = entries[0].leaf.leaf_version == SHA256_LEAF_VERSION;
+        if entries
+            .iter()
+            .any(|entry| (entry.leaf.leaf_version == SHA256_LEAF_VERSION) != sha256)
+        {
+            return Err(ManifestError::MixedHashes);
+        }
+        // Leaf hash breaks ties, so even entries sharing an input out point
+        // are ordered deterministically. Without batches, the order is the
+        // one of input out points.
+        entries.sort_by_cached_key(|entry| {
+            let out_point = &entry.leaf.input_out_point;
+            let index: u32 = out_point.index().unpack();
+            (
+                entry.batch.is_none(),
+                entry.batch,
+                out_point.tx_hash().raw_data(),
+                index,
+                entry.leaf.hash().raw_data(),
+            )
+        });
+        Ok(Self { entries })
+    }
+
+    pub fn entries(&self) -> &[ManifestEntry] {
+        &self.entries
+    }
+
+    pub fn leaves(&self) -> Vec<UpgradeLeaf> {
+        self.entries
+            .iter()
+            .map(|entry| entry.leaf.clone())
+            .collect()
+    }
+
+    /// Position of the entry upgrading `out_point` in the tree.
+    pub fn position(&self, out_point: &OutPoint) -> Option<u32> {
+        self.entries
+            .iter()
+            .position(|entry| &entry.leaf.input_out_point == out_point)
+            .map(|index| index as u32)
+    }
+
+    pub fn tree(&self) -> UpgradeTree {
+        UpgradeTree::build(&self.leaves()).expect("validated manifest")
+    }
+
+    /// Hash identifying manifest content, independent of entry order and
+    /// formatting, see `manifest_hash`.
+    pub fn content_hash(&self) -> Byte32 {
+        let leaves: Vec<Byte32> = self.entries.iter().map(|entry| entry.leaf.hash()).collect();
+        manifest_hash(&leaves)
+    }
+}
+
+/// Blake2b of the manifest version, the number of leaves, and all leaf
+/// hashes in tree order.
+pub(crate) fn manifest_hash(leaves: &[Byte32]) -> Byte32 {
+    let mut hasher = new_blake2b();
+    hasher.update(&MANIFEST_VERSION.to_le_bytes());
+    hasher.update(&(leaves.len() as u32).to_le_bytes());
+    for leaf in leaves {
+        hasher.update(leaf.as_slice());
+    }
+    let mut hash = [0u8; 32];
+    hasher.finalize(&mut hash);
+    Byte32::new(hash)
+}
+
+fn parse_entry(
+    index: usize,
+    entry: RawEntry,
+    base_dir: &Path,
+) -> Result<ManifestEntry, ManifestError> {
+    let (data, data_hash) = match (entry.data, entry.data_hash) {
+        (Some(path), None) => {
+            let path = base_dir.join(path);
+            let data = std::fs::read(&path).map_err(|e| ManifestError::ReadData {
+                index,
+                path,
+                reason: e.to_string(),
+            })?;
+            let data_hash = blake2b_256(&data).pack();
+            (Some(Bytes::from(data)), data_hash)
+        }
+        (None, Some(data_hash)) => {
+            let data_hash = Byte32::from_slice(data_hash.as_bytes())
+                .map_err(|_| ManifestError::InvalidDataHash { index })?;
+            (None, data_hash)
+        }
+        (Some(_), Some(_)) => return Err(ManifestError::DataAndDataHash { index }),
+        (None, None) => return Err(ManifestError::MissingData { index }),
+    };
+
+    let leaf_version = entry.leaf_version.unwrap_or(LEAF_VERSION);
+    if !matches!(
+        leaf_version,
+        LEAF_VERSION | EXTENDED_LEAF_VERSION | SHA256_LEAF_VERSION
+    ) {
+        return Err(ManifestError::UnsupportedLeafVersion {
+            index,
+            leaf_version,
+        });
+    }
+    let leaf_options = entry
+        .leaf_options
+        .map(|o| o.into_bytes())
+        .unwrap_or_default();
+    if leaf_version == LEAF_VERSION {
+        if !leaf_options.is_empty() {
+            return Err(ManifestError::UnexpectedLeafOptions { index });
+        }
+    } else if LeafOptions::parse(&leaf_options).is_none() {
+        return Err(ManifestError::InvalidLeafOptions { index });
+    }
+
+    let output = CellOutput::new_builder()
+        .capacity(u64::from(entry.capacity).pack())
+        .lock(entry.lock.into())
+        .type_(entry.type_.map(Script::from).pack())
+        .build();
+    let leaf = UpgradeLeaf {
+        leaf_version,
+        leaf_options,
+        input_out_point: entry.input_out_point.into(),
+        output,
+        output_data_hash: data_hash,
+        input_type: entry.input_type.map(|b| b.into_bytes()),
+        output_type: entry.output_type.map(|b| b.into_bytes()),
+    };
+    Ok(ManifestEntry {
+        leaf,
+        data,
+        batch: entry.batch,
+    })
+}
@@ -0,0 +1,103 @@
+use ckb_types::bytes::Bytes;
+
+const UPGRADE_METADATA_FIELDS: usize = 4;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SemanticVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+/// Upgrade metadata as defined in schemas/upgrade_metadata.mol, to be used
+/// as input_type or output_type field in the witness of an upgrade
+/// transaction.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UpgradeMetadata {
+    pub hardfork: String,
+    pub contract: String,
+    pub version: SemanticVersion,
+    pub release_notes_hash: [u8; 32],
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<usize> {
+    let t = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(t.try_into().unwrap()) as usize)
+}
+
+fn encode_bytes(data: &[u8]) -> Vec<u8> {
+    let mut encoded = (data.len() as u32).to_le_bytes().to_vec();
+    encoded.extend_from_slice(data);
+    encoded
+}
+
+fn decode_string(data: &[u8]) -> Option<String> {
+    if read_u32(data, 0)? != data.len() - 4 {
+        return None;
+    }
+    String::from_utf8(data[4..].to_vec()).ok()
+}
+
+impl UpgradeMetadata {
+    /// Serializes upgrade metadata as a molecule table.
+    pub fn to_bytes(&self) -> Bytes {
+        let mut version = Vec::with_capacity(12);
+        version.extend(self.version.major.to_le_bytes());
+        version.extend(self.version.minor.to_le_bytes());
+        version.extend(self.version.patch.to_le_bytes());
+        let fields = [
+            encode_bytes(self.hardfork.as_bytes()),
+            encode_bytes(self.contract.as_bytes()),
+            version,
+            self.release_notes_hash.to_vec(),
+        ];
+
+        let header_size = 4 * (UPGRADE_METADATA_FIELDS + 1);
+        let total_size = header_size + fields.iter().map(Vec::len).sum::<usize>();
+        let mut data = Vec::with_capacity(total_size);
+        data.extend((total_size as u32).to_le_bytes());
+        let mut offset = header_size;
+        for field in &fields {
+            data.extend((offset as u32).to_le_bytes());
+            offset += field.len();
+        }
+        for field in &fields {
+            data.extend(field);
+        }
+        data.into()
+    }
+
+    /// Parses upgrade metadata, None is returned for malformed data, or
+    /// names that are not valid UTF-8.
+    pub fn from_slice(data: &[u8]) -> Option<Self> {
+        let header_size = 4 * (UPGRADE_METADATA_FIELDS + 1);
+        if read_u32(data, 0)? != data.len() || read_u32(data, 4)? != header_size {
+            return None;
+        }
+        let mut offsets = Vec::with_capacity(UPGRADE_METADATA_FIELDS + 1);
+        for i in 0..UPGRADE_METADATA_FIELDS {
+            offsets.push(read_u32(data, 4 * (i + 1))?);
+        }
+        offsets.push(data.len());
+        if offsets.windows(2).any(|w| w[0] > w[1]) {
+            return None;
+        }
+        let field = |i: usize| &data[offsets[i]..offsets[i + 1]];
+
+        let version = field(2);
+        let release_notes_hash = field(3);
+        if version.len() != 12 || release_notes_hash.len() != 32 {
+            return None;
+        }
+        Some(Self {
+            hardfork: decode_string(field(0))?,
+            contract: decode_string(field(1))?,
+            version: SemanticVersion {
+                major: read_u32(version, 0)? as u32,
+                minor: read_u32(version, 4)? as u32,
+                patch: read_u32(version, 8)? as u32,
+            },
+            release_notes_hash: release_notes_hash.try_into().unwrap(),
+        })
+    }
+}
@@ -0,0 +1,77 @@
+use ckb_types::{
+    bytes::Bytes,
+    core::Capacity,
+    packed::{CellOutput, Script},
+    prelude::*,
+};
+
+pub use ckb_zero_lock_core::proof_pointer::PROOF_POINTER_SIZE;
+
+/// Points zero lock to a merkle proof stored in the data of a cell dep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofPointer {
+    pub cell_dep_index: u32,
+    pub offset: u32,
+    pub length: u32,
+}
+
+impl ProofPointer {
+    /// Serializes the pointer, to be used as the lock field of WitnessArgs.
+    pub fn to_bytes(&self) -> Bytes {
+        let mut data = Vec::with_capacity(PROOF_POINTER_SIZE);
+        data.extend(self.cell_dep_index.to_le_bytes());
+        data.extend(self.offset.to_le_bytes());
+        data.extend(self.length.to_le_bytes());
+        data.into()
+    }
+}
+
+/// Builds a proof cell, whose data contains one or more merkle proofs, so
+/// that they can be shared by many upgrade transactions.
+#[derive(Debug, Default, Clone)]
+pub struct ProofCellBuilder {
+    data: Vec<u8>,
+    ranges: Vec<(u32, u32)>,
+}
+
+impl ProofCellBuilder {
+    /// Appends a merkle proof, as it would appear in the witness lock, and
+    /// returns its index in the proof cell.
+    pub fn push(&mut self, proof: &[u8]) -> usize {
+        let offset = u32::try_from(self.data.len()).expect("proof cell too large");
+        let length = u32::try_from(proof.len()).expect("proof too large");
+        assert!(
+            proof.len() > PROOF_POINTER_SIZE,
+            "proof cannot be confused with a proof pointer"
+        );
+        self.data.extend_from_slice(proof);
+        self.ranges.push((offset, length));
+        self.ranges.len() - 1
+    }
+
+    /// Returns the pointer to the proof at `index`, when the proof cell is
+    /// included as cell dep at `cell_dep_index` in the upgrade transaction.
+    pub fn pointer(&self, index: usize, cell_dep_index: u32) -> ProofPointer {
+        let (offset, length) = self.ranges[index];
+        ProofPointer {
+            cell_dep_index,
+            offset,
+            length,
+        }
+    }
+
+    pub fn data(&self) -> Bytes {
+        Bytes::from(self.data.clone())
+    }
+
+    /// Builds the proof cell using the provided lock, with exactly the
+    /// capacity occupied by the cell.
+    pub fn build(&self, lock: Script) -> (CellOutput, Bytes) {
+        let data = self.data();
+        let output = CellOutput::new_builder()
+            .lock(lock)
+            .build_exact_capacity(Capacity::bytes(data.len()).expect("data capacity"))
+            .expect("proof cell capacity");
+        (output, data)
+    }
+}
@@ -0,0 +1,188 @@
+//! Proof sizes of an upgrade tree. Upgraders pay for every witness byte, and
+//! lemmas dominate the witness of small leaves. In a CBMT of n leaves, the
+//! first leaves sit one level above the others when n is not a power of two,
+//! so their proofs carry one lemma less. The total over all leaves does not
+//! depend on the order, but the upgrades expected first can take the
+//! shallow leaves, as manifest batches do. Zero lock verifies one leaf per
+//! proof, so every upgrade pays for its own proof: batches save nothing on
+//! lemmas, but can share one proof cell holding all their proofs.
+
+use crate::{
+    leaf::UpgradeLeaf,
+    manifest::Manifest,
+    proof_cell::{ProofCellBuilder, PROOF_POINTER_SIZE},
+};
+use ckb_types::{bytes::Bytes, prelude::*};
+use std::{collections::BTreeMap, fmt};
+
+/// Leaves whose witness exceeds the median witness size by this factor are
+/// reported by default.
+pub const DEFAULT_OUTLIER_FACTOR: usize = 2;
+
+/// Number of lemmas in the proof of the leaf at `position`, in a tree of
+/// `leaves` leaves.
+pub fn proof_lemmas(leaves: usize, position: usize) -> usize {
+    if leaves <= 1 {
+        return 0;
+    }
+    // Leaf at `position` is node `position + leaves - 1`, whose depth is
+    // the floor of log2 of the node index plus one
+    (position + leaves).ilog2() as usize
+}
+
+// Proof layout of UpgradeTree::proof: header indices, leaf indices and
+// lemmas, each list but header indices prefixed by its length
+fn proof_size(header_count: usize, indices: usize, lemmas: usize) -> usize {
+    4 * header_count + 4 + 4 * indices + 4 + 32 * lemmas
+}
+
+/// Sizes of the proof of one leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeafProofSize {
+    pub position: usize,
+    pub lemmas: usize,
+    /// Size of the proof in the witness lock, leaf trailer included.
+    pub proof_size: usize,
+    /// Size of the whole WitnessArgs carrying the proof.
+    pub witness_size: usize,
+}
+
+/// Sizes of the proofs of a manifest batch, one proof per leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchProofSize {
+    pub batch: u64,
+    pub positions: Vec<usize>,
+    /// Lemmas over all proofs of the batch.
+    pub lemmas: usize,
+    /// Sum of the proof sizes of the batch leaves, leaf trailers included.
+    pub proof_size: usize,
+    /// Data size of a proof cell holding the proofs of the batch.
+    pub proof_cell_size: usize,
+    /// Sum of the witness sizes of the batch leaves, when their proofs are
+    /// read from the proof cell.
+    pub pointer_witness_size: usize,
+}
+
+/// Proof sizes of every leaf of a tree, with `header_count` header indices
+/// in each proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofSizeReport {
+    pub leaves: Vec<LeafProofSize>,
+    pub batches: Vec<BatchProofSize>,
+}
+
+impl ProofSizeReport {
+    /// Reports proof sizes of `leaves`, in tree order.
+    pub fn new(leaves: &[UpgradeLeaf], header_count: usize) -> Self {
+        let leaves = leaves
+            .iter()
+            .enumerate()
+            .map(|(position, leaf)| {
+                let lemmas = proof_lemmas(leaves.len(), position);
+                let proof_size = proof_size(header_count, 1, lemmas) + leaf.trailer().len();
+                let witness_size = leaf
+                    .witness(Bytes::from(vec![0; proof_size]))
+                    .as_slice()
+                    .len();
+                LeafProofSize {
+                    position,
+                    lemmas,
+                    proof_size,
+                    witness_size,
+                }
+            })
+            .collect();
+        Self {
+            leaves,
+            batches: vec![],
+        }
+    }
+
+    /// Reports proof sizes of the manifest tree, together with the proofs
+    /// of each batch.
+    pub fn from_manifest(manifest: &Manifest, header_count: usize) -> Self {
+        let leaves = manifest.leaves();
+        let mut report = Self::new(&leaves, header_count);
+        let mut batches: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+        for (position, entry) in manifest.entries().iter().enumerate() {
+            if let Some(batch) = entry.batch {
+                batches.entry(batch).or_default().push(position);
+            }
+        }
+        report.batches = batches
+            .into_iter()
+            .map(|(batch, positions)| report.batch(&leaves, batch, positions))
+            .collect();
+        report
+    }
+
+    fn batch(&self, leaves: &[UpgradeLeaf], batch: u64, positions: Vec<usize>) -> BatchProofSize {
+        let mut proof_cell = ProofCellBuilder::default();
+        let (mut lemmas, mut proof_size, mut pointer_witness_size) = (0, 0, 0);
+        for position in &positions {
+            let size = &self.leaves[*position];
+            lemmas += size.lemmas;
+            proof_size += size.proof_size;
+            proof_cell.push(&vec![0; size.proof_size]);
+            pointer_witness_size += leaves[*position]
+                .witness(Bytes::from(vec![0; PROOF_POINTER_SIZE]))
+                .as_slice()
+                .len();
+        }
+        BatchProofSize {
+            batch,
+            positions,
+            lemmas,
+            proof_size,
+            proof_cell_size: proof_cell.data().len(),
+            pointer_witness_size,
+        }
+    }
+
+    pub fn total_proof_size(&self) -> usize {
+        self.leaves.iter().map(|leaf| leaf.proof_size).sum()
+    }
+
+    pub fn total_witness_size(&self) -> usize {
+        self.leaves.iter().map(|leaf| leaf.witness_size).sum()
+    }
+
+    /// Leaves whose witness is above `factor` times the median witness
+    /// size, usually because of large leaf options or type fields.
+    pub fn outliers(&self, factor: usize) -> impl Iterator<Item = &LeafProofSize> {
+        let mut sizes: Vec<usize> = self.leaves.iter().map(|leaf| leaf.witness_size).collect();
+        sizes.sort_unstable();
+        let limit = sizes.get(sizes.len() / 2).copied().unwrap_or(0) * factor;
+        self.leaves
+            .iter()
+            .filter(move |leaf| leaf.witness_size > limit)
+    }
+}
+
+impl fmt::Display for ProofSizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Leaves: {}", self.leaves.len())?;
+        writeln!(f, "Total proof size: {}", self.total_proof_size())?;
+        writeln!(f, "Total witness size: {}", self.total_witness_size())?;
+        for leaf in &self.leaves {
+            writeln!(
+                f,
+                "Leaf {}: {} lemmas, proof {} bytes, witness {} bytes",
+                leaf.position, leaf.lemmas, leaf.proof_size, leaf.witness_size
+            )?;
+        }
+        for batch in &self.batches {
+            writeln!(
+                f,
+                "Batch {}: {} leaves, {} lemmas, proofs {} bytes, proof cell {} bytes and pointer witnesses {} bytes",
+                batch.batch,
+                batch.positions.len(),
+                batch.lemmas,
+                batch.proof_size,
+                batch.proof_cell_size,
+                batch.pointer_witness_size
+            )?;
+        }
+        Ok(())
+    }
+}
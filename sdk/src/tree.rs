@@ -0,0 +1,110 @@
+use crate::{
+    extension::ExtensionBuilder,
+    hash::{Blake2bMerge, Sha256Merge, SHA256_LEAF_VERSION},
+    leaf::UpgradeLeaf,
+};
+use ckb_types::{bytes::Bytes, packed::Byte32, prelude::*};
+use merkle_cbt::{merkle_tree::Merge, MerkleTree, CBMT};
+
+/// Merkle tree of upgrade leaves, whose root is published in the block
+/// extension of a hardfork block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeTree {
+    leaves: Vec<Byte32>,
+    sha256: bool,
+}
+
+fn build_proof<M: Merge<Item = Byte32>>(leaves: &[Byte32], index: u32) -> (Vec<u32>, Vec<Byte32>) {
+    let tree: MerkleTree<Byte32, M> = CBMT::build_merkle_tree(leaves);
+    let proof = tree.build_proof(&[index]).expect("build merkle proof");
+    (proof.indices().to_vec(), proof.lemmas().to_vec())
+}
+
+impl UpgradeTree {
+    /// Builds the tree from leaves in the given order. A tree hashed with
+    /// SHA-256 can only contain SHA256_LEAF_VERSION leaves and vice versa,
+    /// None is returned when the two are mixed, or no leaf is provided.
+    pub fn build(leaves: &[UpgradeLeaf]) -> Option<Self> {
+        let sha256 = leaves.first()?.leaf_version == SHA256_LEAF_VERSION;
+        if leaves
+            .iter()
+            .any(|leaf| (leaf.leaf_version == SHA256_LEAF_VERSION) != sha256)
+        {
+            return None;
+        }
+        Some(Self::from_hashes(
+            leaves.iter().map(UpgradeLeaf::hash).collect(),
+            sha256,
+        ))
+    }
+
+    pub fn from_hashes(leaves: Vec<Byte32>, sha256: bool) -> Self {
+        Self { leaves, sha256 }
+    }
+
+    pub fn leaves(&self) -> &[Byte32] {
+        &self.leaves
+    }
+
+    pub fn root(&self) -> Byte32 {
+        if self.sha256 {
+            CBMT::<Byte32, Sha256Merge>::build_merkle_root(&self.leaves)
+        } else {
+            CBMT::<Byte32, Blake2bMerge>::build_merkle_root(&self.leaves)
+        }
+    }
+
+    fn build_proof(&self, index: u32) -> (Vec<u32>, Vec<Byte32>) {
+        if self.sha256 {
+            build_proof::<Sha256Merge>(&self.leaves, index)
+        } else {
+            build_proof::<Blake2bMerge>(&self.leaves, index)
+        }
+    }
+
+    /// Builds the merkle proof for the leaf at `index`, as it appears in the
+    /// witness lock. `header_indices` name header deps carrying the root, and
+    /// `leaf` must be the leaf at `index`, so the leaf trailer can be
+    /// appended. None is returned when `index` is out of range, or `leaf` is
+    /// not the leaf at `index`.
+    pub fn proof(&self, index: u32, header_indices: &[u32], leaf: &UpgradeLeaf) -> Option<Bytes> {
+        if *self.leaves.get(index as usize)? != leaf.hash() {
+            return None;
+        }
+        let mut data = self.merkle_proof(index, header_indices)?.to_vec();
+        data.extend_from_slice(&leaf.trailer());
+        Some(data.into())
+    }
+
+    /// Builds the merkle proof for the leaf at `index` without leaf trailer,
+    /// for callers holding only leaf hashes. None is returned when `index` is
+    /// out of range.
+    pub fn merkle_proof(&self, index: u32, header_indices: &[u32]) -> Option<Bytes> {
+        if index as usize >= self.leaves.len() {
+            return None;
+        }
+        let (indices, lemmas) = self.build_proof(index);
+        let mut data = vec![];
+        for header_index in header_indices {
+            data.extend(header_index.to_le_bytes());
+        }
+        data.extend((indices.len() as u32).to_le_bytes());
+        for index in indices {
+            data.extend(index.to_le_bytes());
+        }
+        data.extend((lemmas.len() as u32).to_le_bytes());
+        for lemma in lemmas {
+            data.extend(lemma.as_slice());
+        }
+        Some(data.into())
+    }
+
+    /// Block extension carrying only the merkle root, with the bytes before
+    /// the root zeroed. Use ExtensionBuilder when the extension is shared.
+    pub fn extension(&self) -> Bytes {
+        ExtensionBuilder::new()
+            .zero_lock_root(&self.root())
+            .build()
+            .expect("root only layout")
+    }
+}
@@ -0,0 +1,338 @@
+//! Persistent form of an upgrade tree. Proofs are requested long after the
+//! root is published, so the tree file keeps the preimage of every leaf, and
+//! is checked against the recorded leaf hashes, root and manifest hash each
+//! time it is loaded.
+
+use crate::{
+    hash::{EXTENDED_LEAF_VERSION, LEAF_VERSION, SHA256_LEAF_VERSION},
+    leaf::UpgradeLeaf,
+    manifest::{manifest_hash, Manifest},
+    tree::UpgradeTree,
+    validate::{Diagnostic, TreeValidator},
+};
+use ckb_jsonrpc_types as json_types;
+use ckb_types::{
+    bytes::Bytes,
+    packed::{Byte32, OutPoint, WitnessArgs},
+    prelude::*,
+    H256,
+};
+use ckb_zero_lock_core::leaf::LeafOptions;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Version of the tree file format written by this SDK.
+pub const TREE_FILE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTreeFile {
+    version: u32,
+    manifest_hash: H256,
+    root: H256,
+    leaves: Vec<RawLeaf>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawLeaf {
+    hash: H256,
+    leaf_version: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    leaf_options: Option<json_types::JsonBytes>,
+    input_out_point: json_types::OutPoint,
+    output: json_types::CellOutput,
+    output_data_hash: H256,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    input_type: Option<json_types::JsonBytes>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output_type: Option<json_types::JsonBytes>,
+}
+
+/// Reason a tree file is rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeFileError {
+    Read {
+        path: PathBuf,
+        reason: String,
+    },
+    Write {
+        path: PathBuf,
+        reason: String,
+    },
+    Parse(String),
+    UnsupportedVersion(u32),
+    Empty,
+    UnsupportedLeafVersion {
+        leaf: usize,
+        leaf_version: u8,
+    },
+    UnexpectedLeafOptions {
+        leaf: usize,
+    },
+    InvalidLeafOptions {
+        leaf: usize,
+    },
+    MixedHashes,
+    LeafHashMismatch {
+        leaf: usize,
+        recorded: Byte32,
+        actual: Byte32,
+    },
+    RootMismatch {
+        recorded: Byte32,
+        actual: Byte32,
+    },
+    ManifestHashMismatch {
+        recorded: Byte32,
+        actual: Byte32,
+    },
+}
+
+impl fmt::Display for TreeFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeFileError::Read { path, reason } => {
+                write!(f, "cannot read tree file {}: {}", path.display(), reason)
+            }
+            TreeFileError::Write { path, reason } => {
+                write!(f, "cannot write tree file {}: {}", path.display(), reason)
+            }
+            TreeFileError::Parse(reason) => write!(f, "invalid tree file: {}", reason),
+            TreeFileError::UnsupportedVersion(version) => write!(
+                f,
+                "tree file version {} is not supported, expected {}",
+                version, TREE_FILE_VERSION
+            ),
+            TreeFileError::Empty => write!(f, "tree file contains no leaf"),
+            TreeFileError::UnsupportedLeafVersion { leaf, leaf_version } => write!(
+                f,
+                "leaf {} uses unsupported leaf version {}",
+                leaf, leaf_version
+            ),
+            TreeFileError::UnexpectedLeafOptions { leaf } => write!(
+                f,
+                "leaf {} has leaf options, which leaf version {} cannot carry",
+                leaf, LEAF_VERSION
+            ),
+            TreeFileError::InvalidLeafOptions { leaf } => {
+                write!(f, "leaf {} has malformed leaf options", leaf)
+            }
+            TreeFileError::MixedHashes => write!(
+                f,
+                "leaf version {} cannot be mixed with other leaf versions",
+                SHA256_LEAF_VERSION
+            ),
+            TreeFileError::LeafHashMismatch {
+                leaf,
+                recorded,
+                actual,
+            } => write!(
+                f,
+                "leaf {} is recorded as {:#x}, but its preimage hashes to {:#x}",
+                leaf, recorded, actual
+            ),
+            TreeFileError::RootMismatch { recorded, actual } => write!(
+                f,
+                "root is recorded as {:#x}, but leaves build root {:#x}",
+                recorded, actual
+            ),
+            TreeFileError::ManifestHashMismatch { recorded, actual } => write!(
+                f,
+                "manifest hash is recorded as {:#x}, but leaves hash to {:#x}",
+                recorded, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TreeFileError {}
+
+/// An upgrade tree together with the preimage of its leaves, so proofs and
+/// witnesses can be produced for any leaf without the original manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeFile {
+    leaves: Vec<UpgradeLeaf>,
+    tree: UpgradeTree,
+    manifest_hash: Byte32,
+}
+
+impl TreeFile {
+    /// Builds the tree file without validating the manifest, see
+    /// [`TreeFile::validated`] for a root to publish.
+    pub fn from_manifest(manifest: &Manifest) -> Self {
+        Self {
+            leaves: manifest.leaves(),
+            tree: manifest.tree(),
+            manifest_hash: manifest.content_hash(),
+        }
+    }
+
+    /// Builds the tree file only when `validator` finds no error in the
+    /// manifest, use this for trees whose root is to be published. Warnings
+    /// are returned together with the tree file.
+    pub fn validated(
+        manifest: &Manifest,
+        validator: &TreeValidator,
+    ) -> Result<(Self, Vec<Diagnostic>), Vec<Diagnostic>> {
+        let (tree, diagnostics) = validator.build_tree(manifest)?;
+        let tree_file = Self {
+            leaves: manifest.leaves(),
+            tree,
+            manifest_hash: manifest.content_hash(),
+        };
+        Ok((tree_file, diagnostics))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, TreeFileError> {
+        let content = std::fs::read_to_string(path).map_err(|e| TreeFileError::Read {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })?;
+        Self::from_json_str(&content)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), TreeFileError> {
+        std::fs::write(path, self.to_json()).map_err(|e| TreeFileError::Write {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })
+    }
+
+    /// Parses a tree file, recomputing every leaf hash, the root and the
+    /// manifest hash from leaf preimages.
+    pub fn from_json_str(content: &str) -> Result<Self, TreeFileError> {
+        let raw: RawTreeFile =
+            serde_json::from_str(content).map_err(|e| TreeFileError::Parse(e.to_string()))?;
+        if raw.version != TREE_FILE_VERSION {
+            return Err(TreeFileError::UnsupportedVersion(raw.version));
+        }
+        let mut leaves = Vec::with_capacity(raw.leaves.len());
+        for (index, raw_leaf) in raw.leaves.into_iter().enumerate() {
+            let recorded: Byte32 = raw_leaf.hash.pack();
+            let leaf = UpgradeLeaf {
+                leaf_version: raw_leaf.leaf_version,
+                leaf_options: raw_leaf
+                    .leaf_options
+                    .map(|o| o.into_bytes())
+                    .unwrap_or_default(),
+                input_out_point: raw_leaf.input_out_point.into(),
+                output: raw_leaf.output.into(),
+                output_data_hash: raw_leaf.output_data_hash.pack(),
+                input_type: raw_leaf.input_type.map(|b| b.into_bytes()),
+                output_type: raw_leaf.output_type.map(|b| b.into_bytes()),
+            };
+            if !matches!(
+                leaf.leaf_version,
+                LEAF_VERSION | EXTENDED_LEAF_VERSION | SHA256_LEAF_VERSION
+            ) {
+                return Err(TreeFileError::UnsupportedLeafVersion {
+                    leaf: index,
+                    leaf_version: leaf.leaf_version,
+                });
+            }
+            if leaf.leaf_version == LEAF_VERSION {
+                if !leaf.leaf_options.is_empty() {
+                    return Err(TreeFileError::UnexpectedLeafOptions { leaf: index });
+                }
+            } else if LeafOptions::parse(&leaf.leaf_options).is_none() {
+                return Err(TreeFileError::InvalidLeafOptions { leaf: index });
+            }
+            let actual = leaf.hash();
+            if actual != recorded {
+                return Err(TreeFileError::LeafHashMismatch {
+                    leaf: index,
+                    recorded,
+                    actual,
+                });
+            }
+            leaves.push(leaf);
+        }
+        if leaves.is_empty() {
+            return Err(TreeFileError::Empty);
+        }
+        let tree = UpgradeTree::build(&leaves).ok_or(TreeFileError::MixedHashes)?;
+        let recorded: Byte32 = raw.root.pack();
+        if tree.root() != recorded {
+            return Err(TreeFileError::RootMismatch {
+                recorded,
+                actual: tree.root(),
+            });
+        }
+        let recorded: Byte32 = raw.manifest_hash.pack();
+        let actual = manifest_hash(tree.leaves());
+        if actual != recorded {
+            return Err(TreeFileError::ManifestHashMismatch { recorded, actual });
+        }
+        Ok(Self {
+            leaves,
+            tree,
+            manifest_hash: recorded,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let optional_bytes = |b: &Option<Bytes>| b.clone().map(json_types::JsonBytes::from_bytes);
+        let raw = RawTreeFile {
+            version: TREE_FILE_VERSION,
+            manifest_hash: self.manifest_hash.unpack(),
+            root: self.tree.root().unpack(),
+            leaves: self
+                .leaves
+                .iter()
+                .map(|leaf| RawLeaf {
+                    hash: leaf.hash().unpack(),
+                    leaf_version: leaf.leaf_version,
+                    leaf_options: Some(leaf.leaf_options.clone())
+                        .filter(|o| !o.is_empty())
+                        .map(json_types::JsonBytes::from_bytes),
+                    input_out_point: leaf.input_out_point.clone().into(),
+                    output: leaf.output.clone().into(),
+                    output_data_hash: leaf.output_data_hash.unpack(),
+                    input_type: optional_bytes(&leaf.input_type),
+                    output_type: optional_bytes(&leaf.output_type),
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&raw).expect("json")
+    }
+
+    pub fn leaves(&self) -> &[UpgradeLeaf] {
+        &self.leaves
+    }
+
+    pub fn tree(&self) -> &UpgradeTree {
+        &self.tree
+    }
+
+    pub fn root(&self) -> Byte32 {
+        self.tree.root()
+    }
+
+    pub fn manifest_hash(&self) -> Byte32 {
+        self.manifest_hash.clone()
+    }
+
+    /// Position of the leaf upgrading `out_point` in the tree.
+    pub fn position(&self, out_point: &OutPoint) -> Option<u32> {
+        self.leaves
+            .iter()
+            .position(|leaf| &leaf.input_out_point == out_point)
+            .map(|index| index as u32)
+    }
+
+    /// Merkle proof of the leaf at `index`, None when `index` is out of range.
+    pub fn proof(&self, index: u32, header_indices: &[u32]) -> Option<Bytes> {
+        self.tree
+            .proof(index, header_indices, self.leaves.get(index as usize)?)
+    }
+
+    /// Witness of the leaf at `index`, None when `index` is out of range.
+    pub fn witness(&self, index: u32, header_indices: &[u32]) -> Option<WitnessArgs> {
+        let proof = self.proof(index, header_indices)?;
+        Some(self.leaves[index as usize].witness(proof))
+    }
+}
@@ -0,0 +1,393 @@
+//! Assembles upgrade transactions ready to be signed. The zero lock input
+//! comes first, followed by fee cells, which pay the transaction fee and
+//! receive the change. The upgrade witness is placed where the witness
+//! location in zero lock args designates, index 0 by default.
+//!
+//! The builder refuses transactions zero lock would reject for an obvious
+//! reason: new cell data not matching the leaf, or header deps not matching
+//! the merkle proof.
+
+use crate::{indexer::DecodedProof, leaf::UpgradeLeaf};
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, TransactionBuilder, TransactionView},
+    packed::{Byte32, CellDep, CellInput, CellOutput, OutPoint, Script, WitnessArgs},
+    prelude::*,
+};
+use ckb_zero_lock_core::{
+    args::{ScriptArgs, WitnessLocation},
+    leaf::{LeafOptions, DEP_GROUP_PLACEHOLDER_SIZE, OUT_POINT_SIZE},
+    proof_pointer::PROOF_POINTER_SIZE,
+    DISCOVER_HEADER_INDEX,
+};
+use std::{collections::HashSet, fmt};
+
+/// Default fee rate in shannons per 1000 bytes, the minimum accepted by the
+/// transaction pool with default settings.
+pub const DEFAULT_FEE_RATE: u64 = 1000;
+
+/// A cell paying the fee of the upgrade transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeCell {
+    pub out_point: OutPoint,
+    pub output: CellOutput,
+    /// Size of the witness lock expected by the lock of the cell, such as 65
+    /// bytes for a secp256k1 signature. A zero filled placeholder of this
+    /// size is included in the witness, so the fee covers the signed
+    /// transaction. `None` for locks not reading the witness.
+    pub witness_lock_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpgradeTxError {
+    /// Capacity of the zero lock input and all fee cells cannot cover the
+    /// new cell, the change cell and the fee.
+    InsufficientCapacity {
+        required: u64,
+        available: u64,
+    },
+    /// No fee cell is used and no change lock is set.
+    MissingChangeLock,
+    CapacityOverflow,
+    /// Lock args of the zero lock input are malformed.
+    InvalidArgs,
+    /// Leaf options of an extended leaf are malformed, or name dep group
+    /// entries missing from the new cell data.
+    InvalidLeafOptions,
+    /// Hash of the new cell data, with dep group placeholder entries zeroed,
+    /// differs from the one committed in the leaf.
+    DataHashMismatch {
+        expected: Byte32,
+        actual: Byte32,
+    },
+    /// The merkle proof cannot be read with the header quorum in zero lock
+    /// args.
+    InvalidProof {
+        header_quorum: u8,
+    },
+    /// Header deps do not match header indices in the merkle proof, or are
+    /// fewer than the header quorum.
+    HeaderDepCount {
+        expected: usize,
+        actual: usize,
+    },
+    /// Witness index in zero lock args belongs to a fee cell input.
+    WitnessIndexTaken(usize),
+}
+
+impl fmt::Display for UpgradeTxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpgradeTxError::InsufficientCapacity {
+                required,
+                available,
+            } => write!(
+                f,
+                "transaction requires {} shannons, but inputs only provide {}",
+                required, available
+            ),
+            UpgradeTxError::MissingChangeLock => {
+                write!(f, "change lock is required when no fee cell is used")
+            }
+            UpgradeTxError::CapacityOverflow => write!(f, "capacity overflows"),
+            UpgradeTxError::InvalidArgs => write!(f, "zero lock args are malformed"),
+            UpgradeTxError::InvalidLeafOptions => {
+                write!(f, "leaf options do not fit the new cell data")
+            }
+            UpgradeTxError::DataHashMismatch { expected, actual } => write!(
+                f,
+                "new cell data hashes to {}, but the leaf commits {}",
+                actual, expected
+            ),
+            UpgradeTxError::InvalidProof { header_quorum } => write!(
+                f,
+                "merkle proof does not carry {} header indices",
+                header_quorum
+            ),
+            UpgradeTxError::HeaderDepCount { expected, actual } => write!(
+                f,
+                "merkle proof requires {} header deps, but {} are given",
+                expected, actual
+            ),
+            UpgradeTxError::WitnessIndexTaken(index) => {
+                write!(f, "witness {} is taken by a fee cell input", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UpgradeTxError {}
+
+/// A balanced upgrade transaction, fee cell witnesses only contain
+/// placeholders to be replaced by signatures.
+#[derive(Debug, Clone)]
+pub struct UpgradeTx {
+    pub tx: TransactionView,
+    /// Fee paid by the transaction in shannons.
+    pub fee: u64,
+    /// Size of the transaction in a block, which the fee is computed from.
+    pub size: usize,
+    /// Number of fee cells used, taken in the order they were added.
+    pub fee_cells: usize,
+}
+
+/// Builds the transaction upgrading one zero lock cell, balancing capacity
+/// with as few fee cells as possible.
+#[derive(Debug, Clone)]
+pub struct UpgradeTxBuilder {
+    leaf: UpgradeLeaf,
+    input_capacity: u64,
+    input_lock: Script,
+    output_data: Bytes,
+    proof: Bytes,
+    header_deps: Vec<Byte32>,
+    cell_deps: Vec<CellDep>,
+    fee_cells: Vec<FeeCell>,
+    change_lock: Option<Script>,
+    fee_rate: u64,
+}
+
+impl UpgradeTxBuilder {
+    /// `input` is the zero lock cell being upgraded, its lock args decide the
+    /// header quorum and the witness location. `proof` is the witness lock
+    /// proving `leaf`, and `header_dep` the header carrying the root.
+    pub fn new(
+        leaf: UpgradeLeaf,
+        input: &CellOutput,
+        output_data: Bytes,
+        proof: Bytes,
+        header_dep: Byte32,
+    ) -> Self {
+        Self {
+            leaf,
+            input_capacity: input.capacity().unpack(),
+            input_lock: input.lock(),
+            output_data,
+            proof,
+            header_deps: vec![header_dep],
+            cell_deps: vec![],
+            fee_cells: vec![],
+            change_lock: None,
+            fee_rate: DEFAULT_FEE_RATE,
+        }
+    }
+
+    /// Appends a header dep, for proofs designating more than one header.
+    pub fn header_dep(mut self, header_dep: Byte32) -> Self {
+        self.header_deps.push(header_dep);
+        self
+    }
+
+    /// Appends a cell dep, such as zero lock, scripts of the new cell, or
+    /// locks of fee cells.
+    pub fn cell_dep(mut self, cell_dep: CellDep) -> Self {
+        self.cell_deps.push(cell_dep);
+        self
+    }
+
+    pub fn fee_cell(mut self, fee_cell: FeeCell) -> Self {
+        self.fee_cells.push(fee_cell);
+        self
+    }
+
+    /// Lock of the change cell, the lock of the first fee cell by default.
+    pub fn change_lock(mut self, lock: Script) -> Self {
+        self.change_lock = Some(lock);
+        self
+    }
+
+    /// Fee rate in shannons per 1000 bytes.
+    pub fn fee_rate(mut self, fee_rate: u64) -> Self {
+        self.fee_rate = fee_rate;
+        self
+    }
+
+    /// Balances the transaction, adding fee cells in order until they cover
+    /// the fee and a change cell.
+    pub fn build(&self) -> Result<UpgradeTx, UpgradeTxError> {
+        let witness_location = self.check()?;
+        let mut result = self.balance(0, witness_location);
+        for count in 1..=self.fee_cells.len() {
+            if result.is_ok() {
+                break;
+            }
+            result = self.balance(count, witness_location);
+        }
+        result
+    }
+
+    // Checks the parts zero lock verifies without help of fee cells, returns
+    // the witness location in zero lock args
+    fn check(&self) -> Result<WitnessLocation, UpgradeTxError> {
+        let args = ScriptArgs::parse(&self.input_lock.args().raw_data())
+            .ok_or(UpgradeTxError::InvalidArgs)?;
+
+        let actual = self.output_data_hash()?;
+        if actual != self.leaf.output_data_hash {
+            return Err(UpgradeTxError::DataHashMismatch {
+                expected: self.leaf.output_data_hash.clone(),
+                actual,
+            });
+        }
+
+        // Header indices in a proof pointer are only resolved on chain, as
+        // are discovered ones, both require at least header quorum header deps
+        let header_quorum = args.header_quorum as usize;
+        let header_indices = if self.proof.len() == PROOF_POINTER_SIZE {
+            vec![DISCOVER_HEADER_INDEX]
+        } else {
+            DecodedProof::decode(&self.proof, header_quorum)
+                .ok_or(UpgradeTxError::InvalidProof {
+                    header_quorum: args.header_quorum,
+                })?
+                .header_indices
+        };
+        let actual = self.header_deps.len();
+        let (expected, matched) = match header_indices.iter().max() {
+            Some(&DISCOVER_HEADER_INDEX) | None => (header_quorum, actual >= header_quorum),
+            Some(&max) => (max as usize + 1, actual == max as usize + 1),
+        };
+        if !matched {
+            return Err(UpgradeTxError::HeaderDepCount { expected, actual });
+        }
+        Ok(args.witness_location)
+    }
+
+    fn output_data_hash(&self) -> Result<Byte32, UpgradeTxError> {
+        let options = LeafOptions::parse(&self.leaf.leaf_options)
+            .ok_or(UpgradeTxError::InvalidLeafOptions)?;
+        let Some(dep_group) = options.dep_group else {
+            return Ok(blake2b_256(&self.output_data).pack());
+        };
+        if dep_group.len() % DEP_GROUP_PLACEHOLDER_SIZE != 0 {
+            return Err(UpgradeTxError::InvalidLeafOptions);
+        }
+        let mut data = self.output_data.to_vec();
+        for placeholder in dep_group.chunks_exact(DEP_GROUP_PLACEHOLDER_SIZE) {
+            let mut entry_index = [0u8; 4];
+            entry_index.copy_from_slice(&placeholder[..4]);
+            let start = (u32::from_le_bytes(entry_index) as usize)
+                .checked_mul(OUT_POINT_SIZE)
+                .and_then(|offset| offset.checked_add(4))
+                .ok_or(UpgradeTxError::InvalidLeafOptions)?;
+            data.get_mut(start..start + OUT_POINT_SIZE)
+                .ok_or(UpgradeTxError::InvalidLeafOptions)?
+                .fill(0);
+        }
+        Ok(blake2b_256(&data).pack())
+    }
+
+    fn balance(
+        &self,
+        count: usize,
+        witness_location: WitnessLocation,
+    ) -> Result<UpgradeTx, UpgradeTxError> {
+        let fee_cells = &self.fee_cells[..count];
+        // Zero lock input is followed by fee cell inputs
+        let witness_index = match witness_location {
+            WitnessLocation::GroupInput => 0,
+            WitnessLocation::Index(index) if index > 0 && index <= count => {
+                return Err(UpgradeTxError::WitnessIndexTaken(index))
+            }
+            WitnessLocation::Index(index) => index,
+            WitnessLocation::BeyondInputs => count + 1,
+        };
+        let available = fee_cells
+            .iter()
+            .try_fold(self.input_capacity, |sum, fee_cell| {
+                sum.checked_add(fee_cell.output.capacity().unpack())
+            })
+            .ok_or(UpgradeTxError::CapacityOverflow)?;
+        let change_lock = self
+            .change_lock
+            .clone()
+            .or_else(|| fee_cells.first().map(|fee_cell| fee_cell.output.lock()))
+            .ok_or(UpgradeTxError::MissingChangeLock)?;
+        let change = CellOutput::new_builder().lock(change_lock).build();
+        let change_occupied = change
+            .occupied_capacity(Capacity::zero())
+            .map_err(|_| UpgradeTxError::CapacityOverflow)?
+            .as_u64();
+
+        // Change capacity has a fixed size, so the transaction size is known
+        // before the change is
+        let size = self
+            .transaction(fee_cells, change.clone(), witness_index)
+            .data()
+            .serialized_size_in_block();
+        let fee = (size as u64)
+            .checked_mul(self.fee_rate)
+            .ok_or(UpgradeTxError::CapacityOverflow)?
+            .div_ceil(1000);
+        let output_capacity: u64 = self.leaf.output.capacity().unpack();
+        let required = [output_capacity, change_occupied, fee]
+            .into_iter()
+            .try_fold(0u64, |sum, capacity| sum.checked_add(capacity))
+            .ok_or(UpgradeTxError::CapacityOverflow)?;
+        if available < required {
+            return Err(UpgradeTxError::InsufficientCapacity {
+                required,
+                available,
+            });
+        }
+
+        let change = change
+            .as_builder()
+            .capacity((available - output_capacity - fee).pack())
+            .build();
+        Ok(UpgradeTx {
+            tx: self.transaction(fee_cells, change, witness_index),
+            fee,
+            size,
+            fee_cells: count,
+        })
+    }
+
+    fn transaction(
+        &self,
+        fee_cells: &[FeeCell],
+        change: CellOutput,
+        witness_index: usize,
+    ) -> TransactionView {
+        // Witness of zero lock input stays empty when the proof is elsewhere
+        let mut witnesses = vec![Bytes::new()];
+        // Only the first input of each lock group carries the signature
+        let mut signed_locks = HashSet::new();
+        for (i, fee_cell) in fee_cells.iter().enumerate() {
+            let Some(witness_lock_size) = fee_cell.witness_lock_size else {
+                continue;
+            };
+            if !signed_locks.insert(fee_cell.output.lock().calc_script_hash()) {
+                continue;
+            }
+            witnesses.resize(i + 1, Bytes::new());
+            let placeholder = WitnessArgs::new_builder()
+                .lock(Some(Bytes::from(vec![0u8; witness_lock_size])).pack())
+                .build();
+            witnesses.push(placeholder.as_bytes());
+        }
+        // Fee cell witnesses come before the upgrade witness, unless it is
+        // the witness of zero lock input
+        if witnesses.len() <= witness_index {
+            witnesses.resize(witness_index + 1, Bytes::new());
+        }
+        witnesses[witness_index] = self.leaf.witness(self.proof.clone()).as_bytes();
+
+        TransactionBuilder::default()
+            .input(CellInput::new(self.leaf.input_out_point.clone(), 0))
+            .inputs(
+                fee_cells
+                    .iter()
+                    .map(|fee_cell| CellInput::new(fee_cell.out_point.clone(), 0)),
+            )
+            .output(self.leaf.output.clone())
+            .output_data(self.output_data.pack())
+            .output(change)
+            .output_data(Bytes::new().pack())
+            .cell_deps(self.cell_deps.clone())
+            .header_deps(self.header_deps.clone())
+            .witnesses(witnesses.into_iter().map(|witness| witness.pack()))
+            .build()
+    }
+}
@@ -0,0 +1,385 @@
+//! Sanity checks run on a manifest before its merkle root is published. A
+//! mistake found after the hardfork block is mined cannot be fixed, so each
+//! problem is reported as a diagnostic, instead of stopping at the first one.
+
+use crate::{
+    hash::LEAF_VERSION,
+    manifest::{Manifest, ManifestEntry},
+    tree::UpgradeTree,
+};
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, ScriptHashType},
+    packed::{Byte, Byte32, CellOutput, OutPoint, Script},
+    prelude::*,
+};
+use ckb_zero_lock_core::leaf::LeafOptions;
+use std::{collections::HashMap, fmt};
+
+/// Code hash of the Type ID system script.
+pub const TYPE_ID_CODE_HASH: [u8; 32] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, b'T', b'Y', b'P',
+    b'E', b'_', b'I', b'D',
+];
+
+/// Witnesses above this size are reported by default. Large proofs can be
+/// moved into proof cells instead.
+pub const DEFAULT_MAX_WITNESS_SIZE: usize = 32 * 1024;
+
+// Size of an empty WitnessArgs, plus the length prefix of each present field
+const WITNESS_ARGS_HEADER_SIZE: usize = 16;
+const BYTES_HEADER_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The upgrade can never pass zero lock, or the tree is ambiguous.
+    Error,
+    /// The upgrade might still work, but deserves a second look.
+    Warning,
+}
+
+/// A problem found in the manifest, `leaf` is the position of the entry in
+/// the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    DuplicateInput {
+        out_point: OutPoint,
+        leaves: Vec<usize>,
+    },
+    InsufficientCapacity {
+        leaf: usize,
+        capacity: u64,
+        occupied: u64,
+    },
+    /// Only the data hash is known, so occupied capacity is not checked.
+    CapacityUnchecked {
+        leaf: usize,
+    },
+    NotZeroLocked {
+        leaf: usize,
+    },
+    /// Zero lock locates the continuing output by its own lock hash, so the
+    /// new cell must keep the exact lock of the input cell, args included.
+    LockChanged {
+        leaf: usize,
+    },
+    ReleasedIntoZeroLock {
+        leaf: usize,
+    },
+    ReleaseLockMismatch {
+        leaf: usize,
+        expected: Byte32,
+        actual: Byte32,
+    },
+    TypeIdChanged {
+        leaf: usize,
+    },
+    TypeIdIntroduced {
+        leaf: usize,
+    },
+    DuplicateTypeId {
+        type_hash: Byte32,
+        leaves: Vec<usize>,
+    },
+    OversizedWitness {
+        leaf: usize,
+        estimated: usize,
+        limit: usize,
+    },
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Diagnostic::CapacityUnchecked { .. }
+            | Diagnostic::TypeIdIntroduced { .. }
+            | Diagnostic::OversizedWitness { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::DuplicateInput { out_point, leaves } => write!(
+                f,
+                "leaves {:?} all upgrade input {:#x}:{}",
+                leaves,
+                out_point.tx_hash(),
+                Unpack::<u32>::unpack(&out_point.index())
+            ),
+            Diagnostic::InsufficientCapacity {
+                leaf,
+                capacity,
+                occupied,
+            } => write!(
+                f,
+                "leaf {} has capacity {}, but occupies {} shannons",
+                leaf, capacity, occupied
+            ),
+            Diagnostic::CapacityUnchecked { leaf } => write!(
+                f,
+                "leaf {} only provides data hash, occupied capacity is not checked",
+                leaf
+            ),
+            Diagnostic::NotZeroLocked { leaf } => {
+                write!(f, "new cell of leaf {} is not locked by zero lock", leaf)
+            }
+            Diagnostic::LockChanged { leaf } => write!(
+                f,
+                "new cell of leaf {} does not keep the lock of the input cell",
+                leaf
+            ),
+            Diagnostic::ReleasedIntoZeroLock { leaf } => write!(
+                f,
+                "leaf {} releases the cell, but the new cell is locked by zero lock",
+                leaf
+            ),
+            Diagnostic::ReleaseLockMismatch {
+                leaf,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "leaf {} releases the cell to lock {:#x}, but the new cell uses lock {:#x}",
+                leaf, expected, actual
+            ),
+            Diagnostic::TypeIdChanged { leaf } => write!(
+                f,
+                "leaf {} does not keep the Type ID script of the input cell",
+                leaf
+            ),
+            Diagnostic::TypeIdIntroduced { leaf } => write!(
+                f,
+                "leaf {} adds a Type ID script absent from the input cell",
+                leaf
+            ),
+            Diagnostic::DuplicateTypeId { type_hash, leaves } => write!(
+                f,
+                "leaves {:?} all create cells with Type ID {:#x}",
+                leaves, type_hash
+            ),
+            Diagnostic::OversizedWitness {
+                leaf,
+                estimated,
+                limit,
+            } => write!(
+                f,
+                "witness of leaf {} is estimated at {} bytes, above {}, consider proof cells",
+                leaf, estimated, limit
+            ),
+        }
+    }
+}
+
+fn is_type_id(script: &Script) -> bool {
+    script.code_hash().as_slice() == TYPE_ID_CODE_HASH
+        && script.hash_type() == Byte::new(ScriptHashType::Type as u8)
+}
+
+fn type_id(output: &CellOutput) -> Option<Script> {
+    output.type_().to_opt().filter(is_type_id)
+}
+
+// Lemmas in a CBMT proof for a single leaf never exceed the tree height
+fn max_lemmas(leaves: usize) -> usize {
+    leaves.next_power_of_two().trailing_zeros() as usize
+}
+
+/// Runs sanity checks on manifest entries. Input cells are optional, when
+/// provided, locks and Type ID scripts are compared against them.
+pub struct TreeValidator {
+    zero_lock_code_hash: Byte32,
+    zero_lock_hash_type: ScriptHashType,
+    input_cells: HashMap<OutPoint, CellOutput>,
+    header_quorum: usize,
+    max_witness_size: usize,
+}
+
+impl TreeValidator {
+    pub fn new(zero_lock_code_hash: Byte32, zero_lock_hash_type: ScriptHashType) -> Self {
+        Self {
+            zero_lock_code_hash,
+            zero_lock_hash_type,
+            input_cells: HashMap::new(),
+            header_quorum: 1,
+            max_witness_size: DEFAULT_MAX_WITNESS_SIZE,
+        }
+    }
+
+    pub fn input_cell(mut self, out_point: OutPoint, output: CellOutput) -> Self {
+        self.input_cells.insert(out_point, output);
+        self
+    }
+
+    /// Number of header indices in each proof, used in witness estimates.
+    pub fn header_quorum(mut self, header_quorum: usize) -> Self {
+        self.header_quorum = header_quorum;
+        self
+    }
+
+    pub fn max_witness_size(mut self, max_witness_size: usize) -> Self {
+        self.max_witness_size = max_witness_size;
+        self
+    }
+
+    fn is_zero_lock(&self, lock: &Script) -> bool {
+        lock.code_hash() == self.zero_lock_code_hash
+            && lock.hash_type() == Byte::new(self.zero_lock_hash_type as u8)
+    }
+
+    /// Upper bound of the witness size for an entry in a tree of `leaves`.
+    pub fn estimate_witness_size(&self, entry: &ManifestEntry, leaves: usize) -> usize {
+        let lemmas = max_lemmas(leaves);
+        // Header indices, then a single leaf index and the lemmas
+        let proof_size = 4 * self.header_quorum + 4 + 4 + 4 + 32 * lemmas;
+        let trailer_size = match entry.leaf.leaf_version {
+            LEAF_VERSION => 0,
+            _ => 1 + 4 + entry.leaf.leaf_options.len(),
+        };
+        let field_size =
+            |field: &Option<Bytes>| field.as_ref().map_or(0, |f| BYTES_HEADER_SIZE + f.len());
+        WITNESS_ARGS_HEADER_SIZE
+            + BYTES_HEADER_SIZE
+            + proof_size
+            + trailer_size
+            + field_size(&entry.leaf.input_type)
+            + field_size(&entry.leaf.output_type)
+    }
+
+    pub fn validate(&self, manifest: &Manifest) -> Vec<Diagnostic> {
+        let entries = manifest.entries();
+        let mut diagnostics = vec![];
+        let mut inputs: HashMap<OutPoint, Vec<usize>> = HashMap::new();
+        let mut type_ids: HashMap<Byte32, Vec<usize>> = HashMap::new();
+        for (leaf, entry) in entries.iter().enumerate() {
+            let output = &entry.leaf.output;
+            inputs
+                .entry(entry.leaf.input_out_point.clone())
+                .or_default()
+                .push(leaf);
+            if let Some(type_id) = type_id(output) {
+                type_ids
+                    .entry(type_id.calc_script_hash())
+                    .or_default()
+                    .push(leaf);
+            }
+            self.check_capacity(leaf, entry, &mut diagnostics);
+            self.check_lock(leaf, entry, &mut diagnostics);
+            if let Some(input_cell) = self.input_cells.get(&entry.leaf.input_out_point) {
+                match (type_id(input_cell), type_id(output)) {
+                    (Some(input_type_id), output_type_id) => {
+                        if output_type_id.as_ref() != Some(&input_type_id) {
+                            diagnostics.push(Diagnostic::TypeIdChanged { leaf });
+                        }
+                    }
+                    (None, Some(_)) => diagnostics.push(Diagnostic::TypeIdIntroduced { leaf }),
+                    (None, None) => (),
+                }
+            }
+            let estimated = self.estimate_witness_size(entry, entries.len());
+            if estimated > self.max_witness_size {
+                diagnostics.push(Diagnostic::OversizedWitness {
+                    leaf,
+                    estimated,
+                    limit: self.max_witness_size,
+                });
+            }
+        }
+        // Entries are sorted, so leaf indices in each group are ascending
+        let mut duplicates: Vec<Diagnostic> = inputs
+            .into_iter()
+            .filter(|(_, leaves)| leaves.len() > 1)
+            .map(|(out_point, leaves)| Diagnostic::DuplicateInput { out_point, leaves })
+            .chain(
+                type_ids
+                    .into_iter()
+                    .filter(|(_, leaves)| leaves.len() > 1)
+                    .map(|(type_hash, leaves)| Diagnostic::DuplicateTypeId { type_hash, leaves }),
+            )
+            .collect();
+        duplicates.sort_by_key(|diagnostic| match diagnostic {
+            Diagnostic::DuplicateInput { leaves, .. }
+            | Diagnostic::DuplicateTypeId { leaves, .. } => leaves[0],
+            _ => unreachable!(),
+        });
+        duplicates.extend(diagnostics);
+        duplicates
+    }
+
+    fn check_capacity(
+        &self,
+        leaf: usize,
+        entry: &ManifestEntry,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let Some(data) = &entry.data else {
+            diagnostics.push(Diagnostic::CapacityUnchecked { leaf });
+            return;
+        };
+        let output = &entry.leaf.output;
+        let capacity: u64 = output.capacity().unpack();
+        // Overflow means the cell can never hold its data
+        let occupied = Capacity::bytes(data.len())
+            .and_then(|data_capacity| output.occupied_capacity(data_capacity))
+            .map(|c| c.as_u64())
+            .unwrap_or(u64::MAX);
+        if occupied > capacity {
+            diagnostics.push(Diagnostic::InsufficientCapacity {
+                leaf,
+                capacity,
+                occupied,
+            });
+        }
+    }
+
+    fn check_lock(&self, leaf: usize, entry: &ManifestEntry, diagnostics: &mut Vec<Diagnostic>) {
+        let lock = entry.leaf.output.lock();
+        let release = match entry.leaf.leaf_version {
+            LEAF_VERSION => None,
+            // Manifest has validated leaf options already
+            _ => LeafOptions::parse(&entry.leaf.leaf_options).and_then(|o| o.release.copied()),
+        };
+        match release {
+            Some(release_lock_hash) => {
+                if self.is_zero_lock(&lock) {
+                    diagnostics.push(Diagnostic::ReleasedIntoZeroLock { leaf });
+                } else if lock.calc_script_hash().as_slice() != release_lock_hash {
+                    diagnostics.push(Diagnostic::ReleaseLockMismatch {
+                        leaf,
+                        expected: Byte32::new(release_lock_hash),
+                        actual: lock.calc_script_hash(),
+                    });
+                }
+            }
+            None => match self.input_cells.get(&entry.leaf.input_out_point) {
+                Some(input_cell) if input_cell.lock() != lock => {
+                    diagnostics.push(Diagnostic::LockChanged { leaf })
+                }
+                Some(_) => (),
+                None if !self.is_zero_lock(&lock) => {
+                    diagnostics.push(Diagnostic::NotZeroLocked { leaf })
+                }
+                None => (),
+            },
+        }
+    }
+
+    /// Validates the manifest and builds its tree. Any diagnostic with
+    /// Error severity fails the build, warnings are returned together with
+    /// the tree.
+    pub fn build_tree(
+        &self,
+        manifest: &Manifest,
+    ) -> Result<(UpgradeTree, Vec<Diagnostic>), Vec<Diagnostic>> {
+        let diagnostics = self.validate(manifest);
+        if diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity() == Severity::Error)
+        {
+            return Err(diagnostics);
+        }
+        Ok((manifest.tree(), diagnostics))
+    }
+}
@@ -0,0 +1,728 @@
+//! Offline verification of upgrade transactions. The verification flow of
+//! zero lock in `ckb_zero_lock_core::verifier` is run against a mock
+//! transaction, so that a failing upgrade can be explained without running
+//! ckb-debugger.
+
+use ckb_hash::blake2b_256;
+use ckb_mock_tx_types::MockTransaction;
+use ckb_types::{
+    bytes::Bytes,
+    core::DepType,
+    packed::{Byte32, CellDep, CellOutput, OutPoint, OutPointVec, Script, WitnessArgs},
+    prelude::*,
+};
+use ckb_zero_lock_core::{
+    leaf::{CELL_DEP_DATA_HASH, CELL_DEP_SIZE, OUT_POINT_SIZE},
+    proof_pointer::{self, PROOF_POINTER_SIZE},
+    verifier::{self, DataSource, Error, SourceError, WitnessSource},
+    witness_args::{FieldEvent, WitnessArgsVisitor},
+    Data, ERROR_CODE_WITNESS_READER, MERKLE_ROOT_OFFSET,
+};
+use std::fmt;
+
+const REMAINDER_FIELD_NAMES: [&str; 2] = ["input_type", "output_type"];
+
+/// Reason an upgrade transaction fails verification. Each variant names
+/// the check in zero lock that fails, see `code` for the error code printed
+/// by zero lock in debug output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The mock transaction itself is incomplete, zero lock is not reached.
+    MalformedTransaction(String),
+    InvalidArgs(Bytes),
+    MultipleInputs {
+        first: usize,
+        second: usize,
+    },
+    MultipleOutputs {
+        lock_hash: Byte32,
+        first: usize,
+        second: usize,
+    },
+    WitnessOfOtherInput(usize),
+    /// The merkle proof is in another witness, but the witness of the zero
+    /// lock input is not empty.
+    GroupWitnessNotEmpty(usize),
+    MissingWitness(usize),
+    InvalidWitness {
+        index: usize,
+        reason: String,
+    },
+    UnsupportedLeafVersion(u8),
+    InvalidLeafOptions,
+    MissingOutput,
+    ReleasedCellInZeroLock {
+        output_index: usize,
+    },
+    InvalidDepGroupData {
+        output_index: usize,
+    },
+    InvalidPlaceholder(String),
+    EntryMismatch {
+        entry_index: usize,
+        cell_dep_index: usize,
+    },
+    CellDepHashMismatch {
+        cell_dep_index: usize,
+        expected: Byte32,
+    },
+    InvalidRequiredCellDeps,
+    RequiredCellDepMissing {
+        kind: u8,
+        hash: Byte32,
+    },
+    InvalidMetadata(&'static str),
+    IncompleteProof,
+    NoRoot,
+    DiscoveryMixed,
+    DuplicateHeaderIndex(u32),
+    ShortExtension(u32),
+    MissingExtension(u32),
+    RootsDiffer {
+        header_index: u32,
+        root: Byte32,
+        expected: Byte32,
+    },
+    RootMismatch {
+        header_index: u32,
+        header_root: Byte32,
+        proof_root: Byte32,
+    },
+    DiscoveryFailed {
+        matched: usize,
+        required: usize,
+        proof_root: Byte32,
+    },
+}
+
+impl VerifyError {
+    /// Error code of the failing check in zero lock. Note zero lock always
+    /// exits with -61, the code is only visible in debug output.
+    pub fn code(&self) -> Option<i8> {
+        let code = match self {
+            VerifyError::MalformedTransaction(_) => return None,
+            VerifyError::MultipleInputs { .. } => 1,
+            VerifyError::MultipleOutputs { .. } => 2,
+            VerifyError::MissingOutput => 4,
+            VerifyError::ShortExtension(_) => 5,
+            VerifyError::MissingExtension(_) => 6,
+            VerifyError::RootMismatch { .. } | VerifyError::DiscoveryFailed { .. } => 8,
+            VerifyError::MissingWitness(_) | VerifyError::InvalidWitness { .. } => 9,
+            VerifyError::IncompleteProof => 10,
+            VerifyError::NoRoot => 11,
+            VerifyError::UnsupportedLeafVersion(_) | VerifyError::InvalidLeafOptions => 12,
+            VerifyError::InvalidDepGroupData { .. } => 13,
+            VerifyError::InvalidPlaceholder(_) => 14,
+            VerifyError::EntryMismatch { .. } => 15,
+            VerifyError::CellDepHashMismatch { .. } => 16,
+            VerifyError::InvalidRequiredCellDeps => 17,
+            VerifyError::RequiredCellDepMissing { .. } => 18,
+            VerifyError::InvalidArgs(_) => 19,
+            VerifyError::DuplicateHeaderIndex(_) => 20,
+            VerifyError::RootsDiffer { .. } => 21,
+            VerifyError::DiscoveryMixed => 22,
+            VerifyError::ReleasedCellInZeroLock { .. } => 23,
+            VerifyError::InvalidMetadata(_) => 24,
+            VerifyError::WitnessOfOtherInput(_) => 25,
+            VerifyError::GroupWitnessNotEmpty(_) => 26,
+        };
+        Some(code)
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::MalformedTransaction(reason) => {
+                write!(f, "malformed mock transaction: {}", reason)
+            }
+            VerifyError::InvalidArgs(args) => write!(f, "invalid script args: {:x}", args),
+            VerifyError::MultipleInputs { first, second } => write!(
+                f,
+                "inputs {} and {} both use the same zero lock",
+                first, second
+            ),
+            VerifyError::MultipleOutputs {
+                lock_hash,
+                first,
+                second,
+            } => write!(
+                f,
+                "outputs {} and {} both use lock {:#x}",
+                first, second, lock_hash
+            ),
+            VerifyError::WitnessOfOtherInput(index) => write!(
+                f,
+                "witness {} named in script args belongs to another input",
+                index
+            ),
+            VerifyError::GroupWitnessNotEmpty(index) => write!(
+                f,
+                "witness {} of the zero lock input must be empty, the proof is in another witness",
+                index
+            ),
+            VerifyError::MissingWitness(index) => write!(f, "witness {} is missing", index),
+            VerifyError::InvalidWitness { index, reason } => {
+                write!(f, "witness {} is invalid: {}", index, reason)
+            }
+            VerifyError::UnsupportedLeafVersion(version) => {
+                write!(f, "leaf version {} is not supported", version)
+            }
+            VerifyError::InvalidLeafOptions => {
+                write!(f, "leaf options in leaf trailer are invalid")
+            }
+            VerifyError::MissingOutput => {
+                write!(f, "no output cell continues the upgraded cell")
+            }
+            VerifyError::ReleasedCellInZeroLock { output_index } => write!(
+                f,
+                "leaf releases the cell, but output {} is still in zero lock",
+                output_index
+            ),
+            VerifyError::InvalidDepGroupData { output_index } => {
+                write!(f, "output {} data is not a valid dep group", output_index)
+            }
+            VerifyError::InvalidPlaceholder(reason) => {
+                write!(f, "invalid dep group placeholder: {}", reason)
+            }
+            VerifyError::EntryMismatch {
+                entry_index,
+                cell_dep_index,
+            } => write!(
+                f,
+                "dep group entry {} does not match code cell dep {}",
+                entry_index, cell_dep_index
+            ),
+            VerifyError::CellDepHashMismatch {
+                cell_dep_index,
+                expected,
+            } => write!(
+                f,
+                "cell dep {} does not have the expected hash {:#x}",
+                cell_dep_index, expected
+            ),
+            VerifyError::InvalidRequiredCellDeps => {
+                write!(f, "required cell deps option has invalid length")
+            }
+            VerifyError::RequiredCellDepMissing { kind, hash } => {
+                let kind = match *kind {
+                    CELL_DEP_DATA_HASH => "data hash",
+                    _ => "type hash",
+                };
+                write!(f, "no cell dep with {} {:#x} is found", kind, hash)
+            }
+            VerifyError::InvalidMetadata(field) => {
+                write!(f, "witness {} is not valid upgrade metadata", field)
+            }
+            VerifyError::IncompleteProof => write!(f, "merkle proof is incomplete"),
+            VerifyError::NoRoot => write!(f, "merkle proof does not yield a root"),
+            VerifyError::DiscoveryMixed => write!(
+                f,
+                "header discovery cannot be mixed with designated header deps"
+            ),
+            VerifyError::DuplicateHeaderIndex(index) => {
+                write!(f, "header dep {} is designated more than once", index)
+            }
+            VerifyError::ShortExtension(index) => write!(
+                f,
+                "extension of header dep {} is too short to hold a merkle root at offset {}",
+                index, MERKLE_ROOT_OFFSET
+            ),
+            VerifyError::MissingExtension(index) => {
+                write!(f, "header dep {} or its extension is missing", index)
+            }
+            VerifyError::RootsDiffer {
+                header_index,
+                root,
+                expected,
+            } => write!(
+                f,
+                "root in header dep {} is {:#x}, other designated headers have {:#x}",
+                header_index, root, expected
+            ),
+            VerifyError::RootMismatch {
+                header_index,
+                header_root,
+                proof_root,
+            } => write!(
+                f,
+                "root in header dep {} at offset {} is {:#x}, proof root is {:#x}",
+                header_index, MERKLE_ROOT_OFFSET, header_root, proof_root
+            ),
+            VerifyError::DiscoveryFailed {
+                matched,
+                required,
+                proof_root,
+            } => write!(
+                f,
+                "only {} header deps carry proof root {:#x}, required: {}",
+                matched, proof_root, required
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Details of an upgrade that passes verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedUpgrade {
+    pub witness_index: usize,
+    pub output_index: usize,
+    pub leaf_version: u8,
+    pub leaf: Byte32,
+    pub root: Byte32,
+    /// Header deps carrying the merkle root.
+    pub header_indices: Vec<u32>,
+}
+
+/// Verifies the zero lock input at `input_index` of a mock transaction, the
+/// same way zero lock does on chain.
+pub fn verify_mock_transaction(
+    mock_tx: &MockTransaction,
+    input_index: usize,
+) -> Result<VerifiedUpgrade, VerifyError> {
+    verify_with_proof(mock_tx, input_index).map(|(verified, _)| verified)
+}
+
+/// Like verify_mock_transaction, also returning the merkle proof, after
+/// following a proof pointer if there is one.
+pub(crate) fn verify_with_proof(
+    mock_tx: &MockTransaction,
+    input_index: usize,
+) -> Result<(VerifiedUpgrade, Bytes), VerifyError> {
+    let source = MockSource::new(mock_tx, input_index)?;
+    let verified = verifier::verify(&source).map_err(|e| source.explain(e))?;
+    let witness_index = source.witness_index(verified.witness);
+    let upgrade = VerifiedUpgrade {
+        witness_index,
+        output_index: verified.output_index,
+        leaf_version: verified.leaf_version,
+        leaf: byte32(&verified.leaf),
+        root: byte32(&verified.root),
+        header_indices: verified.header_indices,
+    };
+    Ok((upgrade, source.proof(witness_index)))
+}
+
+fn byte32(data: &Data) -> Byte32 {
+    Byte32::from_slice(data.as_bytes()).expect("byte32")
+}
+
+fn raw_hash(hash: &Byte32) -> [u8; 32] {
+    hash.as_slice().try_into().expect("hash")
+}
+
+fn malformed(reason: String) -> VerifyError {
+    VerifyError::MalformedTransaction(reason)
+}
+
+// Partial loading, as performed by syscalls
+fn load_partial(buf: &mut [u8], offset: usize, data: &[u8]) -> usize {
+    let data = data.get(offset..).unwrap_or_default();
+    let len = buf.len().min(data.len());
+    buf[..len].copy_from_slice(&data[..len]);
+    len
+}
+
+fn dispatch<V: WitnessArgsVisitor>(visitor: &mut V, field: usize, event: FieldEvent) -> i32 {
+    match field {
+        0 => visitor.on_lock(event),
+        1 => visitor.on_input_type(event),
+        _ => visitor.on_output_type(event),
+    }
+}
+
+/// Transaction data of a mock transaction, as seen by zero lock running for
+/// the input at `input_index`.
+struct MockSource<'a> {
+    mock_tx: &'a MockTransaction,
+    lock: Script,
+    // Inputs using the same lock, which form the script group
+    group_inputs: Vec<usize>,
+    inputs: Vec<(OutPoint, CellOutput)>,
+    outputs: Vec<(CellOutput, Bytes)>,
+    raw_cell_deps: Vec<CellDep>,
+    cell_deps: Vec<(CellOutput, Bytes)>,
+    witnesses: Vec<Bytes>,
+}
+
+impl<'a> MockSource<'a> {
+    fn new(mock_tx: &'a MockTransaction, input_index: usize) -> Result<Self, VerifyError> {
+        let raw = mock_tx.tx.raw();
+        let mut inputs = Vec::with_capacity(raw.inputs().len());
+        for input in raw.inputs().into_iter() {
+            let out_point = input.previous_output();
+            let mock_input = mock_tx
+                .mock_info
+                .inputs
+                .iter()
+                .find(|mock_input| mock_input.input.previous_output() == out_point)
+                .ok_or_else(|| malformed(format!("input cell {} is not provided", out_point)))?;
+            inputs.push((out_point, mock_input.output.clone()));
+        }
+        let lock = inputs
+            .get(input_index)
+            .map(|(_, input)| input.lock())
+            .ok_or_else(|| malformed(format!("input {} does not exist", input_index)))?;
+        let lock_hash = lock.calc_script_hash();
+        let group_inputs = inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, input))| input.lock().calc_script_hash() == lock_hash)
+            .map(|(i, _)| i)
+            .collect();
+        let outputs = raw
+            .outputs()
+            .into_iter()
+            .zip(raw.outputs_data().into_iter().map(|data| data.raw_data()))
+            .collect();
+        let mut cell_deps = Vec::with_capacity(raw.cell_deps().len());
+        for cell_dep in raw.cell_deps().into_iter() {
+            let (output, data) = Self::resolve_cell(mock_tx, &cell_dep.out_point())?;
+            if cell_dep.dep_type() == DepType::DepGroup.into() {
+                let out_points = OutPointVec::from_slice(&data).map_err(|_| {
+                    malformed(format!("dep group {} is invalid", cell_dep.out_point()))
+                })?;
+                for out_point in out_points.into_iter() {
+                    cell_deps.push(Self::resolve_cell(mock_tx, &out_point)?);
+                }
+            } else {
+                cell_deps.push((output, data));
+            }
+        }
+        Ok(Self {
+            mock_tx,
+            lock,
+            group_inputs,
+            inputs,
+            outputs,
+            raw_cell_deps: raw.cell_deps().into_iter().collect(),
+            cell_deps,
+            witnesses: mock_tx
+                .tx
+                .witnesses()
+                .into_iter()
+                .map(|witness| witness.raw_data())
+                .collect(),
+        })
+    }
+
+    fn resolve_cell(
+        mock_tx: &MockTransaction,
+        out_point: &OutPoint,
+    ) -> Result<(CellOutput, Bytes), VerifyError> {
+        mock_tx
+            .mock_info
+            .cell_deps
+            .iter()
+            .find(|mock_cell_dep| mock_cell_dep.cell_dep.out_point() == *out_point)
+            .map(|mock_cell_dep| (mock_cell_dep.output.clone(), mock_cell_dep.data.clone()))
+            .ok_or_else(|| malformed(format!("cell dep {} is not provided", out_point)))
+    }
+
+    /// Absolute index of a witness.
+    fn witness_index(&self, witness: WitnessSource) -> usize {
+        match witness {
+            WitnessSource::GroupInput(index) => self.group_inputs[index],
+            WitnessSource::Input(index) => index,
+        }
+    }
+
+    fn witness(&self, witness: WitnessSource) -> Option<&Bytes> {
+        match witness {
+            WitnessSource::GroupInput(index) => self
+                .group_inputs
+                .get(index)
+                .and_then(|index| self.witnesses.get(*index)),
+            WitnessSource::Input(index) => self.witnesses.get(index),
+        }
+    }
+
+    /// Merkle proof in a verified witness, following the proof pointer if
+    /// there is one.
+    fn proof(&self, witness_index: usize) -> Bytes {
+        let lock = WitnessArgs::from_slice(&self.witnesses[witness_index])
+            .expect("verified witness")
+            .lock()
+            .to_opt()
+            .expect("verified witness lock")
+            .raw_data();
+        if lock.len() != PROOF_POINTER_SIZE {
+            return lock;
+        }
+        let pointer = proof_pointer::ProofPointer::parse(&lock).expect("proof pointer");
+        self.cell_deps[pointer.cell_dep_index]
+            .1
+            .slice(pointer.offset..pointer.offset + pointer.length)
+    }
+
+    /// Translates an error of zero lock into terms of the mock transaction.
+    fn explain(&self, error: Error) -> VerifyError {
+        match error {
+            Error::Source(e) => malformed(format!("transaction data cannot be loaded: {:?}", e)),
+            Error::MultipleInputs => VerifyError::MultipleInputs {
+                first: self.group_inputs[0],
+                second: self.group_inputs[1],
+            },
+            Error::MultipleOutputs {
+                lock_hash,
+                first,
+                second,
+            } => VerifyError::MultipleOutputs {
+                lock_hash: lock_hash.pack(),
+                first,
+                second,
+            },
+            Error::MissingOutput => VerifyError::MissingOutput,
+            Error::ShortExtension(index) => VerifyError::ShortExtension(index),
+            Error::MissingExtension(index) => VerifyError::MissingExtension(index),
+            Error::RootMismatch {
+                header_index,
+                header_root,
+                proof_root,
+            } => VerifyError::RootMismatch {
+                header_index,
+                header_root: byte32(&header_root),
+                proof_root: byte32(&proof_root),
+            },
+            Error::DiscoveryFailed {
+                matched,
+                required,
+                proof_root,
+            } => VerifyError::DiscoveryFailed {
+                matched,
+                required,
+                proof_root: byte32(&proof_root),
+            },
+            Error::MissingWitness(witness) => {
+                VerifyError::MissingWitness(self.witness_index(witness))
+            }
+            Error::InvalidWitness { witness, reason } => VerifyError::InvalidWitness {
+                index: self.witness_index(witness),
+                reason: reason.to_string(),
+            },
+            Error::IncompleteProof => VerifyError::IncompleteProof,
+            Error::NoRoot => VerifyError::NoRoot,
+            Error::UnsupportedLeafVersion(version) => VerifyError::UnsupportedLeafVersion(version),
+            Error::InvalidLeafOptions => VerifyError::InvalidLeafOptions,
+            Error::InvalidDepGroupData { output_index } => {
+                VerifyError::InvalidDepGroupData { output_index }
+            }
+            Error::InvalidPlaceholderLength(length) => {
+                VerifyError::InvalidPlaceholder(format!("option length {} is invalid", length))
+            }
+            Error::InvalidEntryIndex(entry_index) => {
+                VerifyError::InvalidPlaceholder(format!("entry index {} is invalid", entry_index))
+            }
+            Error::UnknownHashKind(kind) => {
+                VerifyError::InvalidPlaceholder(format!("unknown cell dep hash kind {}", kind))
+            }
+            Error::EntryMismatch {
+                entry_index,
+                cell_dep_index,
+            } => VerifyError::EntryMismatch {
+                entry_index,
+                cell_dep_index,
+            },
+            Error::CellDepHashMismatch {
+                cell_dep_index,
+                expected,
+            } => VerifyError::CellDepHashMismatch {
+                cell_dep_index,
+                expected: expected.pack(),
+            },
+            Error::InvalidRequiredCellDeps => VerifyError::InvalidRequiredCellDeps,
+            Error::RequiredCellDepMissing { kind, hash } => VerifyError::RequiredCellDepMissing {
+                kind,
+                hash: hash.pack(),
+            },
+            Error::InvalidArgs => VerifyError::InvalidArgs(self.lock.args().raw_data()),
+            Error::DuplicateHeaderIndex(index) => VerifyError::DuplicateHeaderIndex(index),
+            Error::RootsDiffer {
+                header_index,
+                root,
+                expected,
+            } => VerifyError::RootsDiffer {
+                header_index,
+                root: byte32(&root),
+                expected: byte32(&expected),
+            },
+            Error::DiscoveryMixed => VerifyError::DiscoveryMixed,
+            Error::ReleasedCellInZeroLock { output_index } => {
+                VerifyError::ReleasedCellInZeroLock { output_index }
+            }
+            Error::InvalidMetadata(field) => {
+                VerifyError::InvalidMetadata(REMAINDER_FIELD_NAMES[field])
+            }
+            Error::WitnessOfOtherInput(index) => VerifyError::WitnessOfOtherInput(index),
+            Error::GroupWitnessNotEmpty => VerifyError::GroupWitnessNotEmpty(self.group_inputs[0]),
+        }
+    }
+}
+
+impl DataSource for MockSource<'_> {
+    fn script_args(&self) -> Result<Vec<u8>, SourceError> {
+        Ok(self.lock.args().raw_data().to_vec())
+    }
+
+    fn script_hash(&self) -> Result<[u8; 32], SourceError> {
+        Ok(raw_hash(&self.lock.calc_script_hash()))
+    }
+
+    fn group_input_out_point(&self, index: usize) -> Result<[u8; OUT_POINT_SIZE], SourceError> {
+        let input_index = self
+            .group_inputs
+            .get(index)
+            .ok_or(SourceError::IndexOutOfBound)?;
+        Ok(self.inputs[*input_index]
+            .0
+            .as_slice()
+            .try_into()
+            .expect("out point"))
+    }
+
+    fn input_count(&self) -> Result<usize, SourceError> {
+        Ok(self.inputs.len())
+    }
+
+    fn input_lock_hash(&self, index: usize) -> Result<[u8; 32], SourceError> {
+        let (_, input) = self.inputs.get(index).ok_or(SourceError::IndexOutOfBound)?;
+        Ok(raw_hash(&input.lock().calc_script_hash()))
+    }
+
+    fn output_lock_hash(&self, index: usize) -> Result<[u8; 32], SourceError> {
+        let (output, _) = self
+            .outputs
+            .get(index)
+            .ok_or(SourceError::IndexOutOfBound)?;
+        Ok(raw_hash(&output.lock().calc_script_hash()))
+    }
+
+    fn output_data_hash(&self, index: usize) -> Result<[u8; 32], SourceError> {
+        let (_, data) = self
+            .outputs
+            .get(index)
+            .ok_or(SourceError::IndexOutOfBound)?;
+        Ok(blake2b_256(data))
+    }
+
+    fn output_data(&self, index: usize) -> Result<Vec<u8>, SourceError> {
+        let (_, data) = self
+            .outputs
+            .get(index)
+            .ok_or(SourceError::IndexOutOfBound)?;
+        Ok(data.to_vec())
+    }
+
+    fn output_cell(&self, index: usize, f: &mut dyn FnMut(&[u8])) -> Result<(), SourceError> {
+        let (output, _) = self
+            .outputs
+            .get(index)
+            .ok_or(SourceError::IndexOutOfBound)?;
+        f(output.as_slice());
+        Ok(())
+    }
+
+    fn cell_dep_data_hash(&self, index: usize) -> Result<[u8; 32], SourceError> {
+        let (_, data) = self
+            .cell_deps
+            .get(index)
+            .ok_or(SourceError::IndexOutOfBound)?;
+        Ok(blake2b_256(data))
+    }
+
+    fn cell_dep_type_hash(&self, index: usize) -> Result<Option<[u8; 32]>, SourceError> {
+        let (output, _) = self
+            .cell_deps
+            .get(index)
+            .ok_or(SourceError::IndexOutOfBound)?;
+        Ok(output
+            .type_()
+            .to_opt()
+            .map(|type_script| raw_hash(&type_script.calc_script_hash())))
+    }
+
+    fn load_cell_dep_data(
+        &self,
+        buf: &mut [u8],
+        offset: usize,
+        index: usize,
+    ) -> Result<usize, SourceError> {
+        let (_, data) = self
+            .cell_deps
+            .get(index)
+            .ok_or(SourceError::IndexOutOfBound)?;
+        Ok(load_partial(buf, offset, data))
+    }
+
+    fn raw_cell_dep(&self, index: usize) -> Result<[u8; CELL_DEP_SIZE], SourceError> {
+        let cell_dep = self
+            .raw_cell_deps
+            .get(index)
+            .ok_or(SourceError::IndexOutOfBound)?;
+        Ok(cell_dep.as_slice().try_into().expect("cell dep"))
+    }
+
+    fn load_block_extension(
+        &self,
+        buf: &mut [u8],
+        offset: usize,
+        index: usize,
+    ) -> Result<usize, SourceError> {
+        let header_hash = self
+            .mock_tx
+            .tx
+            .raw()
+            .header_deps()
+            .get(index)
+            .ok_or(SourceError::IndexOutOfBound)?;
+        let extension = self
+            .mock_tx
+            .mock_info
+            .extensions
+            .iter()
+            .find(|(hash, _)| *hash == header_hash)
+            .map(|(_, extension)| extension)
+            .ok_or(SourceError::ItemMissing)?;
+        Ok(load_partial(buf, offset, extension))
+    }
+
+    fn witness_len(&self, witness: WitnessSource) -> Result<usize, SourceError> {
+        self.witness(witness)
+            .map(|witness| witness.len())
+            .ok_or(SourceError::IndexOutOfBound)
+    }
+
+    fn read_witness<V: WitnessArgsVisitor>(&self, witness: WitnessSource, visitor: &mut V) -> i32 {
+        let Some(witness_args) = self
+            .witness(witness)
+            .and_then(|witness| WitnessArgs::from_slice(witness).ok())
+        else {
+            return ERROR_CODE_WITNESS_READER;
+        };
+        let fields = [
+            witness_args.lock(),
+            witness_args.input_type(),
+            witness_args.output_type(),
+        ];
+        for (i, field) in fields.iter().enumerate() {
+            let field = field.to_opt().map(|field| field.raw_data());
+            let start = FieldEvent::Start {
+                present: field.is_some(),
+                length: field.as_ref().map_or(0, |field| field.len() as u32),
+            };
+            let ret = dispatch(visitor, i, start);
+            if ret != 0 {
+                return ret;
+            }
+            if let Some(field) = field.filter(|field| !field.is_empty()) {
+                let ret = dispatch(visitor, i, FieldEvent::Data(&field));
+                if ret != 0 {
+                    return ret;
+                }
+            }
+        }
+        0
+    }
+}
@@ -0,0 +1,68 @@
+use serde_json::{json, Value};
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+const ZERO_LOCK_CODE_HASH: &str =
+    "0x0202020202020202020202020202020202020202020202020202020202020202";
+
+fn manifest_entry(index: u32) -> Value {
+    json!({
+        "input_out_point": {
+            "tx_hash": "0x0101010101010101010101010101010101010101010101010101010101010101",
+            "index": format!("{:#x}", index),
+        },
+        "capacity": "0x174876e800",
+        "lock": { "code_hash": ZERO_LOCK_CODE_HASH, "hash_type": "data2", "args": "0x" },
+        "data_hash": "0x0303030303030303030303030303030303030303030303030303030303030303",
+    })
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zero-lock-cli-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run_tree(dir: &Path, entries: Vec<Value>) -> Output {
+    let manifest = dir.join("manifest.json");
+    std::fs::write(
+        &manifest,
+        json!({ "version": 1, "upgrade": entries }).to_string(),
+    )
+    .unwrap();
+    Command::new(env!("CARGO_BIN_EXE_zero-lock"))
+        .arg("tree")
+        .arg("--manifest")
+        .arg(&manifest)
+        .args(["--zero-lock-code-hash", ZERO_LOCK_CODE_HASH])
+        .arg("--out")
+        .arg(dir.join("tree.json"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_tree_writes_valid_manifest() {
+    let dir = temp_dir("valid");
+    let output = run_tree(&dir, vec![manifest_entry(0), manifest_entry(1)]);
+    let written = dir.join("tree.json").exists();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+    assert!(written);
+}
+
+#[test]
+fn test_tree_refuses_invalid_manifest() {
+    let dir = temp_dir("invalid");
+    // Both leaves upgrade the same cell
+    let output = run_tree(&dir, vec![manifest_entry(0), manifest_entry(0)]);
+    let written = dir.join("tree.json").exists();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(!output.status.success());
+    assert!(!written);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("error: "));
+    assert!(!stdout.contains("Merkle root"));
+}
@@ -0,0 +1,177 @@
+// Transaction data of the running script, loaded via syscalls.
+use super::witness_args_reader::read_witness_args;
+use alloc::vec::Vec;
+use ckb_std::{
+    ckb_constants::Source, ckb_types::prelude::Entity, debug, error::SysError, high_level, syscalls,
+};
+use ckb_zero_lock_core::{
+    leaf::{CELL_DEP_SIZE, OUT_POINT_SIZE},
+    verifier::{DataSource, SourceError, WitnessSource},
+    witness_args::WitnessArgsVisitor,
+};
+
+pub struct SyscallSource;
+
+fn source_error(e: SysError) -> SourceError {
+    match e {
+        SysError::IndexOutOfBound => SourceError::IndexOutOfBound,
+        SysError::ItemMissing => SourceError::ItemMissing,
+        e => {
+            debug!("System error: {:?}", e);
+            let _ = e;
+            SourceError::Other
+        }
+    }
+}
+
+// Partial loading fills the whole buffer when more data is available
+fn loaded_len(result: Result<usize, SysError>, buf_len: usize) -> Result<usize, SourceError> {
+    match result {
+        Ok(n) => Ok(n),
+        Err(SysError::LengthNotEnough(_)) => Ok(buf_len),
+        Err(e) => Err(source_error(e)),
+    }
+}
+
+fn witness_source(witness: WitnessSource) -> (usize, Source) {
+    match witness {
+        WitnessSource::GroupInput(index) => (index, Source::GroupInput),
+        WitnessSource::Input(index) => (index, Source::Input),
+    }
+}
+
+fn load_transaction_u32(offset: usize) -> Result<u32, SourceError> {
+    let mut t = [0u8; 4];
+    load_transaction_exact(&mut t, offset)?;
+    Ok(u32::from_le_bytes(t))
+}
+
+fn load_transaction_exact(buf: &mut [u8], offset: usize) -> Result<(), SourceError> {
+    if loaded_len(syscalls::load_transaction(buf, offset), buf.len())? != buf.len() {
+        debug!(
+            "Transaction does not have enough data at offset {}!",
+            offset
+        );
+        return Err(SourceError::Other);
+    }
+    Ok(())
+}
+
+impl DataSource for SyscallSource {
+    fn script_args(&self) -> Result<Vec<u8>, SourceError> {
+        let script = high_level::load_script().map_err(source_error)?;
+        Ok(script.args().raw_data().to_vec())
+    }
+
+    fn script_hash(&self) -> Result<[u8; 32], SourceError> {
+        high_level::load_script_hash().map_err(source_error)
+    }
+
+    fn group_input_out_point(&self, index: usize) -> Result<[u8; OUT_POINT_SIZE], SourceError> {
+        let out_point =
+            high_level::load_input_out_point(index, Source::GroupInput).map_err(source_error)?;
+        let mut data = [0u8; OUT_POINT_SIZE];
+        data.copy_from_slice(out_point.as_slice());
+        Ok(data)
+    }
+
+    fn input_count(&self) -> Result<usize, SourceError> {
+        Ok(high_level::QueryIter::new(high_level::load_input_since, Source::Input).count())
+    }
+
+    fn input_lock_hash(&self, index: usize) -> Result<[u8; 32], SourceError> {
+        high_level::load_cell_lock_hash(index, Source::Input).map_err(source_error)
+    }
+
+    fn output_lock_hash(&self, index: usize) -> Result<[u8; 32], SourceError> {
+        high_level::load_cell_lock_hash(index, Source::Output).map_err(source_error)
+    }
+
+    fn output_data_hash(&self, index: usize) -> Result<[u8; 32], SourceError> {
+        high_level::load_cell_data_hash(index, Source::Output).map_err(source_error)
+    }
+
+    fn output_data(&self, index: usize) -> Result<Vec<u8>, SourceError> {
+        high_level::load_cell_data(index, Source::Output).map_err(source_error)
+    }
+
+    fn output_cell(&self, index: usize, f: &mut dyn FnMut(&[u8])) -> Result<(), SourceError> {
+        let mut loaded = 0;
+        let mut buf = [0u8; 4096];
+        loop {
+            match syscalls::load_cell(&mut buf, loaded, index, Source::Output) {
+                Ok(actual_loaded_len) => {
+                    f(&buf[..actual_loaded_len]);
+                    return Ok(());
+                }
+                Err(SysError::LengthNotEnough(_total_length)) => {
+                    f(&buf);
+                    loaded += buf.len();
+                }
+                Err(e) => return Err(source_error(e)),
+            }
+        }
+    }
+
+    fn cell_dep_data_hash(&self, index: usize) -> Result<[u8; 32], SourceError> {
+        high_level::load_cell_data_hash(index, Source::CellDep).map_err(source_error)
+    }
+
+    fn cell_dep_type_hash(&self, index: usize) -> Result<Option<[u8; 32]>, SourceError> {
+        high_level::load_cell_type_hash(index, Source::CellDep).map_err(source_error)
+    }
+
+    fn load_cell_dep_data(
+        &self,
+        buf: &mut [u8],
+        offset: usize,
+        index: usize,
+    ) -> Result<usize, SourceError> {
+        let buf_len = buf.len();
+        loaded_len(
+            syscalls::load_cell_data(buf, offset, index, Source::CellDep),
+            buf_len,
+        )
+    }
+
+    // Parses cell deps from the raw transaction, since Source::CellDep only
+    // exposes cell deps expanded from dep groups.
+    fn raw_cell_dep(&self, index: usize) -> Result<[u8; CELL_DEP_SIZE], SourceError> {
+        // Transaction: header(4 bytes), offset of raw(4 bytes), offset of witnesses(4 bytes)
+        // RawTransaction: header(4 bytes), offset of version(4 bytes), offset of cell_deps(4 bytes), ...
+        let raw_offset = load_transaction_u32(4)? as usize;
+        let cell_deps_offset = raw_offset + load_transaction_u32(raw_offset + 8)? as usize;
+        if index >= load_transaction_u32(cell_deps_offset)? as usize {
+            return Err(SourceError::IndexOutOfBound);
+        }
+        let mut cell_dep = [0u8; CELL_DEP_SIZE];
+        load_transaction_exact(&mut cell_dep, cell_deps_offset + 4 + index * CELL_DEP_SIZE)?;
+        Ok(cell_dep)
+    }
+
+    fn load_block_extension(
+        &self,
+        buf: &mut [u8],
+        offset: usize,
+        index: usize,
+    ) -> Result<usize, SourceError> {
+        let buf_len = buf.len();
+        loaded_len(
+            syscalls::load_block_extension(buf, offset, index, Source::HeaderDep),
+            buf_len,
+        )
+    }
+
+    fn witness_len(&self, witness: WitnessSource) -> Result<usize, SourceError> {
+        let (index, source) = witness_source(witness);
+        match syscalls::load_witness(&mut [], 0, index, source) {
+            Ok(n) | Err(SysError::LengthNotEnough(n)) => Ok(n),
+            Err(e) => Err(source_error(e)),
+        }
+    }
+
+    fn read_witness<V: WitnessArgsVisitor>(&self, witness: WitnessSource, visitor: &mut V) -> i32 {
+        let (index, source) = witness_source(witness);
+        read_witness_args(index, source, visitor)
+    }
+}
@@ -0,0 +1,108 @@
+//! Streaming WitnessArgs reader. WitnessArgs fields are loaded via partial
+//! loading and delivered to a visitor piece by piece, so that huge witnesses
+//! can be processed without being loaded in full.
+//!
+//! By default, WitnessArgs is parsed by the handwritten C reader, when
+//! `rust-witness-reader` feature is enabled, a pure Rust reader is used
+//! instead. Both deliver the same events.
+
+use ckb_std::ckb_constants::Source;
+#[cfg(not(feature = "rust-witness-reader"))]
+use core::{ffi::c_void, slice::from_raw_parts};
+
+#[cfg(feature = "rust-witness-reader")]
+mod rust_reader;
+
+pub use ckb_zero_lock_core::witness_args::{FieldEvent, WitnessArgsVisitor};
+
+// Fields in the order they appear in WitnessArgs
+const LOCK: usize = 0;
+const INPUT_TYPE: usize = 1;
+const OUTPUT_TYPE: usize = 2;
+
+fn dispatch<V: WitnessArgsVisitor>(visitor: &mut V, field: usize, event: FieldEvent) -> i32 {
+    match field {
+        LOCK => visitor.on_lock(event),
+        INPUT_TYPE => visitor.on_input_type(event),
+        _ => visitor.on_output_type(event),
+    }
+}
+
+/// Reads WitnessArgs at `index` of `source`, returns 0 when the whole
+/// WitnessArgs has been visited, or a non-zero error code otherwise.
+pub fn read_witness_args<V: WitnessArgsVisitor>(
+    index: usize,
+    source: Source,
+    visitor: &mut V,
+) -> i32 {
+    #[cfg(feature = "rust-witness-reader")]
+    {
+        rust_reader::read_witness_args(index, source, visitor)
+    }
+    #[cfg(not(feature = "rust-witness-reader"))]
+    {
+        let accessors = Accessors {
+            context: visitor as *mut V as *mut _,
+            lock_meta_accessor: visit_meta::<V, LOCK>,
+            lock_data_accessor: visit_data::<V, LOCK>,
+            input_type_meta_accessor: visit_meta::<V, INPUT_TYPE>,
+            input_type_data_accessor: visit_data::<V, INPUT_TYPE>,
+            output_type_meta_accessor: visit_meta::<V, OUTPUT_TYPE>,
+            output_type_data_accessor: visit_data::<V, OUTPUT_TYPE>,
+        };
+        unsafe { cwhr_rust_read_witness(index, source as usize, &accessors as *const Accessors) }
+    }
+}
+
+#[cfg(not(feature = "rust-witness-reader"))]
+type DataAccessor = unsafe extern "C" fn(*const u8, usize, *mut c_void) -> i32;
+#[cfg(not(feature = "rust-witness-reader"))]
+type MetaAccessor = unsafe extern "C" fn(i32, u32, *mut c_void) -> i32;
+
+#[cfg(not(feature = "rust-witness-reader"))]
+#[repr(C)]
+struct Accessors {
+    context: *mut c_void,
+    lock_meta_accessor: MetaAccessor,
+    lock_data_accessor: DataAccessor,
+    input_type_meta_accessor: MetaAccessor,
+    input_type_data_accessor: DataAccessor,
+    output_type_meta_accessor: MetaAccessor,
+    output_type_data_accessor: DataAccessor,
+}
+
+#[cfg(not(feature = "rust-witness-reader"))]
+extern "C" {
+    fn cwhr_rust_read_witness(index: usize, source: usize, accessors: *const Accessors) -> i32;
+}
+
+#[cfg(not(feature = "rust-witness-reader"))]
+unsafe extern "C" fn visit_meta<V: WitnessArgsVisitor, const FIELD: usize>(
+    present: i32,
+    length: u32,
+    context: *mut c_void,
+) -> i32 {
+    let visitor = &mut *(context as *mut V);
+    dispatch(
+        visitor,
+        FIELD,
+        FieldEvent::Start {
+            present: present != 0,
+            length,
+        },
+    )
+}
+
+#[cfg(not(feature = "rust-witness-reader"))]
+unsafe extern "C" fn visit_data<V: WitnessArgsVisitor, const FIELD: usize>(
+    data: *const u8,
+    length: usize,
+    context: *mut c_void,
+) -> i32 {
+    let visitor = &mut *(context as *mut V);
+    dispatch(
+        visitor,
+        FIELD,
+        FieldEvent::Data(from_raw_parts(data, length)),
+    )
+}
@@ -0,0 +1,111 @@
+// Streaming WitnessArgs reader in pure Rust, used in place of the handwritten
+// C reader when rust-witness-reader feature is enabled. Like the C reader,
+// the witness is never fully loaded: fields are loaded via partial loading
+// and fed to the visitor piece by piece.
+use super::{dispatch, FieldEvent, WitnessArgsVisitor};
+use crate::ERROR_CODE_WITNESS_READER;
+use ckb_std::{ckb_constants::Source, debug, error::SysError, syscalls};
+use core::cmp;
+
+const BUF_SIZE: usize = 32768;
+const WITNESS_ARGS_FIELDS: usize = 3;
+const HEADER_SIZE: usize = 4 * (WITNESS_ARGS_FIELDS + 1);
+
+fn read_u32(data: &[u8], offset: usize) -> usize {
+    let mut t = [0u8; 4];
+    t.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(t) as usize
+}
+
+// Fills buf with witness data starting at offset
+fn load_exact(buf: &mut [u8], offset: usize, index: usize, source: Source) -> Result<(), i32> {
+    match syscalls::load_witness(buf, offset, index, source) {
+        Ok(n) if n == buf.len() => Ok(()),
+        Err(SysError::LengthNotEnough(_)) => Ok(()),
+        e => {
+            debug!("Error loading witness: {:?}", e);
+            let _ = e;
+            Err(ERROR_CODE_WITNESS_READER)
+        }
+    }
+}
+
+fn read<V: WitnessArgsVisitor>(index: usize, source: Source, visitor: &mut V) -> Result<(), i32> {
+    let mut buf = [0u8; BUF_SIZE];
+    let total_size = match syscalls::load_witness(&mut buf[..HEADER_SIZE], 0, index, source) {
+        Ok(n) => n,
+        Err(SysError::LengthNotEnough(n)) => n,
+        Err(e) => {
+            debug!("Error loading witness: {:?}", e);
+            let _ = e;
+            return Err(ERROR_CODE_WITNESS_READER);
+        }
+    };
+    // WitnessArgs is verified in strict mode, additional fields are not allowed
+    if total_size < HEADER_SIZE
+        || read_u32(&buf, 0) != total_size
+        || read_u32(&buf, 4) != HEADER_SIZE
+    {
+        debug!("Witness is not a valid WitnessArgs!");
+        return Err(ERROR_CODE_WITNESS_READER);
+    }
+    let mut offsets = [0usize; WITNESS_ARGS_FIELDS + 1];
+    for (i, offset) in offsets.iter_mut().take(WITNESS_ARGS_FIELDS).enumerate() {
+        *offset = read_u32(&buf, 4 * (i + 1));
+    }
+    offsets[WITNESS_ARGS_FIELDS] = total_size;
+    if offsets.windows(2).any(|w| w[0] > w[1]) {
+        debug!("Witness is not a valid WitnessArgs!");
+        return Err(ERROR_CODE_WITNESS_READER);
+    }
+
+    // Each field is a BytesOpt, an empty field denotes None, otherwise the
+    // field is a Bytes: a little-endian u32 length followed by the content.
+    for field in 0..WITNESS_ARGS_FIELDS {
+        let (start, end) = (offsets[field], offsets[field + 1]);
+        let present = end > start;
+        let mut length = 0;
+        if present {
+            load_exact(&mut buf[..4], start, index, source)?;
+            length = read_u32(&buf, 0);
+            if end - start < 4 || length != end - start - 4 {
+                debug!("Witness field {} is not a valid Bytes!", field);
+                return Err(ERROR_CODE_WITNESS_READER);
+            }
+        }
+        let ret = dispatch(
+            visitor,
+            field,
+            FieldEvent::Start {
+                present,
+                length: length as u32,
+            },
+        );
+        if ret != 0 {
+            return Err(ret);
+        }
+
+        let mut loaded = 0;
+        while loaded < length {
+            let len = cmp::min(BUF_SIZE, length - loaded);
+            load_exact(&mut buf[..len], start + 4 + loaded, index, source)?;
+            let ret = dispatch(visitor, field, FieldEvent::Data(&buf[..len]));
+            if ret != 0 {
+                return Err(ret);
+            }
+            loaded += len;
+        }
+    }
+    Ok(())
+}
+
+pub fn read_witness_args<V: WitnessArgsVisitor>(
+    index: usize,
+    source: Source,
+    visitor: &mut V,
+) -> i32 {
+    match read(index, source, visitor) {
+        Ok(()) => 0,
+        Err(e) => e,
+    }
+}
@@ -0,0 +1,202 @@
+//! Cycles consumed by zero lock over a matrix of tree sizes, input_type
+//! payloads and output counts, compared against the committed baseline in
+//! cycles_baseline.toml. The run fails when a scenario consumes more cycles
+//! than its baseline by over `max_regression_percent`, or when a scenario
+//! has no baseline at all.
+//!
+//! cargo bench --bench cycles
+//! UPDATE_BASELINE=1 cargo bench --bench cycles
+
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, ScriptHashType, TransactionBuilder},
+    packed::{Byte32, CellOutput, Script},
+    prelude::*,
+};
+use ckb_zero_lock_sdk::{UpgradeLeaf, UpgradeTree};
+use ckb_zero_lock_tests::{
+    complete_tx, header, random_type_id_script, zero_lock_cell, DummyDataLoader, ALWAYS_SUCCESS_BIN,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::BTreeMap, path::PathBuf, process::ExitCode};
+
+const LEAVES: [u32; 4] = [1, 100, 10_000, 100_000];
+const INPUT_TYPE_SIZES: [usize; 4] = [0, 1024, 100 * 1024, 500 * 1024];
+const OUTPUTS: [usize; 3] = [1, 100, 1000];
+
+const DEFAULT_MAX_REGRESSION_PERCENT: u64 = 5;
+
+fn baseline_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("benches/cycles_baseline.toml")
+}
+
+struct Baseline {
+    max_regression_percent: u64,
+    cycles: BTreeMap<String, u64>,
+}
+
+impl Baseline {
+    fn load() -> Self {
+        let content = std::fs::read_to_string(baseline_path()).unwrap_or_default();
+        let table: toml::Table = content.parse().expect("parse baseline");
+        let max_regression_percent = table
+            .get("max_regression_percent")
+            .and_then(|v| v.as_integer())
+            .map_or(DEFAULT_MAX_REGRESSION_PERCENT, |v| v as u64);
+        let cycles = table
+            .get("cycles")
+            .and_then(|v| v.as_table())
+            .map(|cycles| {
+                cycles
+                    .iter()
+                    .map(|(name, v)| (name.clone(), v.as_integer().expect("cycles") as u64))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            max_regression_percent,
+            cycles,
+        }
+    }
+
+    fn write(&self) {
+        let mut table = toml::Table::new();
+        table.insert(
+            "max_regression_percent".to_string(),
+            (self.max_regression_percent as i64).into(),
+        );
+        let cycles: toml::Table = self
+            .cycles
+            .iter()
+            .map(|(name, cycles)| (name.clone(), (*cycles as i64).into()))
+            .collect();
+        table.insert("cycles".to_string(), cycles.into());
+        std::fs::write(baseline_path(), toml::to_string(&table).expect("toml"))
+            .expect("write baseline");
+    }
+}
+
+struct Tree {
+    // Hashes of other leaves, the upgraded leaf is appended as the last and
+    // deepest leaf
+    other_leaves: Vec<Byte32>,
+}
+
+impl Tree {
+    fn new(rng: &mut StdRng, leaves: u32) -> Self {
+        let other_leaves = (1..leaves)
+            .map(|_| {
+                let mut hash = [0u8; 32];
+                rng.fill(&mut hash);
+                Byte32::new(hash)
+            })
+            .collect();
+        Self { other_leaves }
+    }
+
+    fn cycles(&self, input_type_size: usize, outputs: usize) -> u64 {
+        let mut dummy_loader = DummyDataLoader::default();
+        let type_id = random_type_id_script();
+        let input_cell_meta = zero_lock_cell(
+            &mut dummy_loader,
+            &vec![1u8; 100].into(),
+            Some(type_id.clone()),
+        );
+        let output_cell_meta =
+            zero_lock_cell(&mut dummy_loader, &vec![2u8; 100].into(), Some(type_id));
+
+        let mut leaf = UpgradeLeaf::new(
+            input_cell_meta.out_point.clone(),
+            output_cell_meta.cell_output.clone(),
+            output_cell_meta.mem_cell_data.as_ref().unwrap(),
+        );
+        if input_type_size > 0 {
+            leaf.input_type = Some(vec![3u8; input_type_size].into());
+        }
+        let mut hashes = self.other_leaves.clone();
+        hashes.push(leaf.hash());
+        let index = hashes.len() as u32 - 1;
+        let tree = UpgradeTree::from_hashes(hashes, false);
+        let header_dep = header(&mut dummy_loader, &tree.root());
+        let witness = leaf.witness(tree.proof(index, &[0], &leaf).expect("proof"));
+
+        // Outputs other than the continuing one are locked by always success
+        let other_output = CellOutput::new_builder()
+            .lock(
+                Script::new_builder()
+                    .code_hash(CellOutput::calc_data_hash(&ALWAYS_SUCCESS_BIN))
+                    .hash_type(ScriptHashType::Data2.into())
+                    .build(),
+            )
+            .capacity(Capacity::bytes(61).unwrap().pack())
+            .build();
+        let builder = TransactionBuilder::default()
+            .output(output_cell_meta.cell_output.clone())
+            .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+            .outputs(std::iter::repeat_n(other_output, outputs - 1))
+            .outputs_data(std::iter::repeat_n(Bytes::new().pack(), outputs - 1))
+            .header_dep(header_dep)
+            .witness(witness.as_bytes().pack());
+        let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+        verifier.verify(u64::MAX).expect("pass verification")
+    }
+}
+
+fn main() -> ExitCode {
+    let update = std::env::var("UPDATE_BASELINE").is_ok();
+    let mut baseline = Baseline::load();
+    let mut regressions = 0;
+    let mut missing = 0;
+    let mut rng = StdRng::seed_from_u64(47);
+    for leaves in LEAVES {
+        let tree = Tree::new(&mut rng, leaves);
+        for input_type_size in INPUT_TYPE_SIZES {
+            for outputs in OUTPUTS {
+                let name = format!(
+                    "leaves-{}-input-type-{}-outputs-{}",
+                    leaves, input_type_size, outputs
+                );
+                let cycles = tree.cycles(input_type_size, outputs);
+                match baseline.cycles.get(&name) {
+                    Some(&recorded)
+                        if cycles * 100 > recorded * (100 + baseline.max_regression_percent) =>
+                    {
+                        regressions += 1;
+                        println!("{}: {} cycles, REGRESSION from {}", name, cycles, recorded);
+                    }
+                    Some(&recorded) => {
+                        println!("{}: {} cycles, baseline {}", name, cycles, recorded)
+                    }
+                    None => {
+                        missing += 1;
+                        println!("{}: {} cycles, NO BASELINE", name, cycles)
+                    }
+                }
+                if update {
+                    baseline.cycles.insert(name, cycles);
+                }
+            }
+        }
+    }
+    if update {
+        baseline.write();
+        println!("Baseline written to {}", baseline_path().display());
+        return ExitCode::SUCCESS;
+    }
+    if regressions > 0 {
+        println!(
+            "{} scenarios regress by more than {}%",
+            regressions, baseline.max_regression_percent
+        );
+    }
+    if missing > 0 {
+        println!(
+            "{} scenarios have no baseline, record them with UPDATE_BASELINE=1",
+            missing
+        );
+    }
+    if regressions > 0 || missing > 0 {
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
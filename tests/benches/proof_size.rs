@@ -0,0 +1,74 @@
+//! Measures proof sizes of trees up to 100k leaves, and the cost of upgrade
+//! batches when batch leaves are scattered, as when sorted by input out
+//! point, or take the first leaves, as with manifest batches. Zero lock
+//! verifies one leaf per proof, so a batch costs the proofs of its leaves,
+//! stored in a proof cell shared by the batch.
+//!
+//! cargo bench --bench proof_size
+
+use ckb_types::packed::CellOutput;
+use ckb_zero_lock_sdk::{ProofCellBuilder, ProofSizeReport, UpgradeLeaf};
+use ckb_zero_lock_tests::random_out_point;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::time::Instant;
+
+// Lemmas and proof cell bytes of the batch leaves at `positions`
+fn batch_cost(report: &ProofSizeReport, positions: &[usize]) -> (usize, usize) {
+    let mut proof_cell = ProofCellBuilder::default();
+    let mut lemmas = 0;
+    for position in positions {
+        let leaf = &report.leaves[*position];
+        lemmas += leaf.lemmas;
+        proof_cell.push(&vec![0; leaf.proof_size]);
+    }
+    (lemmas, proof_cell.data().len())
+}
+
+// Share of the leaves upgraded in batches, and batch sizes
+const BATCHED_PERCENT: usize = 30;
+const BATCH_SIZES: std::ops::RangeInclusive<usize> = 2..=8;
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(46);
+    for n in [100, 1_000, 10_000, 100_000] {
+        let leaves: Vec<UpgradeLeaf> = (0..n)
+            .map(|_| UpgradeLeaf::new(random_out_point(), CellOutput::default(), &[]))
+            .collect();
+        let start = Instant::now();
+        let report = ProofSizeReport::new(&leaves, 1);
+        let elapsed = start.elapsed();
+
+        let mut batch_sizes = vec![];
+        let mut batched = 0;
+        while batched < n * BATCHED_PERCENT / 100 {
+            let size = rng.gen_range(BATCH_SIZES).min(n - batched);
+            batch_sizes.push(size);
+            batched += size;
+        }
+        let mut scattered_positions: Vec<usize> = (0..n).collect();
+        scattered_positions.shuffle(&mut rng);
+        let (mut scattered, mut adjacent, mut offset) = ((0, 0), (0, 0), 0);
+        for size in &batch_sizes {
+            let (lemmas, bytes) = batch_cost(&report, &scattered_positions[offset..offset + size]);
+            scattered = (scattered.0 + lemmas, scattered.1 + bytes);
+            let positions: Vec<usize> = (offset..offset + size).collect();
+            let (lemmas, bytes) = batch_cost(&report, &positions);
+            adjacent = (adjacent.0 + lemmas, adjacent.1 + bytes);
+            offset += size;
+        }
+
+        println!("Leaves: {}", n);
+        println!("  Report built in {:?}", elapsed);
+        println!("  Total proof size: {}", report.total_proof_size());
+        println!("  Total witness size: {}", report.total_witness_size());
+        println!("  Batches: {}, {} leaves", batch_sizes.len(), batched);
+        println!(
+            "  Scattered batches: {} lemmas, proof cells {} bytes",
+            scattered.0, scattered.1
+        );
+        println!(
+            "  Manifest batches: {} lemmas, proof cells {} bytes",
+            adjacent.0, adjacent.1
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! Lock script exercising WitnessArgsVisitor in tests. The first witness of
+//! the script group is streamed through a custom visitor, hashing lock and
+//! output_type fields, the digest must match script args. input_type is
+//! left to the default visitor method, and output_type must be present.
+#![no_std]
+#![cfg_attr(not(test), no_main)]
+
+use blake2b_ref::{Blake2b, Blake2bBuilder};
+use ckb_std::{ckb_constants::Source, high_level};
+use ckb_zero_lock::witness_args_reader::{read_witness_args, FieldEvent, WitnessArgsVisitor};
+
+ckb_std::entry!(program_entry);
+ckb_std::default_alloc!();
+
+const ERROR_MISSING_OUTPUT_TYPE: i32 = 4;
+
+struct DigestVisitor {
+    hasher: Blake2b,
+}
+
+impl DigestVisitor {
+    fn visit(&mut self, event: FieldEvent) {
+        match event {
+            FieldEvent::Start { present, length } => {
+                if present {
+                    self.hasher.update(&[1u8]);
+                    self.hasher.update(&length.to_le_bytes());
+                } else {
+                    self.hasher.update(&[0u8]);
+                }
+            }
+            FieldEvent::Data(data) => self.hasher.update(data),
+        }
+    }
+}
+
+impl WitnessArgsVisitor for DigestVisitor {
+    fn on_lock(&mut self, event: FieldEvent) -> i32 {
+        self.visit(event);
+        0
+    }
+
+    fn on_output_type(&mut self, event: FieldEvent) -> i32 {
+        if let FieldEvent::Start { present: false, .. } = event {
+            return ERROR_MISSING_OUTPUT_TYPE;
+        }
+        self.visit(event);
+        0
+    }
+}
+
+pub fn program_entry() -> i8 {
+    let Ok(script) = high_level::load_script() else {
+        return 1;
+    };
+    let mut visitor = DigestVisitor {
+        hasher: Blake2bBuilder::new(32)
+            .personal(b"ckb-default-hash")
+            .build(),
+    };
+    let ret = read_witness_args(0, Source::GroupInput, &mut visitor);
+    if ret != 0 {
+        return ret as i8;
+    }
+    let mut digest = [0u8; 32];
+    visitor.hasher.finalize(&mut digest);
+    if script.args().raw_data()[..] != digest[..] {
+        return 3;
+    }
+    0
+}
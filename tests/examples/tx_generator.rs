@@ -1,122 +1,289 @@
-use ckb_mock_tx_types::{MockCellDep, MockInfo, MockInput, MockTransaction, ReprMockTransaction};
-use ckb_traits::{ExtensionProvider, HeaderProvider};
+//! Generates upgrade transactions in ReprMockTransaction JSON, for fuzzing
+//! corpora and ckb-debugger fixtures. Each scenario builds one upgrade in a
+//! tree of random size, the negative scenarios are expected to fail zero
+//! lock.
+//!
+//! SEED: seed determining the first transaction, later ones use the following
+//! seeds
+//! SCENARIO: comma separated scenario names, or all, valid by default
+//! COUNT: transactions per scenario, 1 by default
+//! OUTPUT_DIR: writes <scenario>-<seed>.json for each transaction
+//! OUTPUT: file of the single transaction without OUTPUT_DIR, ./tx.json by
+//! default
+//!
+//! Exits with failure when a scenario does not verify as expected, no
+//! fixture is written for it.
+
+use ckb_mock_tx_types::ReprMockTransaction;
 use ckb_types::{
-    core::{cell::ResolvedTransaction, TransactionBuilder},
+    bytes::Bytes,
+    core::{DepType, TransactionBuilder},
+    packed::{Byte32, CellDep},
     prelude::*,
 };
+use ckb_zero_lock_sdk::{UpgradeLeaf, UpgradeTree};
 use ckb_zero_lock_tests::{
-    bury_in_merkle_tree, complete_tx, header, random_type_id_script, zero_lock_cell,
-    DummyDataLoader,
+    build_mock_transaction, code_cell_dep, complete_tx, dep_group_cell, dep_group_data,
+    dep_group_option, dep_group_template, header, leaf_options, random_type_id_script_with,
+    script_cell, zero_lock_cell, DummyDataLoader, CELL_DEP_TYPE_HASH, EXTENDED_LEAF_VERSION,
+    LEAF_OPTION_DEP_GROUP,
 };
 use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{path::PathBuf, process::ExitCode};
 
-fn main() {
-    let seed: u64 = match std::env::var("SEED") {
-        Ok(val) => str::parse(&val).expect("parsing number"),
-        Err(_) => std::time::SystemTime::now()
-            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_nanos() as u64,
-    };
-    println!("Seed: {}", seed);
+const MAX_CYCLES: u64 = 60_000_000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scenario {
+    Valid,
+    // Unrelated header deps surround the one carrying the root
+    MultipleHeaders,
+    // Random input_type and output_type payloads in the witness
+    Payloads,
+    // The proof names a header dep carrying another root
+    WrongHeaderIndex,
+    FlippedRootBit,
+    TruncatedProof,
+    // Another output keeps the zero lock of the input
+    ExtraZeroLockOutput,
+    // The upgraded cell is a dep group whose first entry is a placeholder,
+    // and the transaction also uses a dep group cell dep
+    DepGroup,
+}
+
+const SCENARIOS: [Scenario; 8] = [
+    Scenario::Valid,
+    Scenario::MultipleHeaders,
+    Scenario::Payloads,
+    Scenario::WrongHeaderIndex,
+    Scenario::FlippedRootBit,
+    Scenario::TruncatedProof,
+    Scenario::ExtraZeroLockOutput,
+    Scenario::DepGroup,
+];
+
+impl Scenario {
+    fn name(&self) -> &'static str {
+        match self {
+            Scenario::Valid => "valid",
+            Scenario::MultipleHeaders => "multiple-headers",
+            Scenario::Payloads => "payloads",
+            Scenario::WrongHeaderIndex => "wrong-header-index",
+            Scenario::FlippedRootBit => "flipped-root-bit",
+            Scenario::TruncatedProof => "truncated-proof",
+            Scenario::ExtraZeroLockOutput => "extra-zero-lock-output",
+            Scenario::DepGroup => "dep-group",
+        }
+    }
+
+    fn expect_pass(&self) -> bool {
+        matches!(
+            self,
+            Scenario::Valid | Scenario::MultipleHeaders | Scenario::Payloads | Scenario::DepGroup
+        )
+    }
+}
+
+fn parse_scenarios(value: &str) -> Vec<Scenario> {
+    if value == "all" {
+        return SCENARIOS.to_vec();
+    }
+    value
+        .split(',')
+        .map(|name| {
+            *SCENARIOS
+                .iter()
+                .find(|s| s.name() == name)
+                .unwrap_or_else(|| panic!("unknown scenario {}", name))
+        })
+        .collect()
+}
+
+fn random_bytes(rng: &mut StdRng, len: usize) -> Bytes {
+    let mut bytes = vec![0u8; len];
+    rng.fill(&mut bytes[..]);
+    bytes.into()
+}
 
+fn random_hash(rng: &mut StdRng) -> Byte32 {
+    let mut hash = [0u8; 32];
+    rng.fill(&mut hash);
+    Byte32::new(hash)
+}
+
+fn generate(scenario: Scenario, seed: u64) -> Result<ReprMockTransaction, String> {
     let mut rng = StdRng::seed_from_u64(seed);
+    let mut dummy_loader = DummyDataLoader::seeded(rng.gen());
+    let type_id = random_type_id_script_with(&mut rng);
+    let old_contract = vec![1u8; 100].into();
+    let input_cell_meta = zero_lock_cell(&mut dummy_loader, &old_contract, Some(type_id.clone()));
+    let mut builder = TransactionBuilder::default();
+
+    let (output_cell_meta, mut leaf) = if scenario == Scenario::DepGroup {
+        let new_code = zero_lock_cell(
+            &mut dummy_loader,
+            &vec![2u8; 100].into(),
+            Some(type_id.clone()),
+        );
+        let library = script_cell(&mut dummy_loader, &vec![3u8; 100].into());
+        let data = dep_group_data(&[new_code.out_point.clone(), library.out_point.clone()]);
+        let output_cell_meta = zero_lock_cell(&mut dummy_loader, &data, None);
+        let mut leaf = UpgradeLeaf::new(
+            input_cell_meta.out_point.clone(),
+            output_cell_meta.cell_output.clone(),
+            &dep_group_template(&data, &[0]),
+        );
+        leaf.leaf_version = EXTENDED_LEAF_VERSION;
+        leaf.leaf_options = leaf_options(&[(
+            LEAF_OPTION_DEP_GROUP,
+            dep_group_option(&[(0, 0, CELL_DEP_TYPE_HASH, type_id.calc_script_hash())]),
+        )]);
+        let library_group = dep_group_cell(&mut dummy_loader, &[&library]);
+        builder = builder.cell_dep(code_cell_dep(&new_code)).cell_dep(
+            CellDep::new_builder()
+                .out_point(library_group.out_point.clone())
+                .dep_type(DepType::DepGroup.into())
+                .build(),
+        );
+        (output_cell_meta, leaf)
+    } else {
+        let new_contract = vec![2u8; 100].into();
+        let output_cell_meta = zero_lock_cell(&mut dummy_loader, &new_contract, Some(type_id));
+        let leaf = UpgradeLeaf::new(
+            input_cell_meta.out_point.clone(),
+            output_cell_meta.cell_output.clone(),
+            output_cell_meta.mem_cell_data.as_ref().unwrap(),
+        );
+        (output_cell_meta, leaf)
+    };
+    if scenario == Scenario::Payloads {
+        let input_type_size = rng.gen_range(0..64 * 1024);
+        leaf.input_type = Some(random_bytes(&mut rng, input_type_size));
+        let output_type_size = rng.gen_range(0..64 * 1024);
+        leaf.output_type = Some(random_bytes(&mut rng, output_type_size));
+    }
 
     let entries = rng.gen_range(1..1000);
     println!("Merkle root entries: {}", entries + 1);
+    let mut hashes: Vec<Byte32> = (0..entries).map(|_| random_hash(&mut rng)).collect();
+    let index = rng.gen_range(0..=hashes.len());
+    hashes.insert(index, leaf.hash());
+    let tree = UpgradeTree::from_hashes(hashes, false);
 
-    let mut dummy_loader = DummyDataLoader::default();
-    let type_id = random_type_id_script();
-    let old_contract = vec![1u8; 100].into();
-    let input_cell_meta = zero_lock_cell(&mut dummy_loader, &old_contract, Some(type_id.clone()));
-    let new_contract = vec![2u8; 100].into();
-    let output_cell_meta = zero_lock_cell(&mut dummy_loader, &new_contract, Some(type_id));
-
-    let (root, proof_witness) = bury_in_merkle_tree(
-        &input_cell_meta,
-        &output_cell_meta,
-        entries,
-        &mut rng,
-        0,
-        None,
-        None,
-    );
-    let header_dep = header(&mut dummy_loader, &root);
+    let mut root = tree.root();
+    if scenario == Scenario::FlippedRootBit {
+        let flip_bit = rng.gen_range(0..256);
+        let mut raw_root = root.raw_data().to_vec();
+        raw_root[flip_bit / 8] ^= 1 << (flip_bit % 8);
+        root = Byte32::from_slice(&raw_root).unwrap();
+    }
+    let stale_headers = match scenario {
+        Scenario::MultipleHeaders => rng.gen_range(1..4),
+        Scenario::WrongHeaderIndex => 1,
+        _ => 0,
+    };
+    let header_position = rng.gen_range(0..=stale_headers);
+    for i in 0..=stale_headers {
+        let header_root = if i == header_position {
+            root.clone()
+        } else {
+            random_hash(&mut rng)
+        };
+        builder = builder.header_dep(header(&mut dummy_loader, &header_root));
+    }
+    let header_index = match scenario {
+        Scenario::WrongHeaderIndex => (header_position + 1) % (stale_headers + 1),
+        _ => header_position,
+    };
 
+    let mut proof = tree
+        .proof(index as u32, &[header_index as u32], &leaf)
+        .expect("proof");
+    if scenario == Scenario::TruncatedProof {
+        let truncated_bytes = rng.gen_range(1..proof.len());
+        proof.truncate(proof.len() - truncated_bytes);
+    }
+    let witness = leaf.witness(proof).as_bytes();
     println!(
         "Proof witness total length(in WitnessArgs format): {}",
-        proof_witness.len()
+        witness.len()
     );
 
-    let builder = TransactionBuilder::default()
+    builder = builder
         .output(output_cell_meta.cell_output.clone())
         .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
-        .header_dep(header_dep)
-        .witness(proof_witness.pack());
-
-    let (_, rtx, dummy) = complete_tx(dummy_loader, builder, vec![input_cell_meta]);
-
-    let mock_tx = build_mock_transaction(&rtx, &dummy).expect("build mock transaction");
-    let repr_tx: ReprMockTransaction = mock_tx.into();
-    let json = serde_json::to_string_pretty(&repr_tx).expect("json");
+        .witness(witness.pack());
+    if scenario == Scenario::ExtraZeroLockOutput {
+        builder = builder
+            .output(output_cell_meta.cell_output.clone())
+            .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack());
+    }
 
-    let output_path = match std::env::var("OUTPUT") {
-        Ok(val) => val,
-        _ => "./tx.json".to_string(),
-    };
-    std::fs::write(output_path, &json).expect("write");
+    let (verifier, rtx, dummy) = complete_tx(dummy_loader, builder, vec![input_cell_meta]);
+    let verify_result = verifier.verify(MAX_CYCLES);
+    match &verify_result {
+        Ok(cycles) => println!("Passes verification, cycles: {}", cycles),
+        Err(e) => println!("Fails verification: {}", e),
+    }
+    if verify_result.is_ok() != scenario.expect_pass() {
+        let expected = if scenario.expect_pass() {
+            "pass"
+        } else {
+            "fail"
+        };
+        return Err(format!(
+            "scenario {} is expected to {} verification",
+            scenario.name(),
+            expected
+        ));
+    }
+    let mock_tx = build_mock_transaction(&rtx, &dummy)?;
+    Ok(mock_tx.into())
 }
 
-fn build_mock_transaction<DL: HeaderProvider + ExtensionProvider>(
-    rtx: &ResolvedTransaction,
-    dl: &DL,
-) -> Result<MockTransaction, String> {
-    let mut inputs = Vec::with_capacity(rtx.resolved_inputs.len());
-    // TODO: dep group, cell's header
-    for (i, input) in rtx.resolved_inputs.iter().enumerate() {
-        inputs.push(MockInput {
-            input: rtx
-                .transaction
-                .inputs()
-                .get(i)
-                .ok_or_else(|| format!("Cannot locate cell input {} in transaction", i))?,
-            output: input.cell_output.clone(),
-            data: input.mem_cell_data.clone().unwrap(),
-            header: None,
-        });
+fn main() -> ExitCode {
+    let seed: u64 = match std::env::var("SEED") {
+        Ok(val) => str::parse(&val).expect("parsing number"),
+        Err(_) => std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64,
+    };
+    let scenarios = parse_scenarios(
+        &std::env::var("SCENARIO").unwrap_or_else(|_| Scenario::Valid.name().to_string()),
+    );
+    let count: u64 = match std::env::var("COUNT") {
+        Ok(val) => str::parse(&val).expect("parsing number"),
+        Err(_) => 1,
+    };
+    let output_dir = std::env::var("OUTPUT_DIR").ok().map(PathBuf::from);
+    if output_dir.is_none() && scenarios.len() as u64 * count > 1 {
+        panic!("OUTPUT_DIR is required for more than one transaction");
     }
-    let mut cell_deps = Vec::with_capacity(rtx.resolved_cell_deps.len());
-    for (i, dep) in rtx.resolved_cell_deps.iter().enumerate() {
-        cell_deps.push(MockCellDep {
-            cell_dep: rtx.transaction.cell_deps().get(i).ok_or_else(|| {
-                format!(
-                    "Cannot locate cell dep {}, maybe you are using a dep group?",
-                    i
-                )
-            })?,
-            output: dep.cell_output.clone(),
-            data: dep.mem_cell_data.clone().unwrap(),
-            header: None,
-        });
+    if let Some(output_dir) = &output_dir {
+        std::fs::create_dir_all(output_dir).expect("create output dir");
     }
-    let mut header_deps = Vec::with_capacity(rtx.transaction.header_deps().len());
-    let mut extensions = Vec::new();
-    for header_hash in rtx.transaction.header_deps_iter() {
-        header_deps.push(
-            dl.get_header(&header_hash)
-                .ok_or_else(|| format!("Cannot find header {:x}!", header_hash))?,
-        );
-        if let Some(extension) = dl.get_block_extension(&header_hash) {
-            extensions.push((header_hash, extension.unpack()));
+
+    for scenario in scenarios {
+        for i in 0..count {
+            let seed = seed.wrapping_add(i);
+            println!("Scenario: {}, seed: {}", scenario.name(), seed);
+            let repr_tx = match generate(scenario, seed) {
+                Ok(repr_tx) => repr_tx,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let json = serde_json::to_string_pretty(&repr_tx).expect("json");
+            let output_path = match &output_dir {
+                Some(dir) => dir.join(format!("{}-{}.json", scenario.name(), seed)),
+                None => PathBuf::from(
+                    std::env::var("OUTPUT").unwrap_or_else(|_| "./tx.json".to_string()),
+                ),
+            };
+            std::fs::write(output_path, &json).expect("write");
         }
     }
-    Ok(MockTransaction {
-        mock_info: MockInfo {
-            inputs,
-            cell_deps,
-            header_deps,
-            extensions,
-        },
-        tx: rtx.transaction.data(),
-    })
+    ExitCode::SUCCESS
 }
@@ -2,7 +2,7 @@
 mod tests;
 
 use ckb_chain_spec::consensus::{ConsensusBuilder, TYPE_ID_CODE_HASH};
-use ckb_hash::{blake2b_256, new_blake2b};
+use ckb_mock_tx_types::{MockCellDep, MockInfo, MockInput, MockTransaction, ReprMockTransaction};
 use ckb_script::{TransactionScriptsVerifier, TxVerifyEnv};
 use ckb_traits::{CellDataProvider, ExtensionProvider, HeaderProvider};
 use ckb_types::{
@@ -10,15 +10,30 @@ use ckb_types::{
     core::{
         cell::{CellMeta, CellMetaBuilder, ResolvedTransaction},
         hardfork::{HardForks, CKB2021, CKB2023},
-        Capacity, DepType, EpochExt, HeaderBuilder, HeaderView, ScriptHashType, TransactionBuilder,
+        Capacity, Cycle, DepType, EpochExt, HeaderBuilder, HeaderView, ScriptHashType,
+        TransactionBuilder,
     },
     packed::{self, Byte32, CellDep, CellInput, CellOutput, OutPoint, Script, WitnessArgs},
     prelude::*,
 };
+pub use ckb_zero_lock_core::{
+    args::{WITNESS_LOCATION_BEYOND_INPUTS, WITNESS_LOCATION_GROUP_INPUT, WITNESS_LOCATION_INDEX},
+    leaf::{
+        CELL_DEP_DATA_HASH, CELL_DEP_TYPE_HASH, LEAF_OPTION_DEP_GROUP, LEAF_OPTION_METADATA,
+        LEAF_OPTION_RELEASE, LEAF_OPTION_REQUIRED_CELL_DEPS, METADATA_INPUT_TYPE,
+        METADATA_OUTPUT_TYPE,
+    },
+    DISCOVER_HEADER_INDEX, MAX_DISCOVERED_HEADER_DEPS,
+};
+pub use ckb_zero_lock_sdk::{
+    leaf_trailer, Blake2bMerge, ExtensionBuilder, Sha256Merge, UpgradeLeaf, UpgradeTree,
+    EXTENDED_LEAF_VERSION, LEAF_VERSION, MERKLE_ROOT_OFFSET, SHA256_LEAF_VERSION,
+};
 use lazy_static::lazy_static;
-use merkle_cbt::{merkle_tree::Merge, MerkleTree, CBMT};
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 lazy_static! {
@@ -30,15 +45,56 @@ lazy_static! {
     });
     pub static ref ZERO_LOCK_BIN: Bytes =
         Bytes::from(std::fs::read(&*ZERO_LOCK_PATH).expect("read"));
+    // Zero lock built with rust-witness-reader feature
+    pub static ref ZERO_LOCK_RUST_READER_PATH: String =
+        std::env::var("ZERO_LOCK_RUST_READER_PATH").unwrap_or_else(|_| {
+            format!(
+                "{}/../target/rust-witness-reader/riscv64imac-unknown-none-elf/release/ckb-zero-lock",
+                env!("CARGO_MANIFEST_DIR"),
+            )
+        });
+    pub static ref ZERO_LOCK_RUST_READER_BIN: Bytes =
+        Bytes::from(std::fs::read(&*ZERO_LOCK_RUST_READER_PATH).expect("read"));
+    pub static ref WITNESS_DIGEST_PATH: String =
+        std::env::var("WITNESS_DIGEST_PATH").unwrap_or_else(|_| {
+            format!(
+                "{}/contracts/witness-digest/target/riscv64imac-unknown-none-elf/release/witness-digest",
+                env!("CARGO_MANIFEST_DIR"),
+            )
+        });
+    pub static ref WITNESS_DIGEST_BIN: Bytes =
+        Bytes::from(std::fs::read(&*WITNESS_DIGEST_PATH).expect("read"));
     pub static ref ALWAYS_SUCCESS_BIN: Bytes =
         Bytes::from(ckb_always_success_script::ALWAYS_SUCCESS.to_vec());
 }
 
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct DummyDataLoader {
     pub cells: HashMap<OutPoint, (CellOutput, Bytes)>,
     pub headers: HashMap<Byte32, HeaderView>,
     pub extensions: HashMap<Byte32, Bytes>,
+    /// Source of the out points and header contents created through the
+    /// loader.
+    pub rng: StdRng,
+}
+
+impl Default for DummyDataLoader {
+    fn default() -> Self {
+        Self::seeded(thread_rng().gen())
+    }
+}
+
+impl DummyDataLoader {
+    /// Creates a loader whose cells and headers are fully determined by
+    /// `seed`.
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            cells: HashMap::default(),
+            headers: HashMap::default(),
+            extensions: HashMap::default(),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
 }
 
 impl CellDataProvider for DummyDataLoader {
@@ -66,8 +122,11 @@ impl ExtensionProvider for DummyDataLoader {
 }
 
 pub fn random_out_point() -> OutPoint {
+    random_out_point_with(&mut thread_rng())
+}
+
+pub fn random_out_point_with<R: Rng>(rng: &mut R) -> OutPoint {
     let tx_hash = {
-        let mut rng = thread_rng();
         let mut buf = [0u8; 32];
         rng.fill(&mut buf);
         buf.pack()
@@ -76,11 +135,14 @@ pub fn random_out_point() -> OutPoint {
 }
 
 pub fn random_type_id_script() -> Script {
-    let mut rng = thread_rng();
+    random_type_id_script_with(&mut thread_rng())
+}
+
+pub fn random_type_id_script_with<R: Rng>(rng: &mut R) -> Script {
     let args = {
-        let mut buf = vec![0u8; 32];
+        let mut buf = [0u8; 32];
         rng.fill(&mut buf[..]);
-        buf.pack()
+        buf.to_vec().pack()
     };
     Script::new_builder()
         .code_hash(TYPE_ID_CODE_HASH.pack())
@@ -99,8 +161,15 @@ pub fn insert_cell(dummy: &mut DummyDataLoader, cell_meta: &CellMeta) {
     );
 }
 
+pub fn resolve_cell(dummy: &DummyDataLoader, out_point: &OutPoint) -> CellMeta {
+    let (cell_output, data) = dummy.cells.get(out_point).cloned().expect("resolve cell");
+    CellMetaBuilder::from_cell_output(cell_output, data)
+        .out_point(out_point.clone())
+        .build()
+}
+
 pub fn script_cell(dummy: &mut DummyDataLoader, script_data: &Bytes) -> CellMeta {
-    let out_point = random_out_point();
+    let out_point = random_out_point_with(&mut dummy.rng);
     let cell = CellOutput::new_builder()
         .capacity(
             Capacity::bytes(script_data.len())
@@ -116,7 +185,7 @@ pub fn script_cell(dummy: &mut DummyDataLoader, script_data: &Bytes) -> CellMeta
 }
 
 pub fn always_success_cell(dummy: &mut DummyDataLoader, capacity_bytes: usize) -> CellMeta {
-    let out_point = random_out_point();
+    let out_point = random_out_point_with(&mut dummy.rng);
     let lock = Script::new_builder()
         .code_hash(CellOutput::calc_data_hash(&ALWAYS_SUCCESS_BIN))
         .hash_type(ScriptHashType::Data2.into())
@@ -136,15 +205,47 @@ pub fn always_success_cell(dummy: &mut DummyDataLoader, capacity_bytes: usize) -
     cell_meta
 }
 
+pub fn code_cell_dep(cell_meta: &CellMeta) -> CellDep {
+    CellDep::new_builder()
+        .out_point(cell_meta.out_point.clone())
+        .dep_type(DepType::Code.into())
+        .build()
+}
+
+pub fn dep_group_data(out_points: &[OutPoint]) -> Bytes {
+    packed::OutPointVec::new_builder()
+        .set(out_points.to_vec())
+        .build()
+        .as_bytes()
+}
+
+pub fn dep_group_cell(dummy: &mut DummyDataLoader, members: &[&CellMeta]) -> CellMeta {
+    let out_points: Vec<OutPoint> = members
+        .iter()
+        .map(|member| member.out_point.clone())
+        .collect();
+    script_cell(dummy, &dep_group_data(&out_points))
+}
+
 pub fn zero_lock_cell(
     dummy: &mut DummyDataLoader,
     data: &Bytes,
     type_script: Option<Script>,
 ) -> CellMeta {
-    let out_point = random_out_point();
+    zero_lock_cell_with_args(dummy, data, type_script, Bytes::new())
+}
+
+pub fn zero_lock_cell_with_args(
+    dummy: &mut DummyDataLoader,
+    data: &Bytes,
+    type_script: Option<Script>,
+    args: Bytes,
+) -> CellMeta {
+    let out_point = random_out_point_with(&mut dummy.rng);
     let lock = Script::new_builder()
         .code_hash(CellOutput::calc_data_hash(&ZERO_LOCK_BIN))
         .hash_type(ScriptHashType::Data2.into())
+        .args(args.pack())
         .build();
     let cell = CellOutput::new_builder()
         .lock(lock)
@@ -158,15 +259,196 @@ pub fn zero_lock_cell(
     cell_meta
 }
 
+/// Creates a contract cell under a new Type ID, and the cell upgrading it,
+/// both locked by zero lock using `args`.
+pub fn contract_upgrade_cells(dummy: &mut DummyDataLoader, args: Bytes) -> (CellMeta, CellMeta) {
+    let type_id = random_type_id_script_with(&mut dummy.rng);
+    let old_contract = vec![1u8; 100].into();
+    let input_cell_meta =
+        zero_lock_cell_with_args(dummy, &old_contract, Some(type_id.clone()), args.clone());
+    let new_contract = vec![2u8; 100].into();
+    let output_cell_meta = zero_lock_cell_with_args(dummy, &new_contract, Some(type_id), args);
+    (input_cell_meta, output_cell_meta)
+}
+
+thread_local! {
+    static TEST_SEED: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Records the seed of the running test case, so a mock transaction dumped
+/// on failure can be told apart from other cases of the same property test.
+pub fn set_test_seed(seed: u64) {
+    TEST_SEED.with(|s| s.set(Some(seed)));
+}
+
+/// Builds the mock transaction of a resolved transaction, ready to be
+/// replayed in ckb-debugger. Dep groups are exported together with their
+/// members, and headers of input and dep cells are exported as header deps
+/// of the mock info. Headers missing from `dl` are left out, so the mock
+/// transaction fails the same way.
+pub fn build_mock_transaction<DL: HeaderProvider + ExtensionProvider>(
+    rtx: &ResolvedTransaction,
+    dl: &DL,
+) -> Result<MockTransaction, String> {
+    // Block hashes of input and dep cells, exported with the header deps so
+    // load_header on a cell resolves in replays
+    let mut cell_headers: Vec<Byte32> = Vec::new();
+    let mut cell_header = |cell: &CellMeta| {
+        let block_hash = cell
+            .transaction_info
+            .as_ref()
+            .map(|info| info.block_hash.clone());
+        cell_headers.extend(block_hash.clone());
+        block_hash
+    };
+
+    let mut inputs = Vec::with_capacity(rtx.resolved_inputs.len());
+    for (i, input) in rtx.resolved_inputs.iter().enumerate() {
+        inputs.push(MockInput {
+            input: rtx
+                .transaction
+                .inputs()
+                .get(i)
+                .ok_or_else(|| format!("Cannot locate cell input {} in transaction", i))?,
+            output: input.cell_output.clone(),
+            data: input.mem_cell_data.clone().unwrap(),
+            header: cell_header(input),
+        });
+    }
+
+    // Dep groups are exported as the group cell followed by its members,
+    // matching the expansion order of resolved_cell_deps
+    let mut cell_deps: Vec<MockCellDep> = Vec::with_capacity(rtx.resolved_cell_deps.len());
+    let mut resolved_cell_deps = rtx.resolved_cell_deps.iter();
+    let mut resolved_dep_groups = rtx.resolved_dep_groups.iter();
+    for (i, cell_dep) in rtx.transaction.cell_deps_iter().enumerate() {
+        let mut resolved = Vec::new();
+        if cell_dep.dep_type().as_slice()[0] == DepType::DepGroup as u8 {
+            let group = resolved_dep_groups
+                .next()
+                .ok_or_else(|| format!("Cannot locate dep group of cell dep {}", i))?;
+            resolved.push((cell_dep, group));
+            let out_points = packed::OutPointVec::from_slice(group.mem_cell_data.as_ref().unwrap())
+                .map_err(|e| format!("Invalid dep group data of cell dep {}: {}", i, e))?;
+            for out_point in out_points.into_iter() {
+                let member = resolved_cell_deps.next().ok_or_else(|| {
+                    format!("Cannot locate member {} of dep group {}", out_point, i)
+                })?;
+                resolved.push((
+                    CellDep::new_builder()
+                        .out_point(out_point)
+                        .dep_type(DepType::Code.into())
+                        .build(),
+                    member,
+                ));
+            }
+        } else {
+            let dep = resolved_cell_deps
+                .next()
+                .ok_or_else(|| format!("Cannot locate resolved cell dep {}", i))?;
+            resolved.push((cell_dep, dep));
+        }
+        for (cell_dep, dep) in resolved {
+            if cell_deps
+                .iter()
+                .any(|mock| mock.cell_dep.as_slice() == cell_dep.as_slice())
+            {
+                continue;
+            }
+            cell_deps.push(MockCellDep {
+                cell_dep,
+                output: dep.cell_output.clone(),
+                data: dep.mem_cell_data.clone().unwrap(),
+                header: cell_header(dep),
+            });
+        }
+    }
+
+    let mut header_hashes: Vec<Byte32> = rtx.transaction.header_deps_iter().collect();
+    for block_hash in cell_headers {
+        if !header_hashes.contains(&block_hash) {
+            header_hashes.push(block_hash);
+        }
+    }
+    let mut header_deps = Vec::with_capacity(header_hashes.len());
+    let mut extensions = Vec::new();
+    for header_hash in header_hashes {
+        if let Some(header) = dl.get_header(&header_hash) {
+            header_deps.push(header);
+        }
+        if let Some(extension) = dl.get_block_extension(&header_hash) {
+            extensions.push((header_hash, extension.unpack()));
+        }
+    }
+    Ok(MockTransaction {
+        mock_info: MockInfo {
+            inputs,
+            cell_deps,
+            header_deps,
+            extensions,
+        },
+        tx: rtx.transaction.data(),
+    })
+}
+
+/// Writes the ReprMockTransaction JSON of a transaction to MOCK_TX_DIR,
+/// `target/failed-txs` by default, keyed by the name of the running test
+/// and the seed set by `set_test_seed`.
+pub fn dump_mock_transaction(
+    rtx: &ResolvedTransaction,
+    dummy: &DummyDataLoader,
+) -> Result<PathBuf, String> {
+    let repr_tx: ReprMockTransaction = build_mock_transaction(rtx, dummy)?.into();
+    let json = serde_json::to_string_pretty(&repr_tx).map_err(|e| e.to_string())?;
+    let dir = std::env::var("MOCK_TX_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/failed-txs"));
+    let mut name = std::thread::current()
+        .name()
+        .unwrap_or("unnamed")
+        .replace("::", "-");
+    if let Some(seed) = TEST_SEED.with(Cell::get) {
+        name = format!("{}-seed-{}", name, seed);
+    }
+    let path = dir.join(format!("{}.json", name));
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Script verifier of a test transaction. When dropped while the test
+/// panics, the transaction is dumped by `dump_mock_transaction`, so the
+/// failure can be replayed in ckb-debugger.
+pub struct TestVerifier {
+    verifier: TransactionScriptsVerifier<DummyDataLoader>,
+    rtx: Arc<ResolvedTransaction>,
+    dummy: DummyDataLoader,
+}
+
+impl TestVerifier {
+    pub fn verify(&self, max_cycles: Cycle) -> Result<Cycle, ckb_error::Error> {
+        self.verifier.verify(max_cycles)
+    }
+}
+
+impl Drop for TestVerifier {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            match dump_mock_transaction(&self.rtx, &self.dummy) {
+                Ok(path) => eprintln!("Mock transaction written to {}", path.display()),
+                Err(e) => eprintln!("Cannot dump mock transaction: {}", e),
+            }
+        }
+    }
+}
+
+/// Resolves the transaction, adding zero lock and always success as cell
+/// deps. Set ZERO_LOCK_DEBUG to print debug output of scripts.
 pub fn complete_tx(
     mut dummy: DummyDataLoader,
     builder: TransactionBuilder,
     input_cells: Vec<CellMeta>,
-) -> (
-    TransactionScriptsVerifier<DummyDataLoader>,
-    Arc<ResolvedTransaction>,
-    DummyDataLoader,
-) {
+) -> (TestVerifier, Arc<ResolvedTransaction>, DummyDataLoader) {
     let rtx: Arc<ResolvedTransaction> = {
         let zero_lock_cell_meta = script_cell(&mut dummy, &ZERO_LOCK_BIN);
         let always_success_cell_meta = script_cell(&mut dummy, &ALWAYS_SUCCESS_BIN);
@@ -191,11 +473,28 @@ pub fn complete_tx(
             )
             .build();
 
+        let mut resolved_cell_deps = vec![];
+        let mut resolved_dep_groups = vec![];
+        for cell_dep in tx.cell_deps_iter() {
+            let cell_meta = resolve_cell(&dummy, &cell_dep.out_point());
+            if cell_dep.dep_type().as_slice()[0] == DepType::DepGroup as u8 {
+                let out_points =
+                    packed::OutPointVec::from_slice(cell_meta.mem_cell_data.as_ref().unwrap())
+                        .expect("dep group data");
+                for out_point in out_points.into_iter() {
+                    resolved_cell_deps.push(resolve_cell(&dummy, &out_point));
+                }
+                resolved_dep_groups.push(cell_meta);
+            } else {
+                resolved_cell_deps.push(cell_meta);
+            }
+        }
+
         Arc::new(ResolvedTransaction {
             transaction: tx,
             resolved_inputs: input_cells.clone(),
-            resolved_cell_deps: vec![zero_lock_cell_meta, always_success_cell_meta],
-            resolved_dep_groups: vec![],
+            resolved_cell_deps,
+            resolved_dep_groups,
         })
     };
 
@@ -239,33 +538,77 @@ pub fn complete_tx(
         }
     }
 
-    let verifier =
-        TransactionScriptsVerifier::new(rtx.clone(), dummy.clone(), consensus, tx_verify_env);
-    // Uncomment to debug tests:
-    // verifier.set_debug_printer(move |hash: &Byte32, message: &str| {
-    //     let prefix = match groups.get(hash) {
-    //         Some(text) => text.clone(),
-    //         None => format!("Script group: {:x}", hash),
-    //     };
-    //     eprintln!("{} DEBUG OUTPUT: {}", prefix, message);
-    // });
+    let verifier = if std::env::var("ZERO_LOCK_DEBUG").is_ok() {
+        TransactionScriptsVerifier::new_with_debug_printer(
+            rtx.clone(),
+            dummy.clone(),
+            consensus,
+            tx_verify_env,
+            Arc::new(move |hash: &Byte32, message: &str| {
+                let prefix = match groups.get(hash) {
+                    Some(text) => text.clone(),
+                    None => format!("Script group: {:x}", hash),
+                };
+                eprintln!("{} DEBUG OUTPUT: {}", prefix, message);
+            }),
+        )
+    } else {
+        TransactionScriptsVerifier::new(rtx.clone(), dummy.clone(), consensus, tx_verify_env)
+    };
+    let verifier = TestVerifier {
+        verifier,
+        rtx: rtx.clone(),
+        dummy: dummy.clone(),
+    };
     (verifier, rtx, dummy)
 }
 
-#[derive(Debug)]
-pub struct Blake2bHash;
+/// Encodes leaf options as (tag, length, value) records, options must be
+/// sorted by tags.
+pub fn leaf_options(options: &[(u8, Bytes)]) -> Bytes {
+    let mut data = vec![];
+    for (tag, value) in options {
+        data.push(*tag);
+        data.extend(TryInto::<u32>::try_into(value.len()).unwrap().to_le_bytes());
+        data.extend(value.as_ref());
+    }
+    Bytes::from(data)
+}
+
+/// Encodes a dep group option, each placeholder is a tuple of entry index in
+/// the dep group, index of the cell dep it refers to, hash kind, and the
+/// expected hash of the cell dep.
+pub fn dep_group_option(placeholders: &[(u32, u32, u8, Byte32)]) -> Bytes {
+    let mut data = vec![];
+    for (entry_index, cell_dep_index, kind, hash) in placeholders {
+        data.extend(entry_index.to_le_bytes());
+        data.extend(cell_dep_index.to_le_bytes());
+        data.push(*kind);
+        data.extend(hash.as_slice());
+    }
+    Bytes::from(data)
+}
 
-impl Merge for Blake2bHash {
-    type Item = Byte32;
+/// Encodes a required cell deps option, each cell dep is denoted by a hash
+/// kind and the expected hash.
+pub fn required_cell_deps_option(cell_deps: &[(u8, Byte32)]) -> Bytes {
+    let mut data = vec![];
+    for (kind, hash) in cell_deps {
+        data.push(*kind);
+        data.extend(hash.as_slice());
+    }
+    Bytes::from(data)
+}
 
-    fn merge(lhs: &Self::Item, rhs: &Self::Item) -> Self::Item {
-        let mut hasher = new_blake2b();
-        hasher.update(&lhs.as_bytes());
-        hasher.update(&rhs.as_bytes());
-        let mut hash = [0u8; 32];
-        hasher.finalize(&mut hash[..]);
-        Byte32::new(hash)
+/// Zeroes placeholder entries of dep group data, the result is what gets
+/// committed in the leaf.
+pub fn dep_group_template(data: &Bytes, entries: &[u32]) -> Bytes {
+    let mut data = data.to_vec();
+    for entry_index in entries {
+        let start = 4 + *entry_index as usize * 36;
+        data[start..start + 36].fill(0);
     }
+    Bytes::from(data)
 }
 
 pub fn hash_upgrade_data(
@@ -274,36 +617,55 @@ pub fn hash_upgrade_data(
     input_type: Option<Bytes>,
     output_type: Option<Bytes>,
 ) -> Byte32 {
-    let mut hasher = new_blake2b();
-    hasher.update(&[1u8]);
-    hasher.update(old_cell.out_point.as_slice());
-    hasher.update(&blake2b_256(new_cell.mem_cell_data.as_ref().unwrap())[..]);
-    hasher.update(new_cell.cell_output.as_slice());
-    if let Some(input_type) = input_type {
-        hasher.update(&[1u8]);
-        hasher.update(
-            &TryInto::<u32>::try_into(input_type.len())
-                .unwrap()
-                .to_le_bytes(),
-        );
-        hasher.update(&input_type);
-    } else {
-        hasher.update(&[0u8]);
-    }
-    if let Some(output_type) = output_type {
-        hasher.update(&[1u8]);
-        hasher.update(
-            &TryInto::<u32>::try_into(output_type.len())
-                .unwrap()
-                .to_le_bytes(),
-        );
-        hasher.update(&output_type);
-    } else {
-        hasher.update(&[0u8]);
-    }
-    let mut hash = [0u8; 32];
-    hasher.finalize(&mut hash[..]);
-    Byte32::new(hash)
+    let mut leaf = UpgradeLeaf::new(
+        old_cell.out_point.clone(),
+        new_cell.cell_output.clone(),
+        new_cell.mem_cell_data.as_ref().unwrap(),
+    );
+    leaf.input_type = input_type;
+    leaf.output_type = output_type;
+    leaf.hash()
+}
+
+/// Hashes an extended leaf, for a dep group option, new_cell should contain
+/// the template data built via dep_group_template.
+pub fn hash_extended_upgrade_data(
+    old_cell: &CellMeta,
+    new_cell: &CellMeta,
+    leaf_options: &Bytes,
+    input_type: Option<Bytes>,
+    output_type: Option<Bytes>,
+) -> Byte32 {
+    hash_versioned_upgrade_data(
+        EXTENDED_LEAF_VERSION,
+        old_cell,
+        new_cell,
+        leaf_options,
+        input_type,
+        output_type,
+    )
+}
+
+/// Hashes a leaf with leaf trailer, the leaf version also decides the hash
+/// function used.
+pub fn hash_versioned_upgrade_data(
+    leaf_version: u8,
+    old_cell: &CellMeta,
+    new_cell: &CellMeta,
+    leaf_options: &Bytes,
+    input_type: Option<Bytes>,
+    output_type: Option<Bytes>,
+) -> Byte32 {
+    let mut leaf = UpgradeLeaf::new(
+        old_cell.out_point.clone(),
+        new_cell.cell_output.clone(),
+        new_cell.mem_cell_data.as_ref().unwrap(),
+    );
+    leaf.leaf_version = leaf_version;
+    leaf.leaf_options = leaf_options.clone();
+    leaf.input_type = input_type;
+    leaf.output_type = output_type;
+    leaf.hash()
 }
 
 pub fn build_merkle_root_n_proof(
@@ -322,26 +684,94 @@ pub fn build_merkle_root_n_proof(
         };
         hashed_leaves.push(leaf);
     }
-    let tree: MerkleTree<Byte32, Blake2bHash> = CBMT::build_merkle_tree(&hashed_leaves);
-    let proof = tree.build_proof(&[selected]).expect("build merkle proof");
+    build_proof_witness(
+        &hashed_leaves,
+        selected,
+        &[header_index],
+        None,
+        input_type,
+        output_type,
+    )
+}
 
-    let mut data = vec![];
-    data.extend(header_index.to_le_bytes());
-    data.extend(
-        TryInto::<u32>::try_into(proof.indices().len())
-            .unwrap()
-            .to_le_bytes(),
-    );
-    for index in proof.indices() {
-        data.extend(index.to_le_bytes());
+/// Like build_merkle_root_n_proof, but the selected leaf is an extended leaf
+/// using the provided leaf options.
+pub fn build_extended_merkle_root_n_proof(
+    all_leaves: &[(&CellMeta, &CellMeta)],
+    selected: u32,
+    header_index: u32,
+    leaf_options: &Bytes,
+    input_type: Option<Bytes>,
+    output_type: Option<Bytes>,
+) -> (Byte32, Bytes) {
+    let mut hashed_leaves: Vec<Byte32> = Vec::with_capacity(all_leaves.len());
+    for (i, (old_cell, new_cell)) in all_leaves.iter().enumerate() {
+        let leaf = if i == selected as usize {
+            hash_extended_upgrade_data(
+                old_cell,
+                new_cell,
+                leaf_options,
+                input_type.clone(),
+                output_type.clone(),
+            )
+        } else {
+            hash_upgrade_data(old_cell, new_cell, None, None)
+        };
+        hashed_leaves.push(leaf);
     }
-    data.extend(
-        TryInto::<u32>::try_into(proof.lemmas().len())
-            .unwrap()
-            .to_le_bytes(),
-    );
-    for lemma in proof.lemmas() {
-        data.extend(lemma.as_slice());
+    build_proof_witness(
+        &hashed_leaves,
+        selected,
+        &[header_index],
+        Some(leaf_options),
+        input_type,
+        output_type,
+    )
+}
+
+/// Builds merkle root from hashed leaves, together with the witness proving
+/// the selected leaf. More than one header index is used when zero lock
+/// requires a header quorum. When leaf options are provided, a leaf trailer
+/// is appended to the merkle proof.
+pub fn build_proof_witness(
+    hashed_leaves: &[Byte32],
+    selected: u32,
+    header_indices: &[u32],
+    leaf_options: Option<&Bytes>,
+    input_type: Option<Bytes>,
+    output_type: Option<Bytes>,
+) -> (Byte32, Bytes) {
+    build_versioned_proof_witness(
+        EXTENDED_LEAF_VERSION,
+        hashed_leaves,
+        selected,
+        header_indices,
+        leaf_options,
+        input_type,
+        output_type,
+    )
+}
+
+/// Like build_proof_witness, when leaf options are provided, leaf version
+/// is used in the leaf trailer, and decides the hash function of the merkle
+/// tree.
+pub fn build_versioned_proof_witness(
+    leaf_version: u8,
+    hashed_leaves: &[Byte32],
+    selected: u32,
+    header_indices: &[u32],
+    leaf_options: Option<&Bytes>,
+    input_type: Option<Bytes>,
+    output_type: Option<Bytes>,
+) -> (Byte32, Bytes) {
+    let sha256 = leaf_options.is_some() && leaf_version == SHA256_LEAF_VERSION;
+    let tree = UpgradeTree::from_hashes(hashed_leaves.to_vec(), sha256);
+    let mut data = tree
+        .merkle_proof(selected, header_indices)
+        .expect("merkle proof")
+        .to_vec();
+    if let Some(leaf_options) = leaf_options {
+        data.extend_from_slice(&leaf_trailer(leaf_version, leaf_options));
     }
 
     let witness = WitnessArgs::new_builder()
@@ -367,7 +797,7 @@ pub fn bury_in_merkle_tree<R: Rng>(
     let other_entries: Vec<(CellMeta, CellMeta)> = (0..entries)
         .map(|_i| {
             let type_id = if rng.gen_bool(0.5) {
-                Some(random_type_id_script())
+                Some(random_type_id_script_with(rng))
             } else {
                 None
             };
@@ -387,13 +817,13 @@ pub fn bury_in_merkle_tree<R: Rng>(
     let mut leaves: Vec<(&CellMeta, &CellMeta)> =
         other_entries.iter().map(|(a, b)| (a, b)).collect();
     let index = rng.gen_range(0..leaves.len());
-    leaves.insert(index, (&input_meta, &output_meta));
+    leaves.insert(index, (input_meta, output_meta));
 
     build_merkle_root_n_proof(&leaves, index as u32, header_index, input_type, output_type)
 }
 
 pub fn header(dummy: &mut DummyDataLoader, merkle_root: &Byte32) -> Byte32 {
-    let mut rng = thread_rng();
+    let rng = &mut dummy.rng;
     let epoch_ext = EpochExt::new_builder()
         .number(10)
         .start_number(9500)
@@ -408,13 +838,22 @@ pub fn header(dummy: &mut DummyDataLoader, merkle_root: &Byte32) -> Byte32 {
             Byte32::new(d)
         })
         .build();
-    let mut extension = vec![0u8; 180];
-    rng.fill(&mut extension[..]);
-    extension[128..160].copy_from_slice(&merkle_root.as_bytes());
+    // Zero lock only owns the root, random payloads stand for other
+    // consumers of the extension field.
+    let mut before_root = vec![0u8; MERKLE_ROOT_OFFSET];
+    rng.fill(&mut before_root[..]);
+    let mut after_root = vec![0u8; 20];
+    rng.fill(&mut after_root[..]);
+    let extension = ExtensionBuilder::new()
+        .payload("before-root", 0, before_root.into())
+        .expect("payload before root")
+        .zero_lock_root(merkle_root)
+        .payload("after-root", MERKLE_ROOT_OFFSET + 32, after_root.into())
+        .expect("payload after root")
+        .build()
+        .expect("build extension");
     let hash = header.hash();
     dummy.headers.insert(hash.clone(), header);
-    dummy
-        .extensions
-        .insert(hash.clone(), Bytes::from(extension));
+    dummy.extensions.insert(hash.clone(), extension);
     hash
 }
@@ -0,0 +1,137 @@
+use super::indexer::{block, indexer, upgrade};
+use super::*;
+use ckb_types::core::BlockView;
+use ckb_zero_lock_sdk::{read_zero_lock_root, Auditor, Discrepancy, UpgradeIndexer, VerifyError};
+
+struct History {
+    indexer: UpgradeIndexer,
+    // Blocks carrying the root of each upgrade
+    root_blocks: Vec<BlockView>,
+}
+
+// Indexes two consecutive upgrades of the same cell, each under its own root
+fn history() -> History {
+    let mut dummy_loader = DummyDataLoader::default();
+    let zero_lock_dep = script_cell(&mut dummy_loader, &ZERO_LOCK_BIN);
+    let type_id = random_type_id_script();
+    let cells: Vec<CellMeta> = (1..=3u8)
+        .map(|i| {
+            zero_lock_cell(
+                &mut dummy_loader,
+                &vec![i; 100].into(),
+                Some(type_id.clone()),
+            )
+        })
+        .collect();
+    let (root_block1, tx1) = upgrade(&cells[0].out_point, &cells[1], 1, &zero_lock_dep);
+    let (root_block2, tx2) = upgrade(&OutPoint::new(tx1.hash(), 0), &cells[2], 3, &zero_lock_dep);
+
+    let mut indexer = indexer(&[&zero_lock_dep, &cells[0]]);
+    indexer.index_blocks(vec![
+        root_block1.clone(),
+        block(2, tx1),
+        root_block2.clone(),
+        block(4, tx2),
+    ]);
+    History {
+        indexer,
+        root_blocks: vec![root_block1, root_block2],
+    }
+}
+
+fn root(block: &BlockView) -> Byte32 {
+    read_zero_lock_root(&block.extension().unwrap().raw_data()).unwrap()
+}
+
+fn accept(auditor: Auditor, block: &BlockView) -> Auditor {
+    auditor.accepted_header(block.hash(), block.extension().unwrap().raw_data())
+}
+
+#[test]
+fn test_audit_clean_history() {
+    let History {
+        indexer,
+        root_blocks,
+    } = history();
+    let auditor = root_blocks.iter().fold(Auditor::new(), |auditor, block| {
+        accept(auditor, block).published_root(root(block))
+    });
+
+    let report = auditor.audit(&indexer);
+    assert_eq!(report.entries.len(), 2);
+    assert!(report.is_clean());
+    let text = report.to_string();
+    assert!(text.contains("With discrepancies: 0"));
+    for block in &root_blocks {
+        assert!(text.contains(&format!("Root {:#x}", root(block))));
+    }
+}
+
+#[test]
+fn test_audit_reports_discrepancies() {
+    let History {
+        indexer,
+        root_blocks,
+    } = history();
+    // The node accepted another root in the first block, never saw the
+    // second block, and only the second root is published
+    let other_root = Byte32::new([9u8; 32]);
+    let auditor = Auditor::new()
+        .accepted_header(
+            root_blocks[0].hash(),
+            ExtensionBuilder::new()
+                .zero_lock_root(&other_root)
+                .build()
+                .unwrap(),
+        )
+        .published_root(root(&root_blocks[1]));
+
+    let report = auditor.audit(&indexer);
+    assert!(!report.is_clean());
+    assert_eq!(report.discrepancies().count(), 2);
+    assert_eq!(
+        report.entries[0].discrepancies,
+        vec![
+            Discrepancy::RootMismatch {
+                header: root_blocks[0].hash(),
+                accepted_root: Some(other_root),
+                proof_root: root(&root_blocks[0]),
+            },
+            Discrepancy::UnpublishedRoot(root(&root_blocks[0])),
+        ]
+    );
+    assert_eq!(
+        report.entries[1].discrepancies,
+        vec![Discrepancy::UnacceptedHeader(root_blocks[1].hash())]
+    );
+    assert_eq!(report.to_string().matches("DISCREPANCY").count(), 3);
+}
+
+#[test]
+fn test_audit_with_accepted_extensions_only() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let zero_lock_dep = script_cell(&mut dummy_loader, &ZERO_LOCK_BIN);
+    let old_cell = zero_lock_cell(&mut dummy_loader, &vec![1u8; 100].into(), None);
+    let new_cell = zero_lock_cell(&mut dummy_loader, &vec![2u8; 100].into(), None);
+    let (root_block, tx) = upgrade(&old_cell.out_point, &new_cell, 1, &zero_lock_dep);
+
+    // The block carrying the root is not in the indexed blocks, accepted
+    // headers provide its extension
+    let auditor = accept(Auditor::new(), &root_block);
+    let mut verified = indexer(&[&zero_lock_dep, &old_cell]);
+    for (block_hash, extension) in auditor.accepted_extensions() {
+        verified.insert_extension(block_hash.clone(), extension.clone());
+    }
+    verified.index_block(&block(2, tx.clone()));
+    assert!(auditor.audit(&verified).is_clean());
+
+    let mut unverified = indexer(&[&zero_lock_dep, &old_cell]);
+    unverified.index_block(&block(2, tx));
+    let report = auditor.audit(&unverified);
+    assert_eq!(
+        report.entries[0].discrepancies,
+        vec![Discrepancy::VerificationFailed(
+            VerifyError::MissingExtension(0)
+        )]
+    );
+}
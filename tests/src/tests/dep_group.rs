@@ -0,0 +1,313 @@
+use super::*;
+
+struct DepGroupUpgrade {
+    dummy_loader: DummyDataLoader,
+    type_id: Script,
+    new_code: CellMeta,
+    input_cell_meta: CellMeta,
+    output_cell_meta: CellMeta,
+}
+
+// Upgrades a code cell with Type ID, then upgrades the dep group containing
+// the code cell and a library cell. The first entry of the new dep group
+// can only be known after the code cell upgrade transaction is committed.
+fn dep_group_upgrade() -> DepGroupUpgrade {
+    let mut dummy_loader = DummyDataLoader::default();
+    let type_id = random_type_id_script();
+    let old_code = zero_lock_cell(
+        &mut dummy_loader,
+        &vec![1u8; 100].into(),
+        Some(type_id.clone()),
+    );
+    let new_code = zero_lock_cell(
+        &mut dummy_loader,
+        &vec![2u8; 100].into(),
+        Some(type_id.clone()),
+    );
+    let library = script_cell(&mut dummy_loader, &vec![3u8; 100].into());
+
+    let old_dep_group_data =
+        dep_group_data(&[old_code.out_point.clone(), library.out_point.clone()]);
+    let input_cell_meta = zero_lock_cell(&mut dummy_loader, &old_dep_group_data, None);
+    let new_dep_group_data =
+        dep_group_data(&[new_code.out_point.clone(), library.out_point.clone()]);
+    let output_cell_meta = zero_lock_cell(&mut dummy_loader, &new_dep_group_data, None);
+
+    DepGroupUpgrade {
+        dummy_loader,
+        type_id,
+        new_code,
+        input_cell_meta,
+        output_cell_meta,
+    }
+}
+
+fn build_dep_group_proof(
+    upgrade: &DepGroupUpgrade,
+    placeholders: &[(u32, u32, u8, Byte32)],
+) -> (Byte32, Bytes) {
+    let entries: Vec<u32> = placeholders.iter().map(|p| p.0).collect();
+    let template_cell_meta = CellMetaBuilder::from_cell_output(
+        upgrade.output_cell_meta.cell_output.clone(),
+        dep_group_template(
+            upgrade.output_cell_meta.mem_cell_data.as_ref().unwrap(),
+            &entries,
+        ),
+    )
+    .build();
+    let options = leaf_options(&[(LEAF_OPTION_DEP_GROUP, dep_group_option(placeholders))]);
+
+    build_extended_merkle_root_n_proof(
+        &[(&upgrade.input_cell_meta, &template_cell_meta)],
+        0,
+        0,
+        &options,
+        None,
+        None,
+    )
+}
+
+#[test]
+fn test_dep_group_upgrade() {
+    let mut upgrade = dep_group_upgrade();
+    let (root, proof_witness) = build_dep_group_proof(
+        &upgrade,
+        &[(0, 0, CELL_DEP_TYPE_HASH, upgrade.type_id.calc_script_hash())],
+    );
+    let header_dep = header(&mut upgrade.dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(code_cell_dep(&upgrade.new_code))
+        .output(upgrade.output_cell_meta.cell_output.clone())
+        .output_data(
+            upgrade
+                .output_cell_meta
+                .mem_cell_data
+                .clone()
+                .unwrap()
+                .pack(),
+        )
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(upgrade.dummy_loader, builder, vec![upgrade.input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_dep_group_upgrade_by_data_hash() {
+    let mut upgrade = dep_group_upgrade();
+    let data_hash = CellOutput::calc_data_hash(upgrade.new_code.mem_cell_data.as_ref().unwrap());
+    let (root, proof_witness) =
+        build_dep_group_proof(&upgrade, &[(0, 0, CELL_DEP_DATA_HASH, data_hash)]);
+    let header_dep = header(&mut upgrade.dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(code_cell_dep(&upgrade.new_code))
+        .output(upgrade.output_cell_meta.cell_output.clone())
+        .output_data(
+            upgrade
+                .output_cell_meta
+                .mem_cell_data
+                .clone()
+                .unwrap()
+                .pack(),
+        )
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(upgrade.dummy_loader, builder, vec![upgrade.input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_dep_group_upgrade_with_dep_group_cell_deps() {
+    let mut upgrade = dep_group_upgrade();
+    let (root, proof_witness) = build_dep_group_proof(
+        &upgrade,
+        &[(0, 0, CELL_DEP_TYPE_HASH, upgrade.type_id.calc_script_hash())],
+    );
+    let header_dep = header(&mut upgrade.dummy_loader, &root);
+    let always_success = script_cell(&mut upgrade.dummy_loader, &ALWAYS_SUCCESS_BIN);
+    let dep_group = dep_group_cell(&mut upgrade.dummy_loader, &[&always_success]);
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(code_cell_dep(&upgrade.new_code))
+        .cell_dep(
+            CellDep::new_builder()
+                .out_point(dep_group.out_point.clone())
+                .dep_type(DepType::DepGroup.into())
+                .build(),
+        )
+        .output(upgrade.output_cell_meta.cell_output.clone())
+        .output_data(
+            upgrade
+                .output_cell_meta
+                .mem_cell_data
+                .clone()
+                .unwrap()
+                .pack(),
+        )
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(upgrade.dummy_loader, builder, vec![upgrade.input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_dep_group_upgrade_after_dep_group_cell_dep_fails_verification() {
+    let mut upgrade = dep_group_upgrade();
+    let (root, proof_witness) = build_dep_group_proof(
+        &upgrade,
+        &[(0, 1, CELL_DEP_TYPE_HASH, upgrade.type_id.calc_script_hash())],
+    );
+    let header_dep = header(&mut upgrade.dummy_loader, &root);
+    let always_success = script_cell(&mut upgrade.dummy_loader, &ALWAYS_SUCCESS_BIN);
+    let dep_group = dep_group_cell(&mut upgrade.dummy_loader, &[&always_success]);
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(
+            CellDep::new_builder()
+                .out_point(dep_group.out_point.clone())
+                .dep_type(DepType::DepGroup.into())
+                .build(),
+        )
+        .cell_dep(code_cell_dep(&upgrade.new_code))
+        .output(upgrade.output_cell_meta.cell_output.clone())
+        .output_data(
+            upgrade
+                .output_cell_meta
+                .mem_cell_data
+                .clone()
+                .unwrap()
+                .pack(),
+        )
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let lock = upgrade.input_cell_meta.cell_output.lock();
+    let verifier = complete_tx(upgrade.dummy_loader, builder, vec![upgrade.input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&lock, -61).input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_dep_group_upgrade_wrong_entry_fails_verification() {
+    let mut upgrade = dep_group_upgrade();
+    let (root, proof_witness) = build_dep_group_proof(
+        &upgrade,
+        &[(0, 0, CELL_DEP_TYPE_HASH, upgrade.type_id.calc_script_hash())],
+    );
+    let header_dep = header(&mut upgrade.dummy_loader, &root);
+    // Another cell using the same Type ID, which is not the cell listed in
+    // the new dep group.
+    let impostor = zero_lock_cell(
+        &mut upgrade.dummy_loader,
+        &vec![4u8; 100].into(),
+        Some(upgrade.type_id.clone()),
+    );
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(code_cell_dep(&impostor))
+        .output(upgrade.output_cell_meta.cell_output.clone())
+        .output_data(
+            upgrade
+                .output_cell_meta
+                .mem_cell_data
+                .clone()
+                .unwrap()
+                .pack(),
+        )
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let lock = upgrade.input_cell_meta.cell_output.lock();
+    let verifier = complete_tx(upgrade.dummy_loader, builder, vec![upgrade.input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&lock, -61).input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_dep_group_upgrade_wrong_hash_fails_verification() {
+    let mut upgrade = dep_group_upgrade();
+    let (root, proof_witness) = build_dep_group_proof(
+        &upgrade,
+        &[(
+            0,
+            0,
+            CELL_DEP_TYPE_HASH,
+            random_type_id_script().calc_script_hash(),
+        )],
+    );
+    let header_dep = header(&mut upgrade.dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(code_cell_dep(&upgrade.new_code))
+        .output(upgrade.output_cell_meta.cell_output.clone())
+        .output_data(
+            upgrade
+                .output_cell_meta
+                .mem_cell_data
+                .clone()
+                .unwrap()
+                .pack(),
+        )
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let lock = upgrade.input_cell_meta.cell_output.lock();
+    let verifier = complete_tx(upgrade.dummy_loader, builder, vec![upgrade.input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&lock, -61).input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_dep_group_upgrade_changed_fixed_entry_fails_verification() {
+    let mut upgrade = dep_group_upgrade();
+    let (root, proof_witness) = build_dep_group_proof(
+        &upgrade,
+        &[(0, 0, CELL_DEP_TYPE_HASH, upgrade.type_id.calc_script_hash())],
+    );
+    let header_dep = header(&mut upgrade.dummy_loader, &root);
+    // Library entry is committed in the leaf, and cannot be altered.
+    let other_library = script_cell(&mut upgrade.dummy_loader, &vec![5u8; 100].into());
+    let data = dep_group_data(&[
+        upgrade.new_code.out_point.clone(),
+        other_library.out_point.clone(),
+    ]);
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(code_cell_dep(&upgrade.new_code))
+        .output(upgrade.output_cell_meta.cell_output.clone())
+        .output_data(data.pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let lock = upgrade.input_cell_meta.cell_output.lock();
+    let verifier = complete_tx(upgrade.dummy_loader, builder, vec![upgrade.input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&lock, -61).input_lock_script(0),
+    );
+}
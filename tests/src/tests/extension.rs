@@ -0,0 +1,168 @@
+use super::*;
+use ckb_zero_lock_sdk::{
+    read_zero_lock_root, ExtensionError, RegionKind, DEFAULT_MAX_EXTENSION_SIZE,
+    ZERO_LOCK_ROOT_REGION,
+};
+
+fn shared_extension(root: &Byte32) -> ExtensionBuilder {
+    ExtensionBuilder::new()
+        .reserved("chain-root", 0..32)
+        .payload("other", 32, vec![7u8; 16].into())
+        .expect("payload")
+        .zero_lock_root(root)
+        .payload("after-root", MERKLE_ROOT_OFFSET + 32, vec![9u8; 4].into())
+        .expect("payload")
+}
+
+#[test]
+fn test_extension_layout_round_trip() {
+    let root = Byte32::new([3u8; 32]);
+    let builder = shared_extension(&root);
+    let extension = builder.build().expect("build extension");
+
+    assert_eq!(extension.len(), MERKLE_ROOT_OFFSET + 36);
+    assert_eq!(&extension[0..32], &[0u8; 32]);
+    assert_eq!(&extension[32..48], &[7u8; 16]);
+    assert_eq!(&extension[48..MERKLE_ROOT_OFFSET], &[0u8; 80]);
+    assert_eq!(read_zero_lock_root(&extension).unwrap(), root);
+
+    let regions = builder.parse(&extension).expect("parse extension");
+    let names: Vec<&str> = regions.iter().map(|(r, _)| r.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["chain-root", "other", ZERO_LOCK_ROOT_REGION, "after-root"]
+    );
+    let (root_region, root_data) = &regions[2];
+    assert_eq!(root_region.kind, RegionKind::ZeroLockRoot);
+    assert_eq!(root_data, &root.as_bytes());
+}
+
+#[test]
+fn test_extension_layout_errors() {
+    let root = Byte32::new([3u8; 32]);
+    assert_eq!(
+        ExtensionBuilder::new().build().unwrap_err(),
+        ExtensionError::MissingRoot
+    );
+    assert_eq!(
+        ExtensionBuilder::new()
+            .zero_lock_root(&root)
+            .zero_lock_root(&root)
+            .build()
+            .unwrap_err(),
+        ExtensionError::DuplicateRoot
+    );
+    assert_eq!(
+        ExtensionBuilder::new()
+            .zero_lock_root(&root)
+            .payload("other", MERKLE_ROOT_OFFSET - 1, vec![1u8; 2].into())
+            .expect("payload")
+            .build()
+            .unwrap_err(),
+        ExtensionError::Overlap {
+            first: "other".to_string(),
+            second: ZERO_LOCK_ROOT_REGION.to_string(),
+        }
+    );
+    assert_eq!(
+        ExtensionBuilder::new()
+            .payload("far", usize::MAX, vec![1u8; 2].into())
+            .unwrap_err(),
+        ExtensionError::RangeOverflow("far".to_string())
+    );
+    assert_eq!(
+        ExtensionBuilder::new()
+            .zero_lock_root(&root)
+            .reserved("nothing", 10..10)
+            .build()
+            .unwrap_err(),
+        ExtensionError::EmptyRegion("nothing".to_string())
+    );
+    assert_eq!(
+        shared_extension(&root).max_size(96).build().unwrap_err(),
+        ExtensionError::TooLarge {
+            size: MERKLE_ROOT_OFFSET + 36,
+            limit: 96,
+        }
+    );
+    shared_extension(&root)
+        .max_size(MERKLE_ROOT_OFFSET + 36)
+        .build()
+        .expect("fits in limit");
+
+    let extension = shared_extension(&root).build().unwrap();
+    assert_eq!(
+        shared_extension(&root)
+            .parse(&extension[..MERKLE_ROOT_OFFSET + 32])
+            .unwrap_err(),
+        ExtensionError::Truncated {
+            region: "after-root".to_string(),
+            size: MERKLE_ROOT_OFFSET + 32,
+        }
+    );
+    assert!(read_zero_lock_root(&extension[..MERKLE_ROOT_OFFSET + 31]).is_err());
+}
+
+#[test]
+fn test_extension_oversized_layouts() {
+    let root = Byte32::new([3u8; 32]);
+    let too_large = |size| ExtensionError::TooLarge {
+        size,
+        limit: DEFAULT_MAX_EXTENSION_SIZE,
+    };
+
+    // Rejected by the default limit, before anything is allocated
+    let reserved = ExtensionBuilder::new()
+        .zero_lock_root(&root)
+        .reserved("everything", MERKLE_ROOT_OFFSET + 32..usize::MAX);
+    assert_eq!(reserved.build().unwrap_err(), too_large(usize::MAX));
+    assert_eq!(
+        reserved.parse(&[0u8; 200]).unwrap_err(),
+        too_large(usize::MAX)
+    );
+
+    let offset = usize::MAX - 615;
+    let payload = ExtensionBuilder::new()
+        .zero_lock_root(&root)
+        .payload("far", offset, vec![0u8; 1].into())
+        .expect("payload");
+    assert_eq!(payload.build().unwrap_err(), too_large(offset + 1));
+
+    // An explicit limit replaces the default one
+    let extension = ExtensionBuilder::new()
+        .zero_lock_root(&root)
+        .reserved(
+            "large",
+            MERKLE_ROOT_OFFSET + 32..DEFAULT_MAX_EXTENSION_SIZE + 1,
+        )
+        .max_size(DEFAULT_MAX_EXTENSION_SIZE + 1)
+        .build()
+        .expect("fits in limit");
+    assert_eq!(extension.len(), DEFAULT_MAX_EXTENSION_SIZE + 1);
+}
+
+#[test]
+fn test_shared_extension_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let type_id = random_type_id_script();
+    let input_cell_meta = zero_lock_cell(
+        &mut dummy_loader,
+        &vec![1u8; 100].into(),
+        Some(type_id.clone()),
+    );
+    let output_cell_meta = zero_lock_cell(&mut dummy_loader, &vec![2u8; 100].into(), Some(type_id));
+    let (root, proof_witness) =
+        build_merkle_root_n_proof(&[(&input_cell_meta, &output_cell_meta)], 0, 0, None, None);
+    let header_dep = header(&mut dummy_loader, &Byte32::zero());
+    dummy_loader
+        .extensions
+        .insert(header_dep.clone(), shared_extension(&root).build().unwrap());
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+    verifier.verify(MAX_CYCLES).expect("pass verification");
+}
@@ -0,0 +1,140 @@
+use super::*;
+
+// Builds an upgrade transaction with `stale_headers` header deps carrying
+// unrelated roots, followed by one header dep carrying the actual root.
+fn discovery_tx(header_indices: &[u32], stale_headers: usize) -> (TestVerifier, CellMeta) {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) = contract_upgrade_cells(
+        &mut dummy_loader,
+        Bytes::from(vec![header_indices.len() as u8]),
+    );
+    let leaf = hash_upgrade_data(&input_cell_meta, &output_cell_meta, None, None);
+
+    let (root, proof_witness) = build_proof_witness(&[leaf], 0, header_indices, None, None, None);
+    let mut builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .witness(proof_witness.pack());
+    for _ in 0..stale_headers {
+        let stale_root = random_type_id_script().calc_script_hash();
+        builder = builder.header_dep(header(&mut dummy_loader, &stale_root));
+    }
+    builder = builder.header_dep(header(&mut dummy_loader, &root));
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+    (verifier, input_cell_meta)
+}
+
+#[test]
+fn test_header_discovery_upgrade() {
+    let (verifier, _) = discovery_tx(&[DISCOVER_HEADER_INDEX], 3);
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_header_discovery_with_quorum_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::from(vec![2]));
+    let leaf = hash_upgrade_data(&input_cell_meta, &output_cell_meta, None, None);
+
+    let (stale_root, _) =
+        build_proof_witness(&[leaf.clone(), Byte32::zero()], 0, &[0], None, None, None);
+    let (root, proof_witness) = build_proof_witness(
+        &[leaf],
+        0,
+        &[DISCOVER_HEADER_INDEX, DISCOVER_HEADER_INDEX],
+        None,
+        None,
+        None,
+    );
+    let header_dep = header(&mut dummy_loader, &root);
+    let header_dep2 = header(&mut dummy_loader, &stale_root);
+    let header_dep3 = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .header_dep(header_dep2)
+        .header_dep(header_dep3)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_header_discovery_no_matching_header_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::from(vec![1]));
+    let leaf = hash_upgrade_data(&input_cell_meta, &output_cell_meta, None, None);
+
+    let (stale_root, _) =
+        build_proof_witness(&[leaf.clone(), Byte32::zero()], 0, &[0], None, None, None);
+    let (_, proof_witness) =
+        build_proof_witness(&[leaf], 0, &[DISCOVER_HEADER_INDEX], None, None, None);
+    let header_dep = header(&mut dummy_loader, &stale_root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_header_discovery_mixed_with_header_index_fails_verification() {
+    let (verifier, input_cell_meta) = discovery_tx(&[DISCOVER_HEADER_INDEX, 0], 1);
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_header_discovery_beyond_bound_fails_verification() {
+    let (verifier, input_cell_meta) =
+        discovery_tx(&[DISCOVER_HEADER_INDEX], MAX_DISCOVERED_HEADER_DEPS);
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_header_discovery_cycles() {
+    for stale_headers in [0, 7, 31, MAX_DISCOVERED_HEADER_DEPS - 1] {
+        let (verifier, _) = discovery_tx(&[stale_headers as u32], stale_headers);
+        let designated_cycles = verifier.verify(MAX_CYCLES).expect("pass verification");
+        let (verifier, _) = discovery_tx(&[DISCOVER_HEADER_INDEX], stale_headers);
+        let discovery_cycles = verifier.verify(MAX_CYCLES).expect("pass verification");
+
+        println!(
+            "Header discovery with {} stale headers: {} cycles, designated header: {} cycles",
+            stale_headers, discovery_cycles, designated_cycles
+        );
+        // Each stale header costs one load_block_extension syscall.
+        assert!(discovery_cycles <= designated_cycles + (stale_headers as u64 + 1) * 10_000);
+    }
+}
@@ -0,0 +1,169 @@
+use super::*;
+
+#[test]
+fn test_header_quorum_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::from(vec![2]));
+    let leaf = hash_upgrade_data(&input_cell_meta, &output_cell_meta, None, None);
+
+    let (root, proof_witness) = build_proof_witness(&[leaf], 0, &[0, 1], None, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+    let header_dep2 = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .header_dep(header_dep2)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_header_quorum_with_stale_headers_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::from(vec![3]));
+    let leaf = hash_upgrade_data(&input_cell_meta, &output_cell_meta, None, None);
+
+    let (stale_root, _) =
+        build_proof_witness(&[leaf.clone(), Byte32::zero()], 0, &[0], None, None, None);
+    let (root, proof_witness) = build_proof_witness(&[leaf], 0, &[3, 1, 4], None, None, None);
+    let header_dep = header(&mut dummy_loader, &stale_root);
+    let header_dep2 = header(&mut dummy_loader, &root);
+    let header_dep3 = header(&mut dummy_loader, &stale_root);
+    let header_dep4 = header(&mut dummy_loader, &root);
+    let header_dep5 = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .header_dep(header_dep2)
+        .header_dep(header_dep3)
+        .header_dep(header_dep4)
+        .header_dep(header_dep5)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_header_quorum_including_stale_header_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::from(vec![2]));
+    let leaf = hash_upgrade_data(&input_cell_meta, &output_cell_meta, None, None);
+
+    let (stale_root, _) =
+        build_proof_witness(&[leaf.clone(), Byte32::zero()], 0, &[0], None, None, None);
+    let (root, proof_witness) = build_proof_witness(&[leaf], 0, &[0, 1], None, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+    let header_dep2 = header(&mut dummy_loader, &stale_root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .header_dep(header_dep2)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_header_quorum_repeated_header_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::from(vec![2]));
+    let leaf = hash_upgrade_data(&input_cell_meta, &output_cell_meta, None, None);
+
+    let (root, proof_witness) = build_proof_witness(&[leaf], 0, &[0, 0], None, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+    let header_dep2 = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .header_dep(header_dep2)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_header_quorum_too_few_headers_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::from(vec![2]));
+    let leaf = hash_upgrade_data(&input_cell_meta, &output_cell_meta, None, None);
+
+    let (root, proof_witness) = build_proof_witness(&[leaf], 0, &[0], None, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+    let header_dep2 = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .header_dep(header_dep2)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_zero_header_quorum_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::from(vec![0]));
+    let leaf = hash_upgrade_data(&input_cell_meta, &output_cell_meta, None, None);
+
+    let (root, proof_witness) = build_proof_witness(&[leaf], 0, &[0], None, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
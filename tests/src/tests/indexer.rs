@@ -0,0 +1,167 @@
+use super::*;
+use ckb_jsonrpc_types as json_types;
+use ckb_types::core::{BlockBuilder, BlockView, TransactionView};
+use ckb_zero_lock_sdk::{
+    load_blocks, read_zero_lock_root, UpgradeIndexer, UpgradeLeaf, UpgradeTree, VerifyError,
+};
+
+pub(super) fn indexer(cells: &[&CellMeta]) -> UpgradeIndexer {
+    let mut indexer = UpgradeIndexer::new(
+        CellOutput::calc_data_hash(&ZERO_LOCK_BIN),
+        ScriptHashType::Data2,
+    );
+    for cell in cells {
+        indexer.insert_cell(
+            cell.out_point.clone(),
+            cell.cell_output.clone(),
+            cell.mem_cell_data.clone().unwrap(),
+        );
+    }
+    indexer
+}
+
+pub(super) fn block(number: u64, tx: TransactionView) -> BlockView {
+    BlockBuilder::default()
+        .number(number.pack())
+        .transaction(tx)
+        .build()
+}
+
+// Returns the block carrying the root, and the transaction upgrading the
+// cell at `input` to `new_cell`
+pub(super) fn upgrade(
+    input: &OutPoint,
+    new_cell: &CellMeta,
+    number: u64,
+    zero_lock_dep: &CellMeta,
+) -> (BlockView, TransactionView) {
+    let leaf = UpgradeLeaf::new(
+        input.clone(),
+        new_cell.cell_output.clone(),
+        new_cell.mem_cell_data.as_ref().unwrap(),
+    );
+    let tree = UpgradeTree::from_hashes(vec![leaf.hash(), Byte32::zero()], false);
+    let extension = ExtensionBuilder::new()
+        .zero_lock_root(&tree.root())
+        .build()
+        .unwrap();
+    let root_block = BlockBuilder::default()
+        .number(number.pack())
+        .extension(Some(extension.pack()))
+        .build();
+    // The fee cell is unknown to the indexer
+    let tx = TransactionBuilder::default()
+        .input(CellInput::new(input.clone(), 0))
+        .input(CellInput::new(random_out_point(), 0))
+        .output(new_cell.cell_output.clone())
+        .output_data(new_cell.mem_cell_data.clone().unwrap().pack())
+        .cell_dep(code_cell_dep(zero_lock_dep))
+        .header_dep(root_block.hash())
+        .witness(
+            leaf.witness(tree.proof(0, &[0], &leaf).expect("proof"))
+                .as_bytes()
+                .pack(),
+        )
+        .build();
+    (root_block, tx)
+}
+
+#[test]
+fn test_indexer_upgrade_chain() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let zero_lock_dep = script_cell(&mut dummy_loader, &ZERO_LOCK_BIN);
+    let type_id = random_type_id_script();
+    let type_hash = type_id.calc_script_hash();
+    let cells: Vec<CellMeta> = (1..=3u8)
+        .map(|i| {
+            zero_lock_cell(
+                &mut dummy_loader,
+                &vec![i; 100].into(),
+                Some(type_id.clone()),
+            )
+        })
+        .collect();
+
+    let (root_block1, tx1) = upgrade(&cells[0].out_point, &cells[1], 1, &zero_lock_dep);
+    let second = OutPoint::new(tx1.hash(), 0);
+    let (root_block2, tx2) = upgrade(&second, &cells[2], 3, &zero_lock_dep);
+    let third = OutPoint::new(tx2.hash(), 0);
+    let blocks = vec![root_block1, block(2, tx1), root_block2, block(4, tx2)];
+
+    // Blocks go through the JSON format returned by RPC
+    let path =
+        std::env::temp_dir().join(format!("zero-lock-blocks-{}.json", rand::random::<u64>()));
+    let json: Vec<json_types::BlockView> = blocks.iter().cloned().map(Into::into).collect();
+    std::fs::write(&path, serde_json::to_string(&json).unwrap()).unwrap();
+    let loaded = load_blocks(&path);
+    std::fs::remove_file(&path).unwrap();
+    let loaded = loaded.expect("load blocks");
+    assert_eq!(
+        loaded.iter().map(|b| b.hash()).collect::<Vec<_>>(),
+        blocks.iter().map(|b| b.hash()).collect::<Vec<_>>()
+    );
+
+    let mut indexer = indexer(&[&zero_lock_dep, &cells[0]]);
+    assert_eq!(
+        indexer.live_cell_by_type(&type_hash),
+        Some(&cells[0].out_point)
+    );
+    indexer.index_blocks(loaded);
+    assert_eq!(indexer.records().len(), 2);
+    assert_eq!(indexer.live_cell_by_type(&type_hash), Some(&third));
+
+    let chain = indexer.upgrade_chain(&second);
+    assert_eq!(chain, indexer.upgrade_chain(&cells[0].out_point));
+    assert_eq!(chain, indexer.upgrade_chain(&third));
+    assert_eq!(chain.len(), 2);
+    for (record, (input, output, root_block, number)) in chain.iter().zip([
+        (&cells[0].out_point, &second, &blocks[0], 2),
+        (&second, &third, &blocks[2], 4),
+    ]) {
+        assert_eq!(&record.input_out_point, input);
+        assert_eq!(record.block_number, number);
+        let upgrade = record.upgrade.as_ref().expect("verified upgrade");
+        assert_eq!(&upgrade.output_out_point, output);
+        assert_eq!(upgrade.header_deps, vec![root_block.hash()]);
+        assert_eq!(
+            upgrade.verified.root,
+            read_zero_lock_root(&root_block.extension().unwrap().raw_data()).unwrap()
+        );
+        assert_eq!(upgrade.proof.header_indices, vec![0]);
+        assert_eq!(upgrade.proof.leaf_version, LEAF_VERSION);
+        assert_eq!(upgrade.proof.lemmas, vec![Byte32::zero()]);
+    }
+}
+
+#[test]
+fn test_indexer_records_unverifiable_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let zero_lock_dep = script_cell(&mut dummy_loader, &ZERO_LOCK_BIN);
+    let type_id = random_type_id_script();
+    let old_cell = zero_lock_cell(
+        &mut dummy_loader,
+        &vec![1u8; 100].into(),
+        Some(type_id.clone()),
+    );
+    let new_cell = zero_lock_cell(
+        &mut dummy_loader,
+        &vec![2u8; 100].into(),
+        Some(type_id.clone()),
+    );
+
+    // The block carrying the root is never indexed
+    let (_, tx) = upgrade(&old_cell.out_point, &new_cell, 1, &zero_lock_dep);
+    let output = OutPoint::new(tx.hash(), 0);
+    let mut indexer = indexer(&[&zero_lock_dep, &old_cell]);
+    indexer.index_block(&block(2, tx));
+
+    let records = indexer.records();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].upgrade, Err(VerifyError::MissingExtension(0)));
+    assert_eq!(indexer.upgrade_chain(&old_cell.out_point).len(), 1);
+    assert!(indexer.upgrade_chain(&output).is_empty());
+    assert_eq!(
+        indexer.live_cell_by_type(&type_id.calc_script_hash()),
+        Some(&output)
+    );
+}
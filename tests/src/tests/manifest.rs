@@ -0,0 +1,221 @@
+use super::*;
+use ckb_jsonrpc_types as json_types;
+use ckb_zero_lock_sdk::{Manifest, ManifestError, MANIFEST_VERSION};
+use serde_json::{json, Value};
+use std::path::Path;
+
+pub(super) fn manifest_entry(old_cell: &CellMeta, new_cell: &CellMeta) -> Value {
+    let output = &new_cell.cell_output;
+    let data_hash = CellOutput::calc_data_hash(new_cell.mem_cell_data.as_ref().unwrap());
+    let mut entry = json!({
+        "input_out_point": json_types::OutPoint::from(old_cell.out_point.clone()),
+        "capacity": json_types::Capacity::from(Unpack::<u64>::unpack(&output.capacity())),
+        "lock": json_types::Script::from(output.lock()),
+        "data_hash": format!("{:#x}", data_hash),
+    });
+    if let Some(type_script) = output.type_().to_opt() {
+        entry["type"] = json!(json_types::Script::from(type_script));
+    }
+    entry
+}
+
+pub(super) fn manifest(entries: Vec<Value>) -> Value {
+    json!({ "version": MANIFEST_VERSION, "upgrade": entries })
+}
+
+pub(super) fn from_json(manifest: &Value) -> Result<Manifest, ManifestError> {
+    Manifest::from_json_str(&manifest.to_string(), Path::new("."))
+}
+
+fn from_toml(manifest: &Value) -> Result<Manifest, ManifestError> {
+    Manifest::from_toml_str(&toml::to_string(manifest).unwrap(), Path::new("."))
+}
+
+pub(super) fn upgrade_cells(
+    dummy_loader: &mut DummyDataLoader,
+    n: u8,
+) -> Vec<(CellMeta, CellMeta)> {
+    (0..n)
+        .map(|i| {
+            let type_id = random_type_id_script();
+            let old_cell =
+                zero_lock_cell(dummy_loader, &vec![i; 100].into(), Some(type_id.clone()));
+            let new_cell = zero_lock_cell(dummy_loader, &vec![i + 10; 100].into(), Some(type_id));
+            (old_cell, new_cell)
+        })
+        .collect()
+}
+
+#[test]
+fn test_manifest_formats_and_order_agree() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 4);
+    let mut entries: Vec<Value> = cells
+        .iter()
+        .map(|(old_cell, new_cell)| manifest_entry(old_cell, new_cell))
+        .collect();
+
+    let json_manifest = from_json(&manifest(entries.clone())).expect("json manifest");
+    let toml_manifest = from_toml(&manifest(entries.clone())).expect("toml manifest");
+    entries.reverse();
+    let reversed_manifest = from_json(&manifest(entries)).expect("reversed manifest");
+
+    assert_eq!(json_manifest, toml_manifest);
+    assert_eq!(json_manifest, reversed_manifest);
+    assert_eq!(
+        json_manifest.content_hash(),
+        reversed_manifest.content_hash()
+    );
+    assert_eq!(json_manifest.tree().root(), reversed_manifest.tree().root());
+}
+
+#[test]
+fn test_manifest_content_hash_covers_entries() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 2);
+    let entries: Vec<Value> = cells
+        .iter()
+        .map(|(old_cell, new_cell)| manifest_entry(old_cell, new_cell))
+        .collect();
+    let full = from_json(&manifest(entries.clone())).unwrap();
+    let partial = from_json(&manifest(entries[..1].to_vec())).unwrap();
+    let mut changed_entries = entries;
+    changed_entries[0]["output_type"] = json!("0x01");
+    let changed = from_json(&manifest(changed_entries)).unwrap();
+
+    assert_ne!(full.content_hash(), partial.content_hash());
+    assert_ne!(full.content_hash(), changed.content_hash());
+}
+
+#[test]
+fn test_manifest_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let mut cells = upgrade_cells(&mut dummy_loader, 3);
+    let entries = cells
+        .iter()
+        .map(|(old_cell, new_cell)| manifest_entry(old_cell, new_cell))
+        .collect();
+    let manifest = from_json(&manifest(entries)).unwrap();
+    let tree = manifest.tree();
+    let header_dep = header(&mut dummy_loader, &tree.root());
+
+    let (input_cell_meta, output_cell_meta) = cells.swap_remove(1);
+    let index = manifest.position(&input_cell_meta.out_point).unwrap();
+    let leaf = &manifest.entries()[index as usize].leaf;
+    let witness = leaf.witness(tree.proof(index, &[0], leaf).expect("proof"));
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(witness.as_bytes().pack());
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+    verifier.verify(MAX_CYCLES).expect("pass verification");
+}
+
+#[test]
+fn test_manifest_validation_errors() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 1);
+    let entry = manifest_entry(&cells[0].0, &cells[0].1);
+
+    let mut wrong_version = manifest(vec![entry.clone()]);
+    wrong_version["version"] = json!(MANIFEST_VERSION + 1);
+    assert_eq!(
+        from_json(&wrong_version).unwrap_err(),
+        ManifestError::UnsupportedVersion(MANIFEST_VERSION + 1)
+    );
+    assert_eq!(
+        from_json(&manifest(vec![])).unwrap_err(),
+        ManifestError::Empty
+    );
+
+    let mut no_data = entry.clone();
+    no_data.as_object_mut().unwrap().remove("data_hash");
+    assert_eq!(
+        from_json(&manifest(vec![entry.clone(), no_data])).unwrap_err(),
+        ManifestError::MissingData { index: 1 }
+    );
+
+    let mut both = entry.clone();
+    both["data"] = json!("new_cell.bin");
+    assert_eq!(
+        from_json(&manifest(vec![both])).unwrap_err(),
+        ManifestError::DataAndDataHash { index: 0 }
+    );
+
+    let mut short_hash = entry.clone();
+    short_hash["data_hash"] = json!("0x0102");
+    assert_eq!(
+        from_json(&manifest(vec![short_hash])).unwrap_err(),
+        ManifestError::InvalidDataHash { index: 0 }
+    );
+
+    let mut bad_version = entry.clone();
+    bad_version["leaf_version"] = json!(9);
+    assert_eq!(
+        from_json(&manifest(vec![bad_version])).unwrap_err(),
+        ManifestError::UnsupportedLeafVersion {
+            index: 0,
+            leaf_version: 9
+        }
+    );
+
+    let mut options = entry.clone();
+    options["leaf_options"] = json!("0x0300000000");
+    assert_eq!(
+        from_json(&manifest(vec![options.clone()])).unwrap_err(),
+        ManifestError::UnexpectedLeafOptions { index: 0 }
+    );
+    options["leaf_version"] = json!(EXTENDED_LEAF_VERSION);
+    options["leaf_options"] = json!("0x0301");
+    assert_eq!(
+        from_json(&manifest(vec![options])).unwrap_err(),
+        ManifestError::InvalidLeafOptions { index: 0 }
+    );
+
+    let mut sha256 = entry.clone();
+    sha256["leaf_version"] = json!(SHA256_LEAF_VERSION);
+    assert_eq!(
+        from_json(&manifest(vec![entry.clone(), sha256])).unwrap_err(),
+        ManifestError::MixedHashes
+    );
+
+    let mut unknown_field = entry;
+    unknown_field["capacty"] = json!("0x0");
+    assert!(matches!(
+        from_json(&manifest(vec![unknown_field])).unwrap_err(),
+        ManifestError::Parse(_)
+    ));
+}
+
+#[test]
+fn test_manifest_reads_data_files() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 1);
+    let dir = std::env::temp_dir().join(format!("zero-lock-manifest-{}", rand::random::<u64>()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("new_cell.bin"),
+        cells[0].1.mem_cell_data.as_ref().unwrap(),
+    )
+    .unwrap();
+    let mut entry = manifest_entry(&cells[0].0, &cells[0].1);
+    let data_hash_entry = entry.clone();
+    entry.as_object_mut().unwrap().remove("data_hash");
+    entry["data"] = json!("new_cell.bin");
+    std::fs::write(
+        dir.join("manifest.toml"),
+        toml::to_string(&manifest(vec![entry])).unwrap(),
+    )
+    .unwrap();
+
+    let loaded = Manifest::load(&dir.join("manifest.toml")).expect("load manifest");
+    let hashed = from_json(&manifest(vec![data_hash_entry])).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        loaded.entries()[0].data.as_ref(),
+        cells[0].1.mem_cell_data.as_ref()
+    );
+    assert_eq!(loaded.content_hash(), hashed.content_hash());
+}
@@ -0,0 +1,126 @@
+use super::*;
+use ckb_zero_lock_sdk::{SemanticVersion, UpgradeMetadata};
+
+fn upgrade_metadata() -> UpgradeMetadata {
+    UpgradeMetadata {
+        hardfork: "meepo".to_string(),
+        contract: "zero-lock".to_string(),
+        version: SemanticVersion {
+            major: 1,
+            minor: 2,
+            patch: 3,
+        },
+        release_notes_hash: [7u8; 32],
+    }
+}
+
+fn verify_metadata_upgrade(
+    retained_fields: u8,
+    input_type: Option<Bytes>,
+    output_type: Option<Bytes>,
+) -> (Result<u64, ckb_error::Error>, Script) {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::new());
+
+    let options = leaf_options(&[(LEAF_OPTION_METADATA, Bytes::from(vec![retained_fields]))]);
+    let (root, proof_witness) = build_extended_merkle_root_n_proof(
+        &[(&input_cell_meta, &output_cell_meta)],
+        0,
+        0,
+        &options,
+        input_type,
+        output_type,
+    );
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let lock = input_cell_meta.cell_output.lock();
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+    (verifier.verify(MAX_CYCLES), lock)
+}
+
+#[test]
+fn test_metadata_upgrade() {
+    let (verify_result, _) = verify_metadata_upgrade(
+        METADATA_OUTPUT_TYPE,
+        None,
+        Some(upgrade_metadata().to_bytes()),
+    );
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_metadata_in_both_fields_upgrade() {
+    let mut previous = upgrade_metadata();
+    previous.version.major = 0;
+    let (verify_result, _) = verify_metadata_upgrade(
+        METADATA_INPUT_TYPE | METADATA_OUTPUT_TYPE,
+        Some(previous.to_bytes()),
+        Some(upgrade_metadata().to_bytes()),
+    );
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_unchecked_field_upgrade() {
+    let (verify_result, _) = verify_metadata_upgrade(
+        METADATA_OUTPUT_TYPE,
+        Some(vec![1u8; 10].into()),
+        Some(upgrade_metadata().to_bytes()),
+    );
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_malformed_metadata_fails_verification() {
+    let metadata = upgrade_metadata().to_bytes();
+    let (verify_result, lock) = verify_metadata_upgrade(
+        METADATA_OUTPUT_TYPE,
+        None,
+        Some(metadata.slice(0..metadata.len() - 1)),
+    );
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&lock, -61).input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_missing_metadata_fails_verification() {
+    let (verify_result, lock) = verify_metadata_upgrade(
+        METADATA_INPUT_TYPE,
+        None,
+        Some(upgrade_metadata().to_bytes()),
+    );
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&lock, -61).input_lock_script(0),
+    );
+}
+
+proptest! {
+    #[test]
+    fn test_metadata_round_trip(
+        hardfork in ".*",
+        contract in ".*",
+        major: u32,
+        minor: u32,
+        patch: u32,
+        release_notes_hash: [u8; 32],
+    ) {
+        let metadata = UpgradeMetadata {
+            hardfork,
+            contract,
+            version: SemanticVersion { major, minor, patch },
+            release_notes_hash,
+        };
+        let decoded = UpgradeMetadata::from_slice(&metadata.to_bytes());
+        prop_assert_eq!(decoded, Some(metadata));
+    }
+}
@@ -5,11 +5,33 @@ use ckb_types::{bytes::Bytes, core::TransactionBuilder, packed::WitnessArgs, pre
 use proptest::prelude::*;
 use rand::{rngs::StdRng, SeedableRng};
 
+mod audit;
+mod dep_group;
+mod extension;
+mod header_discovery;
+mod header_quorum;
+mod indexer;
+mod manifest;
+mod metadata;
+mod offline_verifier;
+mod proof_cell;
+mod proof_size;
+mod release;
+mod required_cell_deps;
+mod rust_witness_reader;
+mod sdk_tree;
+mod sha256_leaf;
+mod tree_file;
+mod tree_validation;
+mod upgrade_tx;
+mod witness_location;
+mod witness_visitor;
+
 const MAX_CYCLES: u64 = 60_000_000;
 
 #[test]
 fn test_zero_lock_exists() {
-    assert!(ZERO_LOCK_BIN.len() > 0);
+    assert!(!ZERO_LOCK_BIN.is_empty());
 }
 
 #[test]
@@ -298,6 +320,7 @@ proptest! {
         witness_extra_bytes in 1..409600usize,
         seed: u64,
     ) {
+        set_test_seed(seed);
         let mut dummy_loader = DummyDataLoader::default();
         let type_id = random_type_id_script();
         let old_contract = vec![1u8; 100].into();
@@ -335,6 +358,7 @@ proptest! {
         seed: u64,
         flip_bit in 0..256usize,
     ) {
+        set_test_seed(seed);
         let mut dummy_loader = DummyDataLoader::default();
         let type_id = random_type_id_script();
         let old_contract = vec![1u8; 100].into();
@@ -384,6 +408,7 @@ proptest! {
         entries in 1..30u32,
         seed: u64,
     ) {
+        set_test_seed(seed);
         let mut dummy_loader = DummyDataLoader::default();
         let type_id = random_type_id_script();
         let old_contract = vec![1u8; 100].into();
@@ -421,6 +446,7 @@ proptest! {
         seed: u64,
         flip_bit in 0..256usize,
     ) {
+        set_test_seed(seed);
         let mut dummy_loader = DummyDataLoader::default();
         let type_id = random_type_id_script();
         let old_contract = vec![1u8; 100].into();
@@ -468,6 +494,7 @@ proptest! {
         seed: u64,
         flip_bit: usize,
     ) {
+        set_test_seed(seed);
         let mut dummy_loader = DummyDataLoader::default();
         let type_id = random_type_id_script();
         let old_contract = vec![1u8; 100].into();
@@ -519,6 +546,7 @@ proptest! {
         seed: u64,
         truncated_bytes: usize,
     ) {
+        set_test_seed(seed);
         let mut dummy_loader = DummyDataLoader::default();
         let type_id = random_type_id_script();
         let old_contract = vec![1u8; 100].into();
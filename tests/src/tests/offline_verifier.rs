@@ -0,0 +1,373 @@
+use super::*;
+use ckb_types::core::{EpochNumberWithFraction, TransactionInfo};
+use ckb_zero_lock_sdk::{verify_mock_transaction, VerifiedUpgrade, VerifyError};
+
+// Verifies the transaction both on chain and offline, the offline verifier
+// must agree with zero lock on whether the upgrade passes.
+fn verify_both(
+    dummy_loader: DummyDataLoader,
+    builder: TransactionBuilder,
+    input_cells: Vec<CellMeta>,
+    input_index: usize,
+) -> Result<VerifiedUpgrade, VerifyError> {
+    let (verifier, rtx, dummy) = complete_tx(dummy_loader, builder, input_cells);
+    let verify_result = verifier.verify(MAX_CYCLES);
+    let offline_result = verify_mock_transaction(
+        &build_mock_transaction(&rtx, &dummy).expect("build mock transaction"),
+        input_index,
+    );
+    assert_eq!(
+        verify_result.is_ok(),
+        offline_result.is_ok(),
+        "on chain: {:?}, offline: {:?}",
+        verify_result,
+        offline_result
+    );
+    offline_result
+}
+
+#[test]
+fn test_offline_verifier_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::new());
+    let input_cell2 = always_success_cell(&mut dummy_loader, 150);
+
+    let leaf = hash_upgrade_data(&input_cell_meta, &output_cell_meta, None, None);
+    let (root, proof_witness) =
+        build_proof_witness(&[Byte32::zero(), leaf.clone()], 1, &[1], None, None, None);
+    let header_dep = header(&mut dummy_loader, &Byte32::zero());
+    let header_dep2 = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .header_dep(header_dep2)
+        .witness(Bytes::new().pack())
+        .witness(proof_witness.pack());
+
+    let verified = verify_both(dummy_loader, builder, vec![input_cell2, input_cell_meta], 1)
+        .expect("pass verification");
+    assert_eq!(
+        verified,
+        VerifiedUpgrade {
+            witness_index: 1,
+            output_index: 0,
+            leaf_version: LEAF_VERSION,
+            leaf,
+            root,
+            header_indices: vec![1],
+        }
+    );
+}
+
+#[test]
+fn test_offline_verifier_sha256_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::new());
+
+    let options = leaf_options(&[]);
+    let leaf = hash_versioned_upgrade_data(
+        SHA256_LEAF_VERSION,
+        &input_cell_meta,
+        &output_cell_meta,
+        &options,
+        None,
+        None,
+    );
+    let (root, proof_witness) = build_versioned_proof_witness(
+        SHA256_LEAF_VERSION,
+        &[leaf, Byte32::zero()],
+        0,
+        &[DISCOVER_HEADER_INDEX],
+        Some(&options),
+        None,
+        None,
+    );
+    let header_dep = header(&mut dummy_loader, &Byte32::zero());
+    let header_dep2 = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .header_dep(header_dep2)
+        .witness(proof_witness.pack());
+
+    let verified =
+        verify_both(dummy_loader, builder, vec![input_cell_meta], 0).expect("pass verification");
+    assert_eq!(verified.leaf_version, SHA256_LEAF_VERSION);
+    assert_eq!(verified.root, root);
+    assert_eq!(verified.header_indices, vec![1]);
+}
+
+#[test]
+fn test_offline_verifier_explains_root_mismatch() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::new());
+
+    let (root, proof_witness) =
+        build_merkle_root_n_proof(&[(&input_cell_meta, &output_cell_meta)], 0, 0, None, None);
+    let stale_root = Byte32::new([3u8; 32]);
+    let header_dep = header(&mut dummy_loader, &stale_root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let error = verify_both(dummy_loader, builder, vec![input_cell_meta], 0).unwrap_err();
+    assert_eq!(
+        error,
+        VerifyError::RootMismatch {
+            header_index: 0,
+            header_root: stale_root.clone(),
+            proof_root: root.clone(),
+        }
+    );
+    assert_eq!(error.code(), Some(8));
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "root in header dep 0 at offset 128 is {:#x}, proof root is {:#x}",
+            stale_root, root
+        )
+    );
+}
+
+#[test]
+fn test_offline_verifier_explains_quorum_failure() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::from(vec![2]));
+
+    let leaf = hash_upgrade_data(&input_cell_meta, &output_cell_meta, None, None);
+    let (root, proof_witness) = build_proof_witness(&[leaf], 0, &[0, 1], None, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+    let header_dep2 = header(&mut dummy_loader, &Byte32::zero());
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .header_dep(header_dep2)
+        .witness(proof_witness.pack());
+
+    let error = verify_both(dummy_loader, builder, vec![input_cell_meta], 0).unwrap_err();
+    assert_eq!(
+        error,
+        VerifyError::RootsDiffer {
+            header_index: 1,
+            root: Byte32::zero(),
+            expected: root,
+        }
+    );
+    assert_eq!(error.code(), Some(21));
+}
+
+#[test]
+fn test_offline_verifier_explains_missing_header() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::new());
+
+    let (_, proof_witness) =
+        build_merkle_root_n_proof(&[(&input_cell_meta, &output_cell_meta)], 0, 3, None, None);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .witness(proof_witness.pack());
+
+    let error = verify_both(dummy_loader, builder, vec![input_cell_meta], 0).unwrap_err();
+    assert_eq!(error, VerifyError::MissingExtension(3));
+    assert_eq!(error.code(), Some(6));
+}
+
+// Errors raised while building the leaf, in the middle of reading the
+// witness, are reported as is instead of as a witness error.
+#[test]
+fn test_offline_verifier_explains_missing_output() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::new());
+
+    let (root, proof_witness) =
+        build_merkle_root_n_proof(&[(&input_cell_meta, &output_cell_meta)], 0, 0, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let error = verify_both(dummy_loader, builder, vec![input_cell_meta], 0).unwrap_err();
+    assert_eq!(error, VerifyError::MissingOutput);
+    assert_eq!(error.code(), Some(4));
+}
+
+#[test]
+fn test_offline_verifier_explains_non_empty_group_witness() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let args = Bytes::from(vec![1, WITNESS_LOCATION_BEYOND_INPUTS]);
+    let (input_cell_meta, output_cell_meta) = contract_upgrade_cells(&mut dummy_loader, args);
+
+    let (root, proof_witness) =
+        build_merkle_root_n_proof(&[(&input_cell_meta, &output_cell_meta)], 0, 0, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(Bytes::from(vec![0xffu8; 10]).pack())
+        .witness(proof_witness.pack());
+
+    let error = verify_both(dummy_loader, builder, vec![input_cell_meta], 0).unwrap_err();
+    assert_eq!(error, VerifyError::GroupWitnessNotEmpty(0));
+    assert_eq!(error.code(), Some(26));
+}
+
+#[test]
+fn test_offline_verifier_explains_duplicate_outputs() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::new());
+
+    let (root, proof_witness) =
+        build_merkle_root_n_proof(&[(&input_cell_meta, &output_cell_meta)], 0, 0, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let lock_hash = input_cell_meta.cell_output.lock().calc_script_hash();
+    let error = verify_both(dummy_loader, builder, vec![input_cell_meta], 0).unwrap_err();
+    assert_eq!(
+        error,
+        VerifyError::MultipleOutputs {
+            lock_hash,
+            first: 0,
+            second: 1,
+        }
+    );
+}
+
+#[test]
+fn test_offline_verifier_explains_missing_metadata() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::new());
+
+    let options = leaf_options(&[(
+        LEAF_OPTION_METADATA,
+        Bytes::from(vec![METADATA_OUTPUT_TYPE]),
+    )]);
+    let (root, proof_witness) = build_extended_merkle_root_n_proof(
+        &[(&input_cell_meta, &output_cell_meta)],
+        0,
+        0,
+        &options,
+        None,
+        None,
+    );
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let error = verify_both(dummy_loader, builder, vec![input_cell_meta], 0).unwrap_err();
+    assert_eq!(error, VerifyError::InvalidMetadata("output_type"));
+    assert_eq!(error.code(), Some(24));
+}
+
+#[test]
+fn test_offline_verifier_dep_group_and_cell_headers() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (mut input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::new());
+    // Zero lock is also referenced through a dep group, as deployments do
+    let zero_lock = script_cell(&mut dummy_loader, &ZERO_LOCK_BIN);
+    let library = script_cell(&mut dummy_loader, &vec![3u8; 100].into());
+    let group = dep_group_cell(&mut dummy_loader, &[&zero_lock, &library]);
+    let block_hash = header(&mut dummy_loader, &Byte32::zero());
+    input_cell_meta.transaction_info = Some(TransactionInfo::new(
+        10000,
+        EpochNumberWithFraction::new(10, 500, 1010),
+        block_hash.clone(),
+        0,
+    ));
+
+    let (root, proof_witness) =
+        build_merkle_root_n_proof(&[(&input_cell_meta, &output_cell_meta)], 0, 0, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+    let builder = TransactionBuilder::default()
+        .cell_dep(
+            CellDep::new_builder()
+                .out_point(group.out_point.clone())
+                .dep_type(DepType::DepGroup.into())
+                .build(),
+        )
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep.clone())
+        .witness(proof_witness.pack());
+
+    let (verifier, rtx, dummy) = complete_tx(dummy_loader, builder, vec![input_cell_meta]);
+    verifier.verify(MAX_CYCLES).expect("pass verification");
+    let mock_tx = build_mock_transaction(&rtx, &dummy).expect("build mock transaction");
+    verify_mock_transaction(&mock_tx, 0).expect("pass offline verification");
+
+    let cell_deps = &mock_tx.mock_info.cell_deps;
+    assert_eq!(cell_deps[0].cell_dep.out_point(), group.out_point);
+    assert_eq!(
+        cell_deps[0].cell_dep.dep_type().as_slice()[0],
+        DepType::DepGroup as u8
+    );
+    assert_eq!(cell_deps[1].cell_dep.out_point(), zero_lock.out_point);
+    assert_eq!(cell_deps[2].cell_dep.out_point(), library.out_point);
+    assert_eq!(cell_deps.len(), 5);
+    assert_eq!(mock_tx.mock_info.inputs[0].header, Some(block_hash.clone()));
+    let header_hashes: Vec<Byte32> = mock_tx
+        .mock_info
+        .header_deps
+        .iter()
+        .map(|header| header.hash())
+        .collect();
+    assert_eq!(header_hashes, vec![header_dep, block_hash]);
+}
+
+#[test]
+fn test_offline_verifier_malformed_transaction() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let (input_cell_meta, output_cell_meta) =
+        contract_upgrade_cells(&mut dummy_loader, Bytes::new());
+
+    let (root, proof_witness) =
+        build_merkle_root_n_proof(&[(&input_cell_meta, &output_cell_meta)], 0, 0, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let (_, rtx, dummy) = complete_tx(dummy_loader, builder, vec![input_cell_meta]);
+    let mut mock_tx = build_mock_transaction(&rtx, &dummy).expect("build mock transaction");
+    mock_tx.mock_info.inputs.clear();
+    let error = verify_mock_transaction(&mock_tx, 0).unwrap_err();
+    assert!(matches!(error, VerifyError::MalformedTransaction(_)));
+    assert_eq!(error.code(), None);
+}
@@ -0,0 +1,172 @@
+use super::*;
+use ckb_zero_lock_sdk::{ProofCellBuilder, ProofPointer};
+
+fn witness_lock(witness: &Bytes) -> Bytes {
+    WitnessArgs::from_slice(witness)
+        .expect("witness args")
+        .lock()
+        .to_opt()
+        .expect("witness lock")
+        .raw_data()
+}
+
+fn pointer_witness(pointer: ProofPointer) -> Bytes {
+    WitnessArgs::new_builder()
+        .lock(Some(pointer.to_bytes()).pack())
+        .build()
+        .as_bytes()
+}
+
+fn proof_cell(dummy_loader: &mut DummyDataLoader, builder: &ProofCellBuilder) -> CellMeta {
+    let (output, data) = builder.build(Script::default());
+    let cell_meta = CellMetaBuilder::from_cell_output(output, data)
+        .out_point(random_out_point())
+        .build();
+    insert_cell(dummy_loader, &cell_meta);
+    cell_meta
+}
+
+#[test]
+fn test_proof_in_cell_dep_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let type_id = random_type_id_script();
+    let old_contract = vec![1u8; 100].into();
+    let input_cell_meta = zero_lock_cell(&mut dummy_loader, &old_contract, Some(type_id.clone()));
+    let new_contract = vec![2u8; 100].into();
+    let output_cell_meta = zero_lock_cell(&mut dummy_loader, &new_contract, Some(type_id));
+
+    let (root, proof_witness) =
+        build_merkle_root_n_proof(&[(&input_cell_meta, &output_cell_meta)], 0, 0, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+    let mut proof_cell_builder = ProofCellBuilder::default();
+    let index = proof_cell_builder.push(&witness_lock(&proof_witness));
+    let proof_cell_meta = proof_cell(&mut dummy_loader, &proof_cell_builder);
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(code_cell_dep(&proof_cell_meta))
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(pointer_witness(proof_cell_builder.pointer(index, 0)).pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_shared_proof_cell_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let mut cells = vec![];
+    for i in 0..3u8 {
+        let type_id = random_type_id_script();
+        let old_contract = vec![i; 100].into();
+        let input_cell_meta =
+            zero_lock_cell(&mut dummy_loader, &old_contract, Some(type_id.clone()));
+        let new_contract = vec![i + 10; 100].into();
+        let output_cell_meta = zero_lock_cell(&mut dummy_loader, &new_contract, Some(type_id));
+        cells.push((input_cell_meta, output_cell_meta));
+    }
+    let leaves: Vec<(&CellMeta, &CellMeta)> = cells.iter().map(|(a, b)| (a, b)).collect();
+
+    let mut root = Byte32::zero();
+    let mut proof_cell_builder = ProofCellBuilder::default();
+    for i in 0..leaves.len() {
+        let (r, proof_witness) = build_merkle_root_n_proof(&leaves, i as u32, 0, None, None);
+        root = r;
+        proof_cell_builder.push(&witness_lock(&proof_witness));
+    }
+    let header_dep = header(&mut dummy_loader, &root);
+    let proof_cell_meta = proof_cell(&mut dummy_loader, &proof_cell_builder);
+
+    for (i, (input_cell_meta, output_cell_meta)) in cells.into_iter().enumerate() {
+        let always_success = script_cell(&mut dummy_loader, &ALWAYS_SUCCESS_BIN);
+        let builder = TransactionBuilder::default()
+            .cell_dep(code_cell_dep(&always_success))
+            .cell_dep(code_cell_dep(&proof_cell_meta))
+            .output(output_cell_meta.cell_output.clone())
+            .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+            .header_dep(header_dep.clone())
+            .witness(pointer_witness(proof_cell_builder.pointer(i, 1)).pack());
+
+        let verifier = complete_tx(dummy_loader.clone(), builder, vec![input_cell_meta]).0;
+
+        let verify_result = verifier.verify(MAX_CYCLES);
+        verify_result.expect("pass verification");
+    }
+}
+
+#[test]
+fn test_proof_pointer_to_wrong_proof_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let type_id = random_type_id_script();
+    let old_contract = vec![1u8; 100].into();
+    let input_cell_meta = zero_lock_cell(&mut dummy_loader, &old_contract, Some(type_id.clone()));
+    let new_contract = vec![2u8; 100].into();
+    let output_cell_meta = zero_lock_cell(&mut dummy_loader, &new_contract, Some(type_id));
+    let other_cell_meta = zero_lock_cell(&mut dummy_loader, &vec![3u8; 100].into(), None);
+
+    let leaves = [
+        (&input_cell_meta, &output_cell_meta),
+        (&other_cell_meta, &other_cell_meta),
+    ];
+    let (root, proof_witness) = build_merkle_root_n_proof(&leaves, 0, 0, None, None);
+    let (_, other_proof_witness) = build_merkle_root_n_proof(&leaves, 1, 0, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+    let mut proof_cell_builder = ProofCellBuilder::default();
+    proof_cell_builder.push(&witness_lock(&proof_witness));
+    let other_index = proof_cell_builder.push(&witness_lock(&other_proof_witness));
+    let proof_cell_meta = proof_cell(&mut dummy_loader, &proof_cell_builder);
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(code_cell_dep(&proof_cell_meta))
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(pointer_witness(proof_cell_builder.pointer(other_index, 0)).pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_proof_pointer_beyond_cell_data_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let type_id = random_type_id_script();
+    let old_contract = vec![1u8; 100].into();
+    let input_cell_meta = zero_lock_cell(&mut dummy_loader, &old_contract, Some(type_id.clone()));
+    let new_contract = vec![2u8; 100].into();
+    let output_cell_meta = zero_lock_cell(&mut dummy_loader, &new_contract, Some(type_id));
+
+    let (root, proof_witness) =
+        build_merkle_root_n_proof(&[(&input_cell_meta, &output_cell_meta)], 0, 0, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+    let mut proof_cell_builder = ProofCellBuilder::default();
+    let index = proof_cell_builder.push(&witness_lock(&proof_witness));
+    let proof_cell_meta = proof_cell(&mut dummy_loader, &proof_cell_builder);
+    let mut pointer = proof_cell_builder.pointer(index, 0);
+    pointer.length += 1;
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(code_cell_dep(&proof_cell_meta))
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(pointer_witness(pointer).pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
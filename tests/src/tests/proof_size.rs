@@ -0,0 +1,112 @@
+use super::manifest::{from_json, manifest, manifest_entry, upgrade_cells};
+use super::*;
+use ckb_zero_lock_sdk::{
+    ProofCellBuilder, ProofSizeReport, UpgradeLeaf, UpgradeTree, DEFAULT_OUTLIER_FACTOR,
+    PROOF_POINTER_SIZE,
+};
+use serde_json::{json, Value};
+
+fn random_leaf() -> UpgradeLeaf {
+    UpgradeLeaf::new(random_out_point(), CellOutput::default(), &[])
+}
+
+#[test]
+fn test_proof_sizes_match_tree_proofs() {
+    for n in 1..=9 {
+        let leaves: Vec<UpgradeLeaf> = (0..n)
+            .map(|i| {
+                let mut leaf = random_leaf();
+                if i % 3 == 1 {
+                    leaf.leaf_version = EXTENDED_LEAF_VERSION;
+                    leaf.output_type = Some(vec![i as u8; 20].into());
+                }
+                leaf
+            })
+            .collect();
+        let tree = UpgradeTree::build(&leaves).unwrap();
+        let report = ProofSizeReport::new(&leaves, 2);
+        assert_eq!(report.leaves.len(), n);
+        for (i, leaf) in leaves.iter().enumerate() {
+            let proof = tree.proof(i as u32, &[0, 1], leaf).expect("proof");
+            let size = &report.leaves[i];
+            assert_eq!(size.proof_size, proof.len());
+            assert_eq!(size.witness_size, leaf.witness(proof).as_slice().len());
+        }
+        assert_eq!(
+            report.total_witness_size(),
+            report.leaves.iter().map(|l| l.witness_size).sum::<usize>()
+        );
+    }
+}
+
+#[test]
+fn test_proof_size_outliers() {
+    let mut leaves: Vec<UpgradeLeaf> = (0..5).map(|_| random_leaf()).collect();
+    leaves[3].input_type = Some(vec![0u8; 1000].into());
+    let report = ProofSizeReport::new(&leaves, 1);
+    let outliers: Vec<usize> = report
+        .outliers(DEFAULT_OUTLIER_FACTOR)
+        .map(|leaf| leaf.position)
+        .collect();
+    assert_eq!(outliers, vec![3]);
+    assert!(report.to_string().contains("Leaves: 5"));
+}
+
+#[test]
+fn test_manifest_batches_take_adjacent_shallow_leaves() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 6);
+    let mut entries: Vec<Value> = cells
+        .iter()
+        .map(|(old_cell, new_cell)| manifest_entry(old_cell, new_cell))
+        .collect();
+    entries[4]["batch"] = json!(1);
+    entries[1]["batch"] = json!(2);
+    entries[5]["batch"] = json!(2);
+    let manifest = from_json(&manifest(entries)).expect("manifest");
+
+    let inputs: Vec<OutPoint> = manifest
+        .entries()
+        .iter()
+        .map(|entry| entry.leaf.input_out_point.clone())
+        .collect();
+    assert_eq!(inputs[0], cells[4].0.out_point);
+    let mut second_batch = [cells[1].0.out_point.clone(), cells[5].0.out_point.clone()];
+    second_batch.sort_by_key(|out_point| out_point.tx_hash().raw_data());
+    assert_eq!(inputs[1..3], second_batch[..]);
+
+    let report = ProofSizeReport::from_manifest(&manifest, 1);
+    // In a tree of 6 leaves, the first two leaves have 2 lemmas
+    assert_eq!(report.leaves[0].lemmas, 2);
+    assert_eq!(report.leaves[2].lemmas, 3);
+    assert_eq!(report.batches.len(), 2);
+    assert_eq!(report.batches[0].positions, vec![0]);
+    assert_eq!(report.batches[0].proof_size, report.leaves[0].proof_size);
+    let batch = &report.batches[1];
+    assert_eq!(batch.positions, vec![1, 2]);
+    assert_eq!(batch.lemmas, 5);
+
+    // Each batch leaf is proven on its own, in its witness or in a shared
+    // proof cell
+    let leaves = manifest.leaves();
+    let tree = UpgradeTree::build(&leaves).unwrap();
+    let mut proof_cell = ProofCellBuilder::default();
+    let mut pointer_witness_size = 0;
+    for position in [1, 2] {
+        let proof = tree
+            .proof(position as u32, &[0], &leaves[position])
+            .expect("proof");
+        proof_cell.push(&proof);
+        pointer_witness_size += leaves[position]
+            .witness(vec![0; PROOF_POINTER_SIZE].into())
+            .as_slice()
+            .len();
+    }
+    assert_eq!(
+        batch.proof_size,
+        report.leaves[1].proof_size + report.leaves[2].proof_size
+    );
+    assert_eq!(batch.proof_cell_size, proof_cell.data().len());
+    assert_eq!(batch.pointer_witness_size, pointer_witness_size);
+    assert!(report.to_string().contains("Batch 2: 2 leaves"));
+}
@@ -0,0 +1,193 @@
+use super::*;
+
+fn target_lock() -> Script {
+    Script::new_builder()
+        .code_hash(CellOutput::calc_data_hash(&ALWAYS_SUCCESS_BIN))
+        .hash_type(ScriptHashType::Data2.into())
+        .args(random_out_point().as_bytes().pack())
+        .build()
+}
+
+// Builds a zero lock input cell, and an output cell using the same type
+// script and data, but locked by `lock`.
+fn release_cells(dummy_loader: &mut DummyDataLoader, lock: &Script) -> (CellMeta, CellMeta) {
+    let type_id = random_type_id_script();
+    let contract: Bytes = vec![1u8; 100].into();
+    let input_cell_meta = zero_lock_cell(dummy_loader, &contract, Some(type_id));
+    let output_cell_meta = CellMetaBuilder::from_cell_output(
+        input_cell_meta
+            .cell_output
+            .clone()
+            .as_builder()
+            .lock(lock.clone())
+            .build(),
+        contract,
+    )
+    .build();
+    (input_cell_meta, output_cell_meta)
+}
+
+fn release_options(lock: &Script) -> Bytes {
+    leaf_options(&[(LEAF_OPTION_RELEASE, lock.calc_script_hash().as_bytes())])
+}
+
+#[test]
+fn test_release_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let lock = target_lock();
+    let (input_cell_meta, output_cell_meta) = release_cells(&mut dummy_loader, &lock);
+
+    let (root, proof_witness) = build_extended_merkle_root_n_proof(
+        &[(&input_cell_meta, &output_cell_meta)],
+        0,
+        0,
+        &release_options(&lock),
+        None,
+        None,
+    );
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_release_with_regular_leaf_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let lock = target_lock();
+    let (input_cell_meta, output_cell_meta) = release_cells(&mut dummy_loader, &lock);
+
+    // A regular leaf always requires the output cell to stay in zero lock,
+    // even if the leaf commits to an output cell using another lock.
+    let (root, proof_witness) =
+        build_merkle_root_n_proof(&[(&input_cell_meta, &output_cell_meta)], 0, 0, None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_release_with_extended_leaf_without_release_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let lock = target_lock();
+    let (input_cell_meta, output_cell_meta) = release_cells(&mut dummy_loader, &lock);
+
+    let (root, proof_witness) = build_extended_merkle_root_n_proof(
+        &[(&input_cell_meta, &output_cell_meta)],
+        0,
+        0,
+        &leaf_options(&[]),
+        None,
+        None,
+    );
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_release_to_other_lock_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let lock = target_lock();
+    let (input_cell_meta, output_cell_meta) = release_cells(&mut dummy_loader, &lock);
+    let other_output = output_cell_meta
+        .cell_output
+        .clone()
+        .as_builder()
+        .lock(target_lock())
+        .build();
+
+    let (root, proof_witness) = build_extended_merkle_root_n_proof(
+        &[(&input_cell_meta, &output_cell_meta)],
+        0,
+        0,
+        &release_options(&lock),
+        None,
+        None,
+    );
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(other_output)
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_release_kept_in_zero_lock_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let lock = target_lock();
+    let (input_cell_meta, output_cell_meta) = release_cells(&mut dummy_loader, &lock);
+    let zero_lock_output = zero_lock_cell(&mut dummy_loader, &vec![2u8; 100].into(), None);
+
+    let (root, proof_witness) = build_extended_merkle_root_n_proof(
+        &[(&input_cell_meta, &output_cell_meta)],
+        0,
+        0,
+        &release_options(&lock),
+        None,
+        None,
+    );
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .output(zero_lock_output.cell_output.clone())
+        .output_data(zero_lock_output.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
@@ -0,0 +1,200 @@
+use super::*;
+
+#[test]
+fn test_required_cell_deps_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let type_id = random_type_id_script();
+    let old_contract = vec![1u8; 100].into();
+    let input_cell_meta = zero_lock_cell(&mut dummy_loader, &old_contract, Some(type_id.clone()));
+    let new_contract = vec![2u8; 100].into();
+    let output_cell_meta = zero_lock_cell(&mut dummy_loader, &new_contract, Some(type_id));
+    let library = script_cell(&mut dummy_loader, &vec![3u8; 100].into());
+
+    let options = leaf_options(&[(
+        LEAF_OPTION_REQUIRED_CELL_DEPS,
+        required_cell_deps_option(&[(
+            CELL_DEP_DATA_HASH,
+            CellOutput::calc_data_hash(library.mem_cell_data.as_ref().unwrap()),
+        )]),
+    )]);
+    let (root, proof_witness) = build_extended_merkle_root_n_proof(
+        &[(&input_cell_meta, &output_cell_meta)],
+        0,
+        0,
+        &options,
+        None,
+        None,
+    );
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(code_cell_dep(&library))
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_required_cell_deps_in_dep_group_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let type_id = random_type_id_script();
+    let old_contract = vec![1u8; 100].into();
+    let input_cell_meta = zero_lock_cell(&mut dummy_loader, &old_contract, Some(type_id.clone()));
+    let new_contract = vec![2u8; 100].into();
+    let output_cell_meta = zero_lock_cell(&mut dummy_loader, &new_contract, Some(type_id));
+    let library_type_id = random_type_id_script();
+    let library = zero_lock_cell(
+        &mut dummy_loader,
+        &vec![3u8; 100].into(),
+        Some(library_type_id.clone()),
+    );
+    let library2 = script_cell(&mut dummy_loader, &vec![4u8; 100].into());
+    let dep_group = dep_group_cell(&mut dummy_loader, &[&library2, &library]);
+
+    let options = leaf_options(&[(
+        LEAF_OPTION_REQUIRED_CELL_DEPS,
+        required_cell_deps_option(&[
+            (CELL_DEP_TYPE_HASH, library_type_id.calc_script_hash()),
+            (
+                CELL_DEP_DATA_HASH,
+                CellOutput::calc_data_hash(library2.mem_cell_data.as_ref().unwrap()),
+            ),
+        ]),
+    )]);
+    let (root, proof_witness) = build_extended_merkle_root_n_proof(
+        &[(&input_cell_meta, &output_cell_meta)],
+        0,
+        0,
+        &options,
+        None,
+        None,
+    );
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(
+            CellDep::new_builder()
+                .out_point(dep_group.out_point.clone())
+                .dep_type(DepType::DepGroup.into())
+                .build(),
+        )
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_missing_required_cell_dep_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let type_id = random_type_id_script();
+    let old_contract = vec![1u8; 100].into();
+    let input_cell_meta = zero_lock_cell(&mut dummy_loader, &old_contract, Some(type_id.clone()));
+    let new_contract = vec![2u8; 100].into();
+    let output_cell_meta = zero_lock_cell(&mut dummy_loader, &new_contract, Some(type_id));
+    let library = script_cell(&mut dummy_loader, &vec![3u8; 100].into());
+    let library2 = script_cell(&mut dummy_loader, &vec![4u8; 100].into());
+
+    let options = leaf_options(&[(
+        LEAF_OPTION_REQUIRED_CELL_DEPS,
+        required_cell_deps_option(&[
+            (
+                CELL_DEP_DATA_HASH,
+                CellOutput::calc_data_hash(library.mem_cell_data.as_ref().unwrap()),
+            ),
+            (
+                CELL_DEP_DATA_HASH,
+                CellOutput::calc_data_hash(library2.mem_cell_data.as_ref().unwrap()),
+            ),
+        ]),
+    )]);
+    let (root, proof_witness) = build_extended_merkle_root_n_proof(
+        &[(&input_cell_meta, &output_cell_meta)],
+        0,
+        0,
+        &options,
+        None,
+        None,
+    );
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(code_cell_dep(&library))
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_dropping_required_cell_deps_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let type_id = random_type_id_script();
+    let old_contract = vec![1u8; 100].into();
+    let input_cell_meta = zero_lock_cell(&mut dummy_loader, &old_contract, Some(type_id.clone()));
+    let new_contract = vec![2u8; 100].into();
+    let output_cell_meta = zero_lock_cell(&mut dummy_loader, &new_contract, Some(type_id));
+    let library = script_cell(&mut dummy_loader, &vec![3u8; 100].into());
+
+    let options = leaf_options(&[(
+        LEAF_OPTION_REQUIRED_CELL_DEPS,
+        required_cell_deps_option(&[(
+            CELL_DEP_DATA_HASH,
+            CellOutput::calc_data_hash(library.mem_cell_data.as_ref().unwrap()),
+        )]),
+    )]);
+    let (root, _) = build_extended_merkle_root_n_proof(
+        &[(&input_cell_meta, &output_cell_meta)],
+        0,
+        0,
+        &options,
+        None,
+        None,
+    );
+    // Required cell deps are committed in the leaf, a proof without them
+    // cannot be used even if the merkle proof itself is the same.
+    let (_, proof_witness) = build_extended_merkle_root_n_proof(
+        &[(&input_cell_meta, &output_cell_meta)],
+        0,
+        0,
+        &leaf_options(&[]),
+        None,
+        None,
+    );
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta.clone()]).0;
+
+    let verify_result = verifier.verify(MAX_CYCLES);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(0),
+    );
+}
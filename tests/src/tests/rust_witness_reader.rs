@@ -0,0 +1,177 @@
+use super::*;
+
+// Differential tests between zero lock built with the C WitnessArgs reader,
+// and zero lock built with the pure Rust reader. Zero lock is deployed in a
+// cell with a type script, and cells are locked via the type hash, so the
+// same transaction, containing the same leaf, can be verified by either
+// binary by swapping the content of the deployed cell.
+struct Differential {
+    dummy_loader: DummyDataLoader,
+    deployment: CellMeta,
+    input_cell_meta: CellMeta,
+    output_cell_meta: CellMeta,
+    lock: Script,
+}
+
+fn locked_cell(dummy_loader: &mut DummyDataLoader, lock: &Script, data: &Bytes) -> CellMeta {
+    let cell = CellOutput::new_builder()
+        .lock(lock.clone())
+        .capacity(Capacity::bytes(data.len()).expect("script capacity").pack())
+        .build();
+    let cell_meta = CellMetaBuilder::from_cell_output(cell, data.clone())
+        .out_point(random_out_point())
+        .build();
+    insert_cell(dummy_loader, &cell_meta);
+    cell_meta
+}
+
+fn differential() -> Differential {
+    let mut dummy_loader = DummyDataLoader::default();
+    let deployment_type = random_type_id_script();
+    let deployment = CellMetaBuilder::from_cell_output(
+        CellOutput::new_builder()
+            .type_(Some(deployment_type.clone()).pack())
+            .build(),
+        ZERO_LOCK_BIN.clone(),
+    )
+    .out_point(random_out_point())
+    .build();
+    insert_cell(&mut dummy_loader, &deployment);
+
+    let lock = Script::new_builder()
+        .code_hash(deployment_type.calc_script_hash())
+        .hash_type(ScriptHashType::Type.into())
+        .build();
+    let input_cell_meta = locked_cell(&mut dummy_loader, &lock, &vec![1u8; 100].into());
+    let output_cell_meta = locked_cell(&mut dummy_loader, &lock, &vec![2u8; 100].into());
+
+    Differential {
+        dummy_loader,
+        deployment,
+        input_cell_meta,
+        output_cell_meta,
+        lock,
+    }
+}
+
+impl Differential {
+    fn verify(
+        &self,
+        binary: &Bytes,
+        witness: &Bytes,
+        root: &Byte32,
+    ) -> Result<u64, ckb_error::Error> {
+        let mut dummy_loader = self.dummy_loader.clone();
+        let deployment =
+            CellMetaBuilder::from_cell_output(self.deployment.cell_output.clone(), binary.clone())
+                .out_point(self.deployment.out_point.clone())
+                .build();
+        insert_cell(&mut dummy_loader, &deployment);
+
+        let header_dep = header(&mut dummy_loader, root);
+
+        let builder = TransactionBuilder::default()
+            .cell_dep(code_cell_dep(&deployment))
+            .output(self.output_cell_meta.cell_output.clone())
+            .output_data(self.output_cell_meta.mem_cell_data.clone().unwrap().pack())
+            .header_dep(header_dep)
+            .witness(witness.pack());
+
+        let verifier = complete_tx(dummy_loader, builder, vec![self.input_cell_meta.clone()]).0;
+        verifier.verify(MAX_CYCLES)
+    }
+
+    // Both readers must arrive at the same result, when verification passes,
+    // both readers build the same leaf.
+    fn check(&self, root: &Byte32, witness: &Bytes, expect_success: bool) {
+        let c_result = self.verify(&ZERO_LOCK_BIN, witness, root);
+        let rust_result = self.verify(&ZERO_LOCK_RUST_READER_BIN, witness, root);
+        if expect_success {
+            c_result.expect("C reader passes verification");
+            rust_result.expect("Rust reader passes verification");
+        } else {
+            assert_error_eq!(
+                c_result.unwrap_err(),
+                ScriptError::validation_failure(&self.lock, -61).input_lock_script(0),
+            );
+            assert_error_eq!(
+                rust_result.unwrap_err(),
+                ScriptError::validation_failure(&self.lock, -61).input_lock_script(0),
+            );
+        }
+    }
+
+    fn proof(&self, input_type: Option<Bytes>, output_type: Option<Bytes>) -> (Byte32, Bytes) {
+        build_merkle_root_n_proof(
+            &[(&self.input_cell_meta, &self.output_cell_meta)],
+            0,
+            0,
+            input_type,
+            output_type,
+        )
+    }
+}
+
+#[test]
+fn test_readers_agree_on_remainder_fields() {
+    let differential = differential();
+    let cases: Vec<(Option<Bytes>, Option<Bytes>)> = vec![
+        (None, None),
+        (Some(vec![3u8; 10].into()), None),
+        (None, Some(vec![4u8; 10].into())),
+        (Some(Bytes::new()), Some(Bytes::new())),
+        (Some(vec![3u8; 10].into()), Some(vec![4u8; 20].into())),
+    ];
+    for (input_type, output_type) in cases {
+        let (root, witness) = differential.proof(input_type, output_type);
+        differential.check(&root, &witness, true);
+    }
+}
+
+#[test]
+fn test_readers_agree_on_partial_loading() {
+    let differential = differential();
+    // Fields larger than the reader buffers are loaded in multiple pieces
+    let (root, witness) = differential.proof(
+        Some(vec![5u8; 100_000].into()),
+        Some(vec![6u8; 32769].into()),
+    );
+    differential.check(&root, &witness, true);
+
+    // Merkle proof with many lemmas
+    let mut rng = StdRng::seed_from_u64(34);
+    let (root, witness) = bury_in_merkle_tree(
+        &differential.input_cell_meta,
+        &differential.output_cell_meta,
+        5000,
+        &mut rng,
+        0,
+        None,
+        None,
+    );
+    differential.check(&root, &witness, true);
+}
+
+#[test]
+fn test_readers_agree_on_malformed_witness() {
+    let differential = differential();
+    let (root, witness) = differential.proof(Some(vec![3u8; 10].into()), None);
+
+    let missing_lock = WitnessArgs::from_slice(&witness)
+        .unwrap()
+        .as_builder()
+        .lock(None::<Bytes>.pack())
+        .build()
+        .as_bytes();
+    let truncated = witness.slice(0..witness.len() - 1);
+    let mut extended = witness.to_vec();
+    extended.push(0);
+    // Total size of WitnessArgs is adjusted, but the last field is no longer
+    // a valid Bytes.
+    let total_size = extended.len() as u32;
+    extended[0..4].copy_from_slice(&total_size.to_le_bytes());
+
+    for malformed in [missing_lock, truncated, extended.into(), Bytes::new()] {
+        differential.check(&root, &malformed, false);
+    }
+}
@@ -0,0 +1,83 @@
+use super::*;
+use ckb_zero_lock_sdk::{UpgradeLeaf, UpgradeTree};
+
+fn upgrade_leaf(old_cell: &CellMeta, new_cell: &CellMeta) -> UpgradeLeaf {
+    UpgradeLeaf::new(
+        old_cell.out_point.clone(),
+        new_cell.cell_output.clone(),
+        new_cell.mem_cell_data.as_ref().unwrap(),
+    )
+}
+
+fn sdk_upgrade(leaf_version: u8) -> Result<u64, ckb_error::Error> {
+    let mut dummy_loader = DummyDataLoader::default();
+    let mut cells = vec![];
+    for i in 0..3u8 {
+        let type_id = random_type_id_script();
+        let old_cell = zero_lock_cell(
+            &mut dummy_loader,
+            &vec![i; 100].into(),
+            Some(type_id.clone()),
+        );
+        let new_cell = zero_lock_cell(&mut dummy_loader, &vec![i + 3; 100].into(), Some(type_id));
+        cells.push((old_cell, new_cell));
+    }
+    let leaves: Vec<UpgradeLeaf> = cells
+        .iter()
+        .map(|(old_cell, new_cell)| {
+            let mut leaf = upgrade_leaf(old_cell, new_cell);
+            leaf.leaf_version = leaf_version;
+            leaf.input_type = Some(vec![3u8; 10].into());
+            leaf.output_type = Some(vec![7u8; 20].into());
+            leaf
+        })
+        .collect();
+    let tree = UpgradeTree::build(&leaves).expect("build tree");
+    let header_dep = header(&mut dummy_loader, &tree.root());
+    let witness = leaves[1].witness(tree.proof(1, &[0], &leaves[1]).expect("proof"));
+
+    let (input_cell_meta, output_cell_meta) = cells.swap_remove(1);
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(witness.as_bytes().pack());
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+    verifier.verify(MAX_CYCLES)
+}
+
+#[test]
+fn test_sdk_tree_upgrade() {
+    sdk_upgrade(LEAF_VERSION).expect("pass verification");
+}
+
+#[test]
+fn test_sdk_tree_extended_leaf_upgrade() {
+    sdk_upgrade(EXTENDED_LEAF_VERSION).expect("pass verification");
+}
+
+#[test]
+fn test_sdk_tree_sha256_upgrade() {
+    sdk_upgrade(SHA256_LEAF_VERSION).expect("pass verification");
+}
+
+#[test]
+fn test_sdk_tree_rejects_mixed_hashes() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let old_cell = zero_lock_cell(&mut dummy_loader, &vec![1u8; 100].into(), None);
+    let new_cell = zero_lock_cell(&mut dummy_loader, &vec![2u8; 100].into(), None);
+    let leaf = upgrade_leaf(&old_cell, &new_cell);
+    let mut sha256_leaf = leaf.clone();
+    sha256_leaf.leaf_version = SHA256_LEAF_VERSION;
+
+    assert!(UpgradeTree::build(&[]).is_none());
+    assert!(UpgradeTree::build(&[leaf, sha256_leaf]).is_none());
+}
+
+#[test]
+fn test_sdk_tree_extension_carries_root() {
+    let tree = UpgradeTree::from_hashes(vec![Byte32::new([1u8; 32]), Byte32::zero()], false);
+    let extension = tree.extension();
+    assert_eq!(extension.len(), 160);
+    assert_eq!(&extension[128..], tree.root().as_slice());
+}
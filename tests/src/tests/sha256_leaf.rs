@@ -0,0 +1,89 @@
+use super::*;
+use merkle_cbt::{MerkleTree, CBMT};
+
+fn from_hex(hex: &str) -> Byte32 {
+    let mut data = [0u8; 32];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).expect("hex");
+    }
+    Byte32::new(data)
+}
+
+fn sha256_upgrade(leaf_version: u8, tree_version: u8) -> (Result<u64, ckb_error::Error>, Script) {
+    let mut dummy_loader = DummyDataLoader::default();
+    let type_id = random_type_id_script();
+    let old_contract = vec![1u8; 100].into();
+    let input_cell_meta = zero_lock_cell(&mut dummy_loader, &old_contract, Some(type_id.clone()));
+    let new_contract = vec![2u8; 100].into();
+    let output_cell_meta = zero_lock_cell(&mut dummy_loader, &new_contract, Some(type_id));
+
+    let options = leaf_options(&[]);
+    let leaf = hash_versioned_upgrade_data(
+        leaf_version,
+        &input_cell_meta,
+        &output_cell_meta,
+        &options,
+        None,
+        None,
+    );
+    let leaves = [
+        Byte32::zero(),
+        leaf,
+        random_type_id_script().calc_script_hash(),
+    ];
+    let (root, proof_witness) =
+        build_versioned_proof_witness(tree_version, &leaves, 1, &[0], Some(&options), None, None);
+    let header_dep = header(&mut dummy_loader, &root);
+
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(proof_witness.pack());
+
+    let lock = input_cell_meta.cell_output.lock();
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+    (verifier.verify(MAX_CYCLES), lock)
+}
+
+// Test vectors are generated with an independent SHA-256 implementation,
+// using leaves filled with bytes 0x00, 0x01, 0x02 and 0x03.
+#[test]
+fn test_sha256_merkle_tree_vectors() {
+    let leaves: Vec<Byte32> = (0..4u8).map(|i| Byte32::new([i; 32])).collect();
+
+    let tree: MerkleTree<Byte32, Sha256Merge> = CBMT::build_merkle_tree(&leaves[..2]);
+    assert_eq!(
+        tree.root(),
+        from_hex("5c85955f709283ecce2b74f1b1552918819f390911816e7bb466805a38ab87f3")
+    );
+    let tree: MerkleTree<Byte32, Sha256Merge> = CBMT::build_merkle_tree(&leaves);
+    assert_eq!(
+        tree.root(),
+        from_hex("d35f51699389da7eec7ce5eb02640c6d318cf51ae39eca890bbc7b84ecb5da68")
+    );
+}
+
+#[test]
+fn test_sha256_leaf_upgrade() {
+    let (verify_result, _) = sha256_upgrade(SHA256_LEAF_VERSION, SHA256_LEAF_VERSION);
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_sha256_leaf_in_blake2b_tree_fails_verification() {
+    let (verify_result, lock) = sha256_upgrade(SHA256_LEAF_VERSION, EXTENDED_LEAF_VERSION);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&lock, -61).input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_blake2b_leaf_in_sha256_tree_fails_verification() {
+    let (verify_result, lock) = sha256_upgrade(EXTENDED_LEAF_VERSION, SHA256_LEAF_VERSION);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&lock, -61).input_lock_script(0),
+    );
+}
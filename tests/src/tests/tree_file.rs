@@ -0,0 +1,116 @@
+use super::manifest::{from_json, manifest, manifest_entry, upgrade_cells};
+use super::*;
+use ckb_zero_lock_sdk::{TreeFile, TreeFileError, TREE_FILE_VERSION};
+use serde_json::{json, Value};
+
+fn tree_file(cells: &[(CellMeta, CellMeta)]) -> TreeFile {
+    let entries = cells
+        .iter()
+        .map(|(old_cell, new_cell)| {
+            let mut entry = manifest_entry(old_cell, new_cell);
+            entry["output_type"] = json!("0x0102");
+            entry
+        })
+        .collect();
+    TreeFile::from_manifest(&from_json(&manifest(entries)).unwrap())
+}
+
+fn tampered(tree_file: &TreeFile, tamper: impl FnOnce(&mut Value)) -> TreeFileError {
+    let mut value: Value = serde_json::from_str(&tree_file.to_json()).unwrap();
+    tamper(&mut value);
+    TreeFile::from_json_str(&value.to_string()).unwrap_err()
+}
+
+#[test]
+fn test_tree_file_round_trip_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let mut cells = upgrade_cells(&mut dummy_loader, 5);
+    let written = tree_file(&cells);
+    let path = std::env::temp_dir().join(format!("zero-lock-tree-{}.json", rand::random::<u64>()));
+    written.write(&path).expect("write tree file");
+    let loaded = TreeFile::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    let loaded = loaded.expect("load tree file");
+    assert_eq!(loaded, written);
+
+    let header_dep = header(&mut dummy_loader, &loaded.root());
+    let (input_cell_meta, output_cell_meta) = cells.swap_remove(3);
+    let index = loaded.position(&input_cell_meta.out_point).unwrap();
+    let builder = TransactionBuilder::default()
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(output_cell_meta.mem_cell_data.clone().unwrap().pack())
+        .header_dep(header_dep)
+        .witness(
+            loaded
+                .witness(index, &[0])
+                .expect("witness")
+                .as_bytes()
+                .pack(),
+        );
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+    verifier.verify(MAX_CYCLES).expect("pass verification");
+}
+
+#[test]
+fn test_tree_file_integrity_checks() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 3);
+    let tree_file = tree_file(&cells);
+
+    assert_eq!(
+        tampered(&tree_file, |v| v["version"] = json!(TREE_FILE_VERSION + 1)),
+        TreeFileError::UnsupportedVersion(TREE_FILE_VERSION + 1)
+    );
+    assert!(matches!(
+        tampered(&tree_file, |v| v["leaves"][1]["output_type"] =
+            json!("0x0103")),
+        TreeFileError::LeafHashMismatch { leaf: 1, .. }
+    ));
+    assert!(matches!(
+        tampered(&tree_file, |v| v["leaves"][0]["leaf_version"] = json!(9)),
+        TreeFileError::UnsupportedLeafVersion {
+            leaf: 0,
+            leaf_version: 9
+        }
+    ));
+    // Unknown leaf option tag
+    assert_eq!(
+        tampered(&tree_file, |v| {
+            v["leaves"][0]["leaf_version"] = json!(EXTENDED_LEAF_VERSION);
+            v["leaves"][0]["leaf_options"] = json!("0x0900000000");
+        }),
+        TreeFileError::InvalidLeafOptions { leaf: 0 }
+    );
+    // Swapping leaves keeps every leaf hash intact, but changes the root
+    assert!(matches!(
+        tampered(&tree_file, |v| {
+            let leaves = v["leaves"].as_array_mut().unwrap();
+            leaves.swap(0, 2);
+        }),
+        TreeFileError::RootMismatch { .. }
+    ));
+    assert!(matches!(
+        tampered(&tree_file, |v| v["manifest_hash"] = v["root"].clone()),
+        TreeFileError::ManifestHashMismatch { .. }
+    ));
+    assert!(matches!(
+        tampered(&tree_file, |v| v["leaves"] = json!([])),
+        TreeFileError::Empty
+    ));
+}
+
+#[test]
+fn test_tree_file_proof_out_of_range() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 3);
+    let tree_file = tree_file(&cells);
+
+    assert!(tree_file.proof(2, &[0]).is_some());
+    assert!(tree_file.proof(3, &[0]).is_none());
+    assert!(tree_file.witness(3, &[0]).is_none());
+    // The tree refuses a leaf other than the one at the index
+    let leaves = tree_file.leaves();
+    assert!(tree_file.tree().proof(0, &[0], &leaves[1]).is_none());
+    assert!(tree_file.tree().proof(3, &[0], &leaves[1]).is_none());
+    assert!(tree_file.tree().merkle_proof(3, &[0]).is_none());
+}
@@ -0,0 +1,253 @@
+use super::manifest::{from_json, manifest, manifest_entry, upgrade_cells};
+use super::*;
+use ckb_jsonrpc_types as json_types;
+use ckb_zero_lock_sdk::{Diagnostic, Manifest, Severity, TreeValidator};
+use serde_json::{json, Value};
+
+fn validator() -> TreeValidator {
+    TreeValidator::new(
+        CellOutput::calc_data_hash(&ZERO_LOCK_BIN),
+        ScriptHashType::Data2,
+    )
+}
+
+fn errors(diagnostics: &[Diagnostic]) -> Vec<Diagnostic> {
+    diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+        .cloned()
+        .collect()
+}
+
+// Writes new cell data next to the manifest, so capacity can be checked
+fn manifest_with_data(cells: &[(CellMeta, CellMeta)], entries: Vec<Value>) -> Manifest {
+    let dir = std::env::temp_dir().join(format!("zero-lock-validation-{}", rand::random::<u64>()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let entries = entries
+        .into_iter()
+        .zip(cells)
+        .enumerate()
+        .map(|(i, (mut entry, (_, new_cell)))| {
+            let name = format!("{}.bin", i);
+            std::fs::write(dir.join(&name), new_cell.mem_cell_data.as_ref().unwrap()).unwrap();
+            entry.as_object_mut().unwrap().remove("data_hash");
+            entry["data"] = json!(name);
+            entry
+        })
+        .collect();
+    let manifest = Manifest::from_json_str(&manifest(entries).to_string(), &dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+    manifest.expect("manifest")
+}
+
+// Cells built by zero_lock_cell only hold enough capacity for the data, new
+// cells here get capacity for the whole cell.
+fn entries(cells: &[(CellMeta, CellMeta)]) -> Vec<Value> {
+    cells
+        .iter()
+        .map(|(old_cell, new_cell)| {
+            let mut entry = manifest_entry(old_cell, new_cell);
+            entry["capacity"] = json!(json_types::Capacity::from(1000 * 100_000_000u64));
+            entry
+        })
+        .collect()
+}
+
+#[test]
+fn test_valid_manifest_has_no_diagnostics() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 3);
+    let manifest = manifest_with_data(&cells, entries(&cells));
+    let validator = cells.iter().fold(validator(), |validator, (old_cell, _)| {
+        validator.input_cell(old_cell.out_point.clone(), old_cell.cell_output.clone())
+    });
+
+    let (tree, warnings) = validator.build_tree(&manifest).expect("build tree");
+    assert!(warnings.is_empty());
+    assert_eq!(tree.root(), manifest.tree().root());
+}
+
+#[test]
+fn test_data_hash_only_skips_capacity_check() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 1);
+    let manifest = from_json(&manifest(entries(&cells))).unwrap();
+
+    let (_, warnings) = validator().build_tree(&manifest).expect("build tree");
+    assert_eq!(warnings, vec![Diagnostic::CapacityUnchecked { leaf: 0 }]);
+}
+
+#[test]
+fn test_duplicate_input_and_type_id() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 2);
+    let mut entries = entries(&cells);
+    entries[1] = entries[0].clone();
+    entries[1]["output_type"] = json!("0x01");
+    let manifest = from_json(&manifest(entries)).unwrap();
+
+    let diagnostics = errors(&validator().validate(&manifest));
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(
+        diagnostics[0],
+        Diagnostic::DuplicateInput {
+            out_point: cells[0].0.out_point.clone(),
+            leaves: vec![0, 1],
+        }
+    );
+    assert!(matches!(
+        &diagnostics[1],
+        Diagnostic::DuplicateTypeId { leaves, .. } if leaves == &vec![0, 1]
+    ));
+    assert!(validator().build_tree(&manifest).is_err());
+}
+
+#[test]
+fn test_insufficient_capacity() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 1);
+    let mut entries = entries(&cells);
+    entries[0]["capacity"] = json!(json_types::Capacity::from(100_000_000u64));
+    let manifest = manifest_with_data(&cells, entries);
+
+    assert!(matches!(
+        errors(&validator().validate(&manifest))[..],
+        [Diagnostic::InsufficientCapacity {
+            leaf: 0,
+            capacity: 100_000_000,
+            ..
+        }]
+    ));
+}
+
+#[test]
+fn test_lock_checks() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 1);
+    let other_lock = random_type_id_script();
+    let mut not_zero_locked = entries(&cells);
+    not_zero_locked[0]["lock"] = json!(json_types::Script::from(other_lock));
+    let not_zero_locked = manifest_with_data(&cells, not_zero_locked);
+    assert_eq!(
+        errors(&validator().validate(&not_zero_locked)),
+        vec![Diagnostic::NotZeroLocked { leaf: 0 }]
+    );
+
+    // Zero lock with different args is still zero lock, but cannot be found
+    // as the continuing output
+    let changed_args = cells[0]
+        .1
+        .cell_output
+        .lock()
+        .as_builder()
+        .args(Bytes::from(vec![1u8]).pack())
+        .build();
+    let mut lock_changed = entries(&cells);
+    lock_changed[0]["lock"] = json!(json_types::Script::from(changed_args));
+    let lock_changed = manifest_with_data(&cells, lock_changed);
+    assert!(errors(&validator().validate(&lock_changed)).is_empty());
+    let validator =
+        validator().input_cell(cells[0].0.out_point.clone(), cells[0].0.cell_output.clone());
+    assert_eq!(
+        errors(&validator.validate(&lock_changed)),
+        vec![Diagnostic::LockChanged { leaf: 0 }]
+    );
+}
+
+#[test]
+fn test_release_lock_checks() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 1);
+    let release_lock = random_type_id_script();
+    let options = leaf_options(&[(
+        LEAF_OPTION_RELEASE,
+        release_lock.calc_script_hash().as_bytes(),
+    )]);
+    let mut released = entries(&cells);
+    released[0]["leaf_version"] = json!(EXTENDED_LEAF_VERSION);
+    released[0]["leaf_options"] = json!(json_types::JsonBytes::from_bytes(options));
+
+    let still_zero_locked = manifest_with_data(&cells, released.clone());
+    assert_eq!(
+        errors(&validator().validate(&still_zero_locked)),
+        vec![Diagnostic::ReleasedIntoZeroLock { leaf: 0 }]
+    );
+
+    let other_lock = random_type_id_script();
+    released[0]["lock"] = json!(json_types::Script::from(other_lock.clone()));
+    let mismatch = manifest_with_data(&cells, released.clone());
+    assert_eq!(
+        errors(&validator().validate(&mismatch)),
+        vec![Diagnostic::ReleaseLockMismatch {
+            leaf: 0,
+            expected: release_lock.calc_script_hash(),
+            actual: other_lock.calc_script_hash(),
+        }]
+    );
+
+    released[0]["lock"] = json!(json_types::Script::from(release_lock));
+    let valid = manifest_with_data(&cells, released);
+    assert!(validator().validate(&valid).is_empty());
+}
+
+#[test]
+fn test_type_id_continuity() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 1);
+    let with_input =
+        || validator().input_cell(cells[0].0.out_point.clone(), cells[0].0.cell_output.clone());
+
+    let mut changed = entries(&cells);
+    changed[0]["type"] = json!(json_types::Script::from(random_type_id_script()));
+    let changed = manifest_with_data(&cells, changed);
+    assert_eq!(
+        with_input().validate(&changed),
+        vec![Diagnostic::TypeIdChanged { leaf: 0 }]
+    );
+
+    let mut dropped = entries(&cells);
+    dropped[0].as_object_mut().unwrap().remove("type");
+    let dropped = manifest_with_data(&cells, dropped);
+    assert_eq!(
+        with_input().validate(&dropped),
+        vec![Diagnostic::TypeIdChanged { leaf: 0 }]
+    );
+
+    let untyped = zero_lock_cell(&mut dummy_loader, &vec![3u8; 100].into(), None);
+    let introduced =
+        with_input().input_cell(untyped.out_point.clone(), untyped.cell_output.clone());
+    let mut entries = entries(&cells);
+    entries[0]["input_out_point"] = json!(json_types::OutPoint::from(untyped.out_point.clone()));
+    let manifest = manifest_with_data(&cells, entries);
+    let (_, warnings) = introduced.build_tree(&manifest).expect("build tree");
+    assert_eq!(warnings, vec![Diagnostic::TypeIdIntroduced { leaf: 0 }]);
+}
+
+#[test]
+fn test_oversized_witness_estimate() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let cells = upgrade_cells(&mut dummy_loader, 4);
+    let mut entries = entries(&cells);
+    for entry in &mut entries {
+        entry["output_type"] = json!(json_types::JsonBytes::from_vec(vec![0u8; 200]));
+    }
+    let manifest = manifest_with_data(&cells, entries);
+
+    // Estimates are upper bounds of the real witness
+    let tree = manifest.tree();
+    for (i, entry) in manifest.entries().iter().enumerate() {
+        let witness = entry
+            .leaf
+            .witness(tree.proof(i as u32, &[0], &entry.leaf).expect("proof"));
+        assert!(validator().estimate_witness_size(entry, 4) >= witness.as_slice().len());
+    }
+
+    let (_, warnings) = validator()
+        .max_witness_size(200)
+        .build_tree(&manifest)
+        .expect("build tree");
+    assert_eq!(warnings.len(), 4);
+    assert!(warnings
+        .iter()
+        .all(|warning| matches!(warning, Diagnostic::OversizedWitness { limit: 200, .. })));
+}
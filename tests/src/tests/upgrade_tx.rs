@@ -0,0 +1,315 @@
+use super::*;
+use ckb_types::core::TransactionView;
+use ckb_zero_lock_sdk::{
+    FeeCell, UpgradeLeaf, UpgradeTree, UpgradeTxBuilder, UpgradeTxError, DEFAULT_FEE_RATE,
+};
+
+struct Upgrade {
+    dummy_loader: DummyDataLoader,
+    input_cell_meta: CellMeta,
+    builder: UpgradeTxBuilder,
+}
+
+fn upgrade() -> Upgrade {
+    upgrade_with_args(Bytes::new())
+}
+
+fn upgrade_with_args(args: Bytes) -> Upgrade {
+    let mut dummy_loader = DummyDataLoader::default();
+    let type_id = random_type_id_script();
+    let input_cell_meta = zero_lock_cell_with_args(
+        &mut dummy_loader,
+        &vec![1u8; 100].into(),
+        Some(type_id.clone()),
+        args.clone(),
+    );
+    let output_cell_meta = zero_lock_cell_with_args(
+        &mut dummy_loader,
+        &vec![2u8; 100].into(),
+        Some(type_id),
+        args,
+    );
+    let data = output_cell_meta.mem_cell_data.clone().unwrap();
+    let leaf = UpgradeLeaf::new(
+        input_cell_meta.out_point.clone(),
+        output_cell_meta.cell_output.clone(),
+        &data,
+    );
+    let tree = UpgradeTree::build(&[leaf.clone()]).expect("build tree");
+    let header_dep = header(&mut dummy_loader, &tree.root());
+    let builder = UpgradeTxBuilder::new(
+        leaf.clone(),
+        &input_cell_meta.cell_output,
+        data,
+        tree.proof(0, &[0], &leaf).expect("proof"),
+        header_dep,
+    );
+    Upgrade {
+        dummy_loader,
+        input_cell_meta,
+        builder,
+    }
+}
+
+fn fee_cell(dummy_loader: &mut DummyDataLoader, capacity_bytes: usize) -> (CellMeta, FeeCell) {
+    let cell_meta = always_success_cell(dummy_loader, capacity_bytes);
+    let fee_cell = FeeCell {
+        out_point: cell_meta.out_point.clone(),
+        output: cell_meta.cell_output.clone(),
+        witness_lock_size: Some(65),
+    };
+    (cell_meta, fee_cell)
+}
+
+// Zero lock and always success deps are added by complete_tx, together with
+// the inputs, which must be passed in the order of the built transaction
+fn verify(
+    dummy_loader: DummyDataLoader,
+    tx: &TransactionView,
+    input_cells: Vec<CellMeta>,
+) -> Result<u64, ckb_error::Error> {
+    let builder = tx.as_advanced_builder().set_inputs(vec![]);
+    let verifier = complete_tx(dummy_loader, builder, input_cells).0;
+    verifier.verify(MAX_CYCLES)
+}
+
+fn capacity(cells: impl Iterator<Item = CellOutput>) -> u64 {
+    cells
+        .map(|cell| Unpack::<u64>::unpack(&cell.capacity()))
+        .sum()
+}
+
+#[test]
+fn test_upgrade_tx_balances_fee() {
+    let Upgrade {
+        mut dummy_loader,
+        input_cell_meta,
+        builder,
+    } = upgrade();
+    let fee_cells: Vec<(CellMeta, FeeCell)> =
+        (0..3).map(|_| fee_cell(&mut dummy_loader, 30)).collect();
+    let builder = fee_cells.iter().fold(builder, |builder, (_, fee_cell)| {
+        builder.fee_cell(fee_cell.clone())
+    });
+
+    // A change cell with always success lock occupies 41 CKBytes, so one
+    // fee cell is not enough
+    let upgrade_tx = builder.build().expect("build upgrade tx");
+    assert_eq!(upgrade_tx.fee_cells, 2);
+    let tx = &upgrade_tx.tx;
+    assert_eq!(tx.inputs().len(), 3);
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(
+        tx.outputs().get(1).unwrap().lock(),
+        fee_cells[0].1.output.lock()
+    );
+    // Only the first fee cell of the lock group carries a placeholder
+    assert_eq!(tx.witnesses().len(), 2);
+    let placeholder = WitnessArgs::from_slice(&tx.witnesses().get(1).unwrap().raw_data()).unwrap();
+    assert_eq!(placeholder.lock().to_opt().unwrap().raw_data().len(), 65);
+
+    assert_eq!(upgrade_tx.size, tx.data().serialized_size_in_block());
+    assert_eq!(
+        upgrade_tx.fee,
+        (upgrade_tx.size as u64 * DEFAULT_FEE_RATE).div_ceil(1000)
+    );
+    let inputs = capacity(
+        std::iter::once(input_cell_meta.cell_output.clone())
+            .chain(
+                fee_cells
+                    .iter()
+                    .map(|(_, fee_cell)| fee_cell.output.clone()),
+            )
+            .take(3),
+    );
+    assert_eq!(inputs, capacity(tx.outputs().into_iter()) + upgrade_tx.fee);
+
+    let input_cells = std::iter::once(input_cell_meta)
+        .chain(fee_cells.into_iter().map(|(cell_meta, _)| cell_meta))
+        .take(3)
+        .collect();
+    verify(dummy_loader, tx, input_cells).expect("pass verification");
+}
+
+#[test]
+fn test_upgrade_tx_fee_rate() {
+    let Upgrade {
+        mut dummy_loader,
+        builder,
+        ..
+    } = upgrade();
+    let (_, fee_cell) = fee_cell(&mut dummy_loader, 1000);
+    let builder = builder.fee_cell(fee_cell);
+
+    let default_fee = builder.build().unwrap();
+    let higher_fee = builder.clone().fee_rate(5000).build().unwrap();
+    assert_eq!(higher_fee.size, default_fee.size);
+    assert_eq!(
+        higher_fee.fee,
+        (higher_fee.size as u64 * 5000).div_ceil(1000)
+    );
+    assert!(higher_fee.fee > default_fee.fee);
+}
+
+#[test]
+fn test_upgrade_tx_change_lock() {
+    let Upgrade {
+        mut dummy_loader,
+        input_cell_meta,
+        builder,
+    } = upgrade();
+    let (fee_cell_meta, mut fee_cell) = fee_cell(&mut dummy_loader, 1000);
+    fee_cell.witness_lock_size = None;
+    let change_lock = random_type_id_script();
+    let upgrade_tx = builder
+        .fee_cell(fee_cell)
+        .change_lock(change_lock.clone())
+        .build()
+        .expect("build upgrade tx");
+
+    let tx = &upgrade_tx.tx;
+    assert_eq!(tx.outputs().get(1).unwrap().lock(), change_lock);
+    assert_eq!(tx.witnesses().len(), 1);
+    verify(dummy_loader, tx, vec![input_cell_meta, fee_cell_meta]).expect("pass verification");
+}
+
+#[test]
+fn test_upgrade_tx_insufficient_capacity() {
+    let Upgrade {
+        mut dummy_loader,
+        builder,
+        ..
+    } = upgrade();
+    assert_eq!(
+        builder.build().unwrap_err(),
+        UpgradeTxError::MissingChangeLock
+    );
+
+    let (_, fee_cell) = fee_cell(&mut dummy_loader, 30);
+    let err = builder.fee_cell(fee_cell).build().unwrap_err();
+    assert!(matches!(
+        err,
+        UpgradeTxError::InsufficientCapacity { required, available }
+            if available == 130 * 100_000_000 && required > 141 * 100_000_000
+    ));
+}
+
+#[test]
+fn test_upgrade_tx_data_mismatch() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let input_cell_meta = zero_lock_cell(&mut dummy_loader, &vec![1u8; 100].into(), None);
+    let output_cell_meta = zero_lock_cell(&mut dummy_loader, &vec![2u8; 100].into(), None);
+    let leaf = UpgradeLeaf::new(
+        input_cell_meta.out_point.clone(),
+        output_cell_meta.cell_output.clone(),
+        &output_cell_meta.mem_cell_data.clone().unwrap(),
+    );
+    let tree = UpgradeTree::build(&[leaf.clone()]).expect("build tree");
+    let header_dep = header(&mut dummy_loader, &tree.root());
+    let data = Bytes::from(vec![3u8; 100]);
+    let builder = UpgradeTxBuilder::new(
+        leaf.clone(),
+        &input_cell_meta.cell_output,
+        data.clone(),
+        tree.proof(0, &[0], &leaf).expect("proof"),
+        header_dep,
+    )
+    .change_lock(random_type_id_script());
+
+    assert_eq!(
+        builder.build().unwrap_err(),
+        UpgradeTxError::DataHashMismatch {
+            expected: leaf.output_data_hash,
+            actual: CellOutput::calc_data_hash(&data),
+        }
+    );
+}
+
+#[test]
+fn test_upgrade_tx_header_dep_count() {
+    let Upgrade { builder, .. } = upgrade();
+    let builder = builder.change_lock(random_type_id_script());
+    builder.build().expect("build upgrade tx");
+
+    let err = builder
+        .header_dep(random_out_point().tx_hash())
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        UpgradeTxError::HeaderDepCount {
+            expected: 1,
+            actual: 2
+        }
+    );
+}
+
+#[test]
+fn test_upgrade_tx_header_quorum() {
+    // Header quorum of 2 expects 2 header indices in the proof
+    let Upgrade { builder, .. } = upgrade_with_args(vec![2u8].into());
+    let err = builder
+        .change_lock(random_type_id_script())
+        .build()
+        .unwrap_err();
+    assert_eq!(err, UpgradeTxError::InvalidProof { header_quorum: 2 });
+}
+
+#[test]
+fn test_upgrade_tx_beyond_inputs_location() {
+    let Upgrade {
+        mut dummy_loader,
+        input_cell_meta,
+        builder,
+    } = upgrade_with_args(vec![1u8, 2].into());
+    let (fee_cell_meta, fee_cell) = fee_cell(&mut dummy_loader, 1000);
+    let upgrade_tx = builder
+        .fee_cell(fee_cell)
+        .build()
+        .expect("build upgrade tx");
+
+    // Zero lock witness is empty, followed by the fee cell placeholder and
+    // the upgrade witness beyond inputs
+    let tx = &upgrade_tx.tx;
+    assert_eq!(tx.inputs().len(), 2);
+    assert_eq!(tx.witnesses().len(), 3);
+    assert!(tx.witnesses().get(0).unwrap().raw_data().is_empty());
+    let placeholder = WitnessArgs::from_slice(&tx.witnesses().get(1).unwrap().raw_data()).unwrap();
+    assert_eq!(placeholder.lock().to_opt().unwrap().raw_data().len(), 65);
+    verify(dummy_loader, tx, vec![input_cell_meta, fee_cell_meta]).expect("pass verification");
+}
+
+#[test]
+fn test_upgrade_tx_index_location() {
+    let Upgrade {
+        mut dummy_loader,
+        input_cell_meta,
+        builder,
+    } = upgrade_with_args(vec![1u8, 1, 5, 0, 0, 0].into());
+    let (fee_cell_meta, fee_cell) = fee_cell(&mut dummy_loader, 1000);
+    let upgrade_tx = builder
+        .fee_cell(fee_cell)
+        .build()
+        .expect("build upgrade tx");
+
+    let tx = &upgrade_tx.tx;
+    assert_eq!(tx.witnesses().len(), 6);
+    assert!(tx.witnesses().get(0).unwrap().raw_data().is_empty());
+    assert!(tx.witnesses().get(4).unwrap().raw_data().is_empty());
+    verify(dummy_loader, tx, vec![input_cell_meta, fee_cell_meta]).expect("pass verification");
+}
+
+#[test]
+fn test_upgrade_tx_index_location_taken() {
+    // Witness 1 belongs to the fee cell input
+    let Upgrade {
+        mut dummy_loader,
+        builder,
+        ..
+    } = upgrade_with_args(vec![1u8, 1, 1, 0, 0, 0].into());
+    let (_, fee_cell) = fee_cell(&mut dummy_loader, 1000);
+    assert_eq!(
+        builder.fee_cell(fee_cell).build().unwrap_err(),
+        UpgradeTxError::WitnessIndexTaken(1)
+    );
+}
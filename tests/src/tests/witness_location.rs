@@ -0,0 +1,279 @@
+use super::*;
+
+fn index_args(index: u32) -> Bytes {
+    let mut args = vec![1, WITNESS_LOCATION_INDEX];
+    args.extend_from_slice(&index.to_le_bytes());
+    Bytes::from(args)
+}
+
+struct LocationUpgrade {
+    input_cell_meta: CellMeta,
+    output_cell_meta: CellMeta,
+    header_dep: Byte32,
+    proof_witness: Bytes,
+}
+
+fn location_upgrade(
+    dummy_loader: &mut DummyDataLoader,
+    args: Bytes,
+    input_type: Option<Bytes>,
+) -> LocationUpgrade {
+    let (input_cell_meta, output_cell_meta) = contract_upgrade_cells(dummy_loader, args);
+
+    let (root, proof_witness) = build_merkle_root_n_proof(
+        &[(&input_cell_meta, &output_cell_meta)],
+        0,
+        0,
+        input_type,
+        None,
+    );
+    let header_dep = header(dummy_loader, &root);
+    LocationUpgrade {
+        input_cell_meta,
+        output_cell_meta,
+        header_dep,
+        proof_witness,
+    }
+}
+
+fn verify_upgrade(
+    dummy_loader: DummyDataLoader,
+    upgrade: &LocationUpgrade,
+    witnesses: Vec<Bytes>,
+    input_cells: Vec<CellMeta>,
+) -> Result<u64, ckb_error::Error> {
+    let builder = TransactionBuilder::default()
+        .output(upgrade.output_cell_meta.cell_output.clone())
+        .output_data(
+            upgrade
+                .output_cell_meta
+                .mem_cell_data
+                .clone()
+                .unwrap()
+                .pack(),
+        )
+        .header_dep(upgrade.header_dep.clone())
+        .witnesses(witnesses.into_iter().map(|w| w.pack()));
+
+    let verifier = complete_tx(dummy_loader, builder, input_cells).0;
+    verifier.verify(MAX_CYCLES)
+}
+
+fn assert_zero_lock_failure(
+    verify_result: Result<u64, ckb_error::Error>,
+    upgrade: &LocationUpgrade,
+    input_index: usize,
+) {
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&upgrade.input_cell_meta.cell_output.lock(), -61)
+            .input_lock_script(input_index),
+    );
+}
+
+#[test]
+fn test_explicit_group_input_location_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let args = Bytes::from(vec![1, WITNESS_LOCATION_GROUP_INPUT]);
+    let upgrade = location_upgrade(&mut dummy_loader, args, None);
+    let input_cell2 = always_success_cell(&mut dummy_loader, 150);
+
+    let verify_result = verify_upgrade(
+        dummy_loader,
+        &upgrade,
+        vec![Bytes::new(), upgrade.proof_witness.clone()],
+        vec![input_cell2, upgrade.input_cell_meta.clone()],
+    );
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_index_location_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let upgrade = location_upgrade(&mut dummy_loader, index_args(3), None);
+    let input_cell2 = always_success_cell(&mut dummy_loader, 150);
+
+    let verify_result = verify_upgrade(
+        dummy_loader,
+        &upgrade,
+        vec![
+            Bytes::new(),
+            Bytes::new(),
+            Bytes::new(),
+            upgrade.proof_witness.clone(),
+        ],
+        vec![upgrade.input_cell_meta.clone(), input_cell2],
+    );
+    verify_result.expect("pass verification");
+}
+
+// Nothing commits to zero lock's own witness when the proof is elsewhere,
+// so it must be left empty.
+#[test]
+fn test_index_location_non_empty_group_witness_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let upgrade = location_upgrade(&mut dummy_loader, index_args(3), None);
+    let input_cell2 = always_success_cell(&mut dummy_loader, 150);
+
+    let verify_result = verify_upgrade(
+        dummy_loader,
+        &upgrade,
+        vec![
+            Bytes::from(vec![0xffu8; 10]),
+            Bytes::new(),
+            Bytes::new(),
+            upgrade.proof_witness.clone(),
+        ],
+        vec![upgrade.input_cell_meta.clone(), input_cell2],
+    );
+    assert_zero_lock_failure(verify_result, &upgrade, 0);
+}
+
+#[test]
+fn test_beyond_inputs_location_non_empty_group_witness_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let args = Bytes::from(vec![1, WITNESS_LOCATION_BEYOND_INPUTS]);
+    let upgrade = location_upgrade(&mut dummy_loader, args, None);
+    let input_cell2 = always_success_cell(&mut dummy_loader, 150);
+
+    let verify_result = verify_upgrade(
+        dummy_loader,
+        &upgrade,
+        vec![
+            Bytes::new(),
+            WitnessArgs::new_builder().build().as_bytes(),
+            upgrade.proof_witness.clone(),
+        ],
+        vec![input_cell2, upgrade.input_cell_meta.clone()],
+    );
+    assert_zero_lock_failure(verify_result, &upgrade, 1);
+}
+
+#[test]
+fn test_index_location_at_own_input_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let upgrade = location_upgrade(&mut dummy_loader, index_args(1), None);
+    let input_cell2 = always_success_cell(&mut dummy_loader, 150);
+
+    let verify_result = verify_upgrade(
+        dummy_loader,
+        &upgrade,
+        vec![Bytes::new(), upgrade.proof_witness.clone()],
+        vec![input_cell2, upgrade.input_cell_meta.clone()],
+    );
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_index_location_at_other_input_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let upgrade = location_upgrade(&mut dummy_loader, index_args(1), None);
+    let input_cell2 = always_success_cell(&mut dummy_loader, 150);
+
+    let verify_result = verify_upgrade(
+        dummy_loader,
+        &upgrade,
+        vec![Bytes::new(), upgrade.proof_witness.clone()],
+        vec![upgrade.input_cell_meta.clone(), input_cell2],
+    );
+    assert_zero_lock_failure(verify_result, &upgrade, 0);
+}
+
+#[test]
+fn test_index_location_missing_witness_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let upgrade = location_upgrade(&mut dummy_loader, index_args(5), None);
+
+    let verify_result = verify_upgrade(
+        dummy_loader,
+        &upgrade,
+        vec![Bytes::new(), upgrade.proof_witness.clone()],
+        vec![upgrade.input_cell_meta.clone()],
+    );
+    assert_zero_lock_failure(verify_result, &upgrade, 0);
+}
+
+#[test]
+fn test_beyond_inputs_location_upgrade() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let args = Bytes::from(vec![1, WITNESS_LOCATION_BEYOND_INPUTS]);
+    let upgrade = location_upgrade(&mut dummy_loader, args, None);
+    let input_cell2 = always_success_cell(&mut dummy_loader, 150);
+    let input_cell3 = always_success_cell(&mut dummy_loader, 200);
+
+    let verify_result = verify_upgrade(
+        dummy_loader,
+        &upgrade,
+        vec![
+            Bytes::new(),
+            Bytes::new(),
+            Bytes::new(),
+            upgrade.proof_witness.clone(),
+            Bytes::new(),
+        ],
+        vec![input_cell2, upgrade.input_cell_meta.clone(), input_cell3],
+    );
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_beyond_inputs_location_missing_witness_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let args = Bytes::from(vec![1, WITNESS_LOCATION_BEYOND_INPUTS]);
+    let upgrade = location_upgrade(&mut dummy_loader, args, None);
+    let input_cell2 = always_success_cell(&mut dummy_loader, 150);
+
+    // The proof sits in zero lock's own witness, which is not where args
+    // point to
+    let verify_result = verify_upgrade(
+        dummy_loader,
+        &upgrade,
+        vec![upgrade.proof_witness.clone(), Bytes::new()],
+        vec![upgrade.input_cell_meta.clone(), input_cell2],
+    );
+    assert_zero_lock_failure(verify_result, &upgrade, 0);
+}
+
+#[test]
+fn test_index_location_modified_remainder_fails_verification() {
+    let mut dummy_loader = DummyDataLoader::default();
+    let input_type = Bytes::from(vec![7u8; 64]);
+    let upgrade = location_upgrade(&mut dummy_loader, index_args(1), Some(input_type));
+
+    let proof_witness = {
+        let witness_args = WitnessArgs::new_unchecked(upgrade.proof_witness.clone());
+        witness_args
+            .as_builder()
+            .input_type(Some(Bytes::from(vec![8u8; 64])).pack())
+            .build()
+            .as_bytes()
+    };
+    let verify_result = verify_upgrade(
+        dummy_loader,
+        &upgrade,
+        vec![Bytes::new(), proof_witness],
+        vec![upgrade.input_cell_meta.clone()],
+    );
+    assert_zero_lock_failure(verify_result, &upgrade, 0);
+}
+
+#[test]
+fn test_invalid_witness_location_args_fails_verification() {
+    for args in [
+        vec![1, 3],
+        vec![1, WITNESS_LOCATION_INDEX, 1, 0, 0],
+        vec![1, WITNESS_LOCATION_INDEX, 1, 0, 0, 0, 0],
+        vec![1, WITNESS_LOCATION_BEYOND_INPUTS, 0],
+    ] {
+        let mut dummy_loader = DummyDataLoader::default();
+        let upgrade = location_upgrade(&mut dummy_loader, Bytes::from(args), None);
+
+        let verify_result = verify_upgrade(
+            dummy_loader,
+            &upgrade,
+            vec![upgrade.proof_witness.clone()],
+            vec![upgrade.input_cell_meta.clone()],
+        );
+        assert_zero_lock_failure(verify_result, &upgrade, 0);
+    }
+}
@@ -0,0 +1,150 @@
+use super::*;
+use ckb_hash::new_blake2b;
+
+// Tests for WitnessArgsVisitor, exercised via the witness-digest contract
+// in tests/contracts: it hashes lock and output_type fields of the first
+// witness in script group, and compares the digest against script args.
+fn witness_digest(lock: Option<&Bytes>, output_type: Option<&Bytes>) -> Bytes {
+    let mut hasher = new_blake2b();
+    for field in [lock, output_type] {
+        match field {
+            Some(data) => {
+                hasher.update(&[1u8]);
+                hasher.update(&(data.len() as u32).to_le_bytes());
+                hasher.update(data);
+            }
+            None => hasher.update(&[0u8]),
+        }
+    }
+    let mut digest = [0u8; 32];
+    hasher.finalize(&mut digest);
+    Bytes::from(digest.to_vec())
+}
+
+fn random_bytes(length: usize, seed: u64) -> Bytes {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut bytes = vec![0u8; length];
+    rng.fill(&mut bytes[..]);
+    Bytes::from(bytes)
+}
+
+fn verify_witness_digest(
+    args: Bytes,
+    lock: Option<Bytes>,
+    input_type: Option<Bytes>,
+    output_type: Option<Bytes>,
+) -> (Result<u64, ckb_error::Error>, Script) {
+    let mut dummy_loader = DummyDataLoader::default();
+    let digest_cell_meta = script_cell(&mut dummy_loader, &WITNESS_DIGEST_BIN);
+    let lock_script = Script::new_builder()
+        .code_hash(CellOutput::calc_data_hash(&WITNESS_DIGEST_BIN))
+        .hash_type(ScriptHashType::Data2.into())
+        .args(args.pack())
+        .build();
+    let input_cell_meta = {
+        let cell = CellOutput::new_builder()
+            .lock(lock_script.clone())
+            .capacity(Capacity::bytes(200).expect("capacity").pack())
+            .build();
+        let cell_meta = CellMetaBuilder::from_cell_output(cell, Bytes::new())
+            .out_point(random_out_point())
+            .build();
+        insert_cell(&mut dummy_loader, &cell_meta);
+        cell_meta
+    };
+    let output_cell_meta = always_success_cell(&mut dummy_loader, 100);
+
+    let witness = WitnessArgs::new_builder()
+        .lock(lock.pack())
+        .input_type(input_type.pack())
+        .output_type(output_type.pack())
+        .build();
+
+    let builder = TransactionBuilder::default()
+        .cell_dep(code_cell_dep(&digest_cell_meta))
+        .output(output_cell_meta.cell_output.clone())
+        .output_data(Bytes::new().pack())
+        .witness(witness.as_bytes().pack());
+
+    let verifier = complete_tx(dummy_loader, builder, vec![input_cell_meta]).0;
+    (verifier.verify(MAX_CYCLES), lock_script)
+}
+
+#[test]
+fn test_witness_visitor_digest() {
+    let lock = random_bytes(100, 1);
+    let output_type = random_bytes(50, 2);
+    let args = witness_digest(Some(&lock), Some(&output_type));
+
+    let (verify_result, _) = verify_witness_digest(args, Some(lock), None, Some(output_type));
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_witness_visitor_absent_lock() {
+    let output_type = random_bytes(50, 3);
+    let args = witness_digest(None, Some(&output_type));
+
+    let (verify_result, _) = verify_witness_digest(args, None, None, Some(output_type));
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_witness_visitor_skips_input_type() {
+    // input_type is left to the default method, so it does not affect the
+    // digest, no matter how large it is.
+    let lock = random_bytes(10, 4);
+    let output_type = random_bytes(10, 5);
+    let args = witness_digest(Some(&lock), Some(&output_type));
+
+    let (verify_result, _) = verify_witness_digest(
+        args,
+        Some(lock),
+        Some(random_bytes(200000, 6)),
+        Some(output_type),
+    );
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_witness_visitor_large_fields() {
+    let lock = random_bytes(100000, 7);
+    let output_type = random_bytes(32769, 8);
+    let args = witness_digest(Some(&lock), Some(&output_type));
+
+    let (verify_result, _) = verify_witness_digest(
+        args,
+        Some(lock),
+        Some(random_bytes(70000, 9)),
+        Some(output_type),
+    );
+    verify_result.expect("pass verification");
+}
+
+#[test]
+fn test_witness_visitor_digest_mismatch_fails_verification() {
+    let lock = random_bytes(100, 10);
+    let output_type = random_bytes(50, 11);
+    let args = witness_digest(Some(&output_type), Some(&lock));
+
+    let (verify_result, lock_script) =
+        verify_witness_digest(args, Some(lock), None, Some(output_type));
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&lock_script, 3).input_lock_script(0),
+    );
+}
+
+#[test]
+fn test_witness_visitor_error_aborts_reading() {
+    // The visitor rejects an absent output_type, the error code returned by
+    // the visitor is propagated by read_witness_args.
+    let lock = random_bytes(100, 12);
+    let args = witness_digest(Some(&lock), None);
+
+    let (verify_result, lock_script) = verify_witness_digest(args, Some(lock), None, None);
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::validation_failure(&lock_script, 4).input_lock_script(0),
+    );
+}